@@ -0,0 +1,15 @@
+#!/bin/sh
+# Checks that the crate builds for wasm32-unknown-unknown with the features
+# which are supported there.
+#
+# `mmap`, `rayon`, `libc`, `sqlx`, and `zstd` are not supported on
+# wasm32-unknown-unknown.
+
+set -eu
+
+FEATURES="extended ipnet maxminddb multiaddr nom rand smallvec tracing ufmt wasm-bindgen winnow"
+
+rustup target add wasm32-unknown-unknown
+cargo check --target wasm32-unknown-unknown --no-default-features
+cargo check --target wasm32-unknown-unknown --features "$FEATURES"
+cargo clippy --target wasm32-unknown-unknown --features "$FEATURES" -- -D warnings
@@ -0,0 +1,125 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Anonymization of compact addresses for logging and metrics.
+
+use crate::{siphash, CompactAddr, CompactAddrV4, CompactAddrV6};
+
+impl CompactAddrV4 {
+    /// Returns the address with the host bits of the IP address zeroed.
+    ///
+    /// Only the /24 network prefix and the port are kept.
+    pub fn anonymize(&self) -> Self {
+        let mut a = self.0;
+        a[3] = 0;
+        Self(a)
+    }
+
+    /// Returns a pseudonym derived from a keyed hash of the address.
+    ///
+    /// The same address and key always produce the same pseudonym, so
+    /// pseudonyms can be correlated without revealing the address. The
+    /// pseudonym cannot be reversed without the key.
+    pub fn anonymize_keyed(&self, key: &[u8; 16]) -> Self {
+        let hash = siphash::hash(key, &self.0).to_be_bytes();
+        let mut a: [u8; 6] = [0; 6];
+        a.copy_from_slice(&hash[0..6]);
+        Self(a)
+    }
+}
+
+impl CompactAddrV6 {
+    /// Returns the address with the host bits of the IP address zeroed.
+    ///
+    /// Only the /48 network prefix and the port are kept.
+    pub fn anonymize(&self) -> Self {
+        let mut a = self.0;
+        for b in &mut a[6..16] {
+            *b = 0;
+        }
+        Self(a)
+    }
+
+    /// Returns a pseudonym derived from a keyed hash of the address.
+    ///
+    /// The same address and key always produce the same pseudonym, so
+    /// pseudonyms can be correlated without revealing the address. The
+    /// pseudonym cannot be reversed without the key.
+    pub fn anonymize_keyed(&self, key: &[u8; 16]) -> Self {
+        let mut input: [u8; 19] = [0; 19];
+        input[1..19].copy_from_slice(&self.0);
+
+        let mut a: [u8; 18] = [0; 18];
+        for (i, chunk) in a.chunks_mut(8).enumerate() {
+            input[0] = i as u8;
+            let hash = siphash::hash(key, &input).to_be_bytes();
+            chunk.copy_from_slice(&hash[0..chunk.len()]);
+        }
+        Self(a)
+    }
+}
+
+impl CompactAddr {
+    /// Returns the address with the host bits of the IP address zeroed.
+    ///
+    /// IPv4 addresses keep the /24 network prefix and IPv6 addresses keep the
+    /// /48 network prefix. The port is kept.
+    pub fn anonymize(&self) -> Self {
+        match self {
+            CompactAddr::V4(addr) => CompactAddr::V4(addr.anonymize()),
+            CompactAddr::V6(addr) => CompactAddr::V6(addr.anonymize()),
+        }
+    }
+
+    /// Returns a pseudonym derived from a keyed hash of the address.
+    ///
+    /// The pseudonym has the same address family as the address.
+    pub fn anonymize_keyed(&self, key: &[u8; 16]) -> Self {
+        match self {
+            CompactAddr::V4(addr) => CompactAddr::V4(addr.anonymize_keyed(key)),
+            CompactAddr::V6(addr) => CompactAddr::V6(addr.anonymize_keyed(key)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anonymize_v4_keeps_prefix_and_port() {
+        let addr = CompactAddrV4::new([192, 0, 2, 77, 0x1a, 0xe1]);
+        assert_eq!(addr.anonymize().as_bytes(), &[192, 0, 2, 0, 0x1a, 0xe1]);
+    }
+
+    #[test]
+    fn anonymize_v6_keeps_prefix_and_port() {
+        let mut bytes: [u8; 18] = [0xff; 18];
+        bytes[16] = 0x1a;
+        bytes[17] = 0xe1;
+        let anonymized = CompactAddrV6::new(bytes).anonymize();
+        assert_eq!(anonymized.as_bytes()[..6], [0xff; 6]);
+        assert_eq!(anonymized.as_bytes()[6..16], [0; 10]);
+        assert_eq!(anonymized.as_bytes()[16..], [0x1a, 0xe1]);
+    }
+
+    #[test]
+    fn anonymize_keyed_is_stable_per_key() {
+        let addr = CompactAddr::V4(CompactAddrV4::new([192, 0, 2, 77, 0x1a, 0xe1]));
+        let other = CompactAddr::V4(CompactAddrV4::new([192, 0, 2, 78, 0x1a, 0xe1]));
+        let key = [1; 16];
+        assert_eq!(addr.anonymize_keyed(&key), addr.anonymize_keyed(&key));
+        assert_ne!(addr.anonymize_keyed(&key), addr.anonymize_keyed(&[2; 16]));
+        assert_ne!(addr.anonymize_keyed(&key), other.anonymize_keyed(&key));
+        assert_ne!(addr.anonymize_keyed(&key), addr);
+
+        let v6 = CompactAddr::V6(CompactAddrV6::new([3; 18]));
+        assert!(v6.anonymize_keyed(&key).is_ipv6());
+        assert_eq!(v6.anonymize_keyed(&key), v6.anonymize_keyed(&key));
+    }
+}
@@ -0,0 +1,268 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Compressed bitmap set of IPv4 addresses.
+//!
+//! [`Ipv4BitmapSet`] is a roaring-style bitmap. Keys are split into a high
+//! part, which selects a container, and a low 16-bit part stored in the
+//! container. Sparse containers are sorted arrays of 2 bytes per entry and
+//! dense containers are 8 KiB bitmaps.
+//!
+//! When only IP addresses are tracked, the high part is the upper 16 bits of
+//! the IP address. When ports are included, the high part is the IP address
+//! and the low part is the port.
+
+use crate::{
+    blob::{self, DecodeError, V4_LEN},
+    CompactAddrV4,
+};
+use std::collections::BTreeMap;
+
+/// The number of entries above which an array container becomes a bitmap.
+const ARRAY_MAX_LEN: usize = 4096;
+
+const BITMAP_WORDS: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Container {
+    Array(Vec<u16>),
+    Bitmap(Box<[u64; BITMAP_WORDS]>),
+}
+
+impl Container {
+    fn insert(&mut self, low: u16) -> bool {
+        match self {
+            Container::Array(values) => match values.binary_search(&low) {
+                Ok(_) => false,
+                Err(idx) => {
+                    values.insert(idx, low);
+                    if values.len() > ARRAY_MAX_LEN {
+                        let mut bits = Box::new([0; BITMAP_WORDS]);
+                        for value in values.iter() {
+                            bits[usize::from(*value / 64)] |= 1 << (value % 64);
+                        }
+                        *self = Container::Bitmap(bits);
+                    }
+                    true
+                }
+            },
+            Container::Bitmap(bits) => {
+                let word = &mut bits[usize::from(low / 64)];
+                let mask = 1 << (low % 64);
+                let inserted = *word & mask == 0;
+                *word |= mask;
+                inserted
+            }
+        }
+    }
+
+    fn contains(&self, low: u16) -> bool {
+        match self {
+            Container::Array(values) => values.binary_search(&low).is_ok(),
+            Container::Bitmap(bits) => bits[usize::from(low / 64)] & (1 << (low % 64)) != 0,
+        }
+    }
+}
+
+/// A compressed set of IPv4 addresses, optionally including the port.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Ipv4BitmapSet {
+    with_port: bool,
+    containers: BTreeMap<u32, Container>,
+    len: usize,
+}
+
+impl Ipv4BitmapSet {
+    /// Instantiates an empty set of IP addresses.
+    ///
+    /// Ports are ignored.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Instantiates an empty set of IP addresses and ports.
+    pub fn with_port() -> Self {
+        Self {
+            with_port: true,
+            ..Self::default()
+        }
+    }
+
+    /// Returns true if ports are part of the entries.
+    pub fn includes_port(&self) -> bool {
+        self.with_port
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts the address.
+    ///
+    /// Returns true if the address was not in the set.
+    pub fn insert<A: Into<CompactAddrV4>>(&mut self, addr: A) -> bool {
+        let (high, low) = self.split(&addr.into());
+        let inserted = self
+            .containers
+            .entry(high)
+            .or_insert_with(|| Container::Array(Vec::new()))
+            .insert(low);
+        if inserted {
+            self.len += 1;
+        }
+        inserted
+    }
+
+    /// Returns true if the address is in the set.
+    pub fn contains<A: Into<CompactAddrV4>>(&self, addr: A) -> bool {
+        let (high, low) = self.split(&addr.into());
+        self.containers
+            .get(&high)
+            .is_some_and(|container| container.contains(low))
+    }
+
+    /// Inserts every address in a blob of compact IPv4 addresses.
+    ///
+    /// Returns the number of addresses which were not in the set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blob length is not a multiple of the compact
+    /// address length. No addresses are inserted.
+    pub fn import_v4(&mut self, blob: &[u8]) -> Result<usize, DecodeError> {
+        let (entries, remainder) = blob::as_chunks_v4(blob);
+        if !remainder.is_empty() {
+            return Err(DecodeError::invalid_length(blob.len(), V4_LEN));
+        }
+        Ok(entries
+            .iter()
+            .filter(|entry| self.insert(CompactAddrV4::new(**entry)))
+            .count())
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.containers.clear();
+        self.len = 0;
+    }
+
+    fn split(&self, addr: &CompactAddrV4) -> (u32, u16) {
+        let bytes = addr.as_bytes();
+        if self.with_port {
+            (
+                u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                u16::from_be_bytes([bytes[4], bytes[5]]),
+            )
+        } else {
+            (
+                u32::from(u16::from_be_bytes([bytes[0], bytes[1]])),
+                u16::from_be_bytes([bytes[2], bytes[3]]),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    fn addr(ip: u32, port: u16) -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::from(ip), port)
+    }
+
+    #[test]
+    fn array_promotes_to_bitmap() {
+        let mut set = Ipv4BitmapSet::new();
+        let base = u32::from(Ipv4Addr::new(10, 1, 0, 0));
+        for n in 0..ARRAY_MAX_LEN as u32 {
+            assert!(set.insert(addr(base + n * 3, 80)));
+        }
+        assert_eq!(set.containers.len(), 1);
+        assert!(matches!(
+            set.containers.values().next(),
+            Some(Container::Array(_))
+        ));
+        assert_eq!(set.len(), ARRAY_MAX_LEN);
+
+        assert!(set.insert(addr(base + 1, 80)));
+        assert!(matches!(
+            set.containers.values().next(),
+            Some(Container::Bitmap(_))
+        ));
+        assert_eq!(set.len(), ARRAY_MAX_LEN + 1);
+
+        assert!((0..ARRAY_MAX_LEN as u32).all(|n| set.contains(addr(base + n * 3, 80))));
+        assert!(set.contains(addr(base + 1, 80)));
+        assert!(!set.contains(addr(base + 2, 80)));
+        assert!(!set.insert(addr(base + 3, 80)));
+        assert!(set.insert(addr(base + 2, 80)));
+        assert_eq!(set.len(), ARRAY_MAX_LEN + 2);
+    }
+
+    #[test]
+    fn port_less_keying_ignores_port() {
+        let mut set = Ipv4BitmapSet::new();
+        assert!(!set.includes_port());
+        assert!(set.insert(addr(1, 80)));
+        assert!(!set.insert(addr(1, 81)));
+        assert!(set.contains(addr(1, 443)));
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn with_port_keys_by_port() {
+        let mut set = Ipv4BitmapSet::with_port();
+        assert!(set.includes_port());
+        assert!(set.insert(addr(1, 80)));
+        assert!(set.insert(addr(1, 81)));
+        assert!(!set.contains(addr(1, 443)));
+        assert!(set.contains(addr(1, 81)));
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.containers.len(), 1);
+    }
+
+    #[test]
+    fn import_v4() {
+        let mut blob = Vec::new();
+        for a in &[addr(1, 80), addr(2, 80), addr(1, 80)] {
+            blob.extend_from_slice(CompactAddrV4::from(*a).as_bytes());
+        }
+        let mut set = Ipv4BitmapSet::with_port();
+        assert_eq!(set.import_v4(&blob), Ok(2));
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.import_v4(&blob), Ok(0));
+    }
+
+    #[test]
+    fn import_v4_ragged_blob() {
+        let mut blob = CompactAddrV4::from(addr(1, 80)).as_bytes().to_vec();
+        blob.extend_from_slice(&[1, 2, 3]);
+        let mut set = Ipv4BitmapSet::new();
+        assert_eq!(
+            set.import_v4(&blob),
+            Err(DecodeError::invalid_length(9, V4_LEN))
+        );
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn clear() {
+        let mut set = Ipv4BitmapSet::new();
+        set.insert(addr(1, 80));
+        set.clear();
+        assert!(set.is_empty());
+        assert!(!set.contains(addr(1, 80)));
+    }
+}
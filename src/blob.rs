@@ -0,0 +1,1171 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Blobs of concatenated compact addresses.
+//!
+//! Trackers, PEX messages, and DHT responses transmit lists of peers as a
+//! single byte string of concatenated compact addresses (e.g. the `peers` and
+//! `peers6` keys).
+
+use crate::{
+    CompactAddr, CompactAddrV4, CompactAddrV4Info, CompactAddrV4Ref, CompactAddrV6,
+    CompactAddrV6Info, CompactAddrV6Ref,
+};
+use std::{
+    convert::TryFrom,
+    error, fmt,
+    iter::FromIterator,
+    net::{SocketAddrV4, SocketAddrV6},
+    ops::Index,
+    slice::{Chunks, ChunksExact},
+};
+
+/// The length of a compact IPv4 address.
+pub const V4_LEN: usize = 6;
+
+/// The length of a compact IPv6 address.
+pub const V6_LEN: usize = 18;
+
+/// Returns the blob of the concatenated compact IPv4 addresses.
+pub fn encode_v4<I>(addrs: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = SocketAddrV4>,
+{
+    let addrs = addrs.into_iter();
+    let mut blob = Vec::with_capacity(addrs.size_hint().0 * V4_LEN);
+    for addr in addrs {
+        blob.extend_from_slice(&addr.to_compact_address());
+    }
+    blob
+}
+
+/// Returns the blob of the concatenated compact IPv6 addresses.
+pub fn encode_v6<I>(addrs: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = SocketAddrV6>,
+{
+    let addrs = addrs.into_iter();
+    let mut blob = Vec::with_capacity(addrs.size_hint().0 * V6_LEN);
+    for addr in addrs {
+        blob.extend_from_slice(&addr.to_compact_address());
+    }
+    blob
+}
+
+/// Returns the blobs of the concatenated compact IPv4 and IPv6 addresses.
+///
+/// The blobs are suitable for the `peers` and `peers6` keys.
+pub fn partition_encode<I>(addrs: I) -> (Vec<u8>, Vec<u8>)
+where
+    I: IntoIterator,
+    I::Item: Into<CompactAddr>,
+{
+    let mut v4 = Vec::new();
+    let mut v6 = Vec::new();
+    for addr in addrs {
+        match addr.into() {
+            CompactAddr::V4(addr) => v4.extend_from_slice(addr.as_bytes()),
+            CompactAddr::V6(addr) => v6.extend_from_slice(addr.as_bytes()),
+        }
+    }
+    (v4, v6)
+}
+
+/// Decodes a blob of compact IPv4 addresses.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length.
+pub fn decode_v4(blob: &[u8]) -> Result<Vec<SocketAddrV4>, DecodeError> {
+    DecodeOptions::default().decode_v4(blob)
+}
+
+/// Decodes a blob of compact IPv6 addresses.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length.
+pub fn decode_v6(blob: &[u8]) -> Result<Vec<SocketAddrV6>, DecodeError> {
+    DecodeOptions::default().decode_v6(blob)
+}
+
+/// Decodes a blob of compact IPv4 addresses, ignoring any trailing bytes
+/// which do not form a whole address.
+pub fn decode_v4_lossy(blob: &[u8]) -> Vec<SocketAddrV4> {
+    let iter = iter_v4(blob);
+    log_skipped(iter.remainder().len(), V4_LEN);
+    iter.collect()
+}
+
+/// Decodes a blob of compact IPv6 addresses, ignoring any trailing bytes
+/// which do not form a whole address.
+pub fn decode_v6_lossy(blob: &[u8]) -> Vec<SocketAddrV6> {
+    let iter = iter_v6(blob);
+    log_skipped(iter.remainder().len(), V6_LEN);
+    iter.collect()
+}
+
+/// Returns an iterator which decodes the compact IPv4 addresses in a blob.
+///
+/// Trailing bytes which do not form a whole address are not yielded.
+pub fn iter_v4(blob: &[u8]) -> IterV4<'_> {
+    IterV4 {
+        chunks: blob.chunks_exact(V4_LEN),
+    }
+}
+
+/// Returns an iterator which decodes the compact IPv6 addresses in a blob.
+///
+/// Trailing bytes which do not form a whole address are not yielded.
+pub fn iter_v6(blob: &[u8]) -> IterV6<'_> {
+    IterV6 {
+        chunks: blob.chunks_exact(V6_LEN),
+    }
+}
+
+/// Returns the number of whole compact IPv4 addresses in a blob.
+pub fn len_v4(blob: &[u8]) -> usize {
+    blob.len() / V4_LEN
+}
+
+/// Returns the number of whole compact IPv6 addresses in a blob.
+pub fn len_v6(blob: &[u8]) -> usize {
+    blob.len() / V6_LEN
+}
+
+/// Returns the compact IPv4 address at the index in a blob.
+pub fn get_v4(blob: &[u8], idx: usize) -> Option<SocketAddrV4> {
+    let start = idx.checked_mul(V4_LEN)?;
+    let chunk = blob.get(start..start.checked_add(V4_LEN)?)?;
+    let mut a: [u8; V4_LEN] = [0; V4_LEN];
+    a.copy_from_slice(chunk);
+    Some(SocketAddrV4::from_compact(a))
+}
+
+/// Returns the compact IPv6 address at the index in a blob.
+pub fn get_v6(blob: &[u8], idx: usize) -> Option<SocketAddrV6> {
+    let start = idx.checked_mul(V6_LEN)?;
+    let chunk = blob.get(start..start.checked_add(V6_LEN)?)?;
+    let mut a: [u8; V6_LEN] = [0; V6_LEN];
+    a.copy_from_slice(chunk);
+    Some(SocketAddrV6::from_compact(a))
+}
+
+/// Returns the compact IPv4 addresses in a blob as arrays and the trailing
+/// bytes which do not form a whole address.
+///
+/// The bytes are not copied.
+pub fn as_chunks_v4(blob: &[u8]) -> (&[[u8; V4_LEN]], &[u8]) {
+    blob.as_chunks::<V4_LEN>()
+}
+
+/// Returns the compact IPv6 addresses in a blob as arrays and the trailing
+/// bytes which do not form a whole address.
+///
+/// The bytes are not copied.
+pub fn as_chunks_v6(blob: &[u8]) -> (&[[u8; V6_LEN]], &[u8]) {
+    blob.as_chunks::<V6_LEN>()
+}
+
+/// Returns the compact IPv4 addresses in a blob as mutable arrays and the
+/// trailing bytes which do not form a whole address.
+///
+/// The bytes are not copied.
+pub fn as_chunks_mut_v4(blob: &mut [u8]) -> (&mut [[u8; V4_LEN]], &mut [u8]) {
+    blob.as_chunks_mut::<V4_LEN>()
+}
+
+/// Returns the compact IPv6 addresses in a blob as mutable arrays and the
+/// trailing bytes which do not form a whole address.
+///
+/// The bytes are not copied.
+pub fn as_chunks_mut_v6(blob: &mut [u8]) -> (&mut [[u8; V6_LEN]], &mut [u8]) {
+    blob.as_chunks_mut::<V6_LEN>()
+}
+
+/// Returns the leading whole compact IPv4 addresses in a blob which fit in the
+/// byte budget.
+pub fn truncate_v4(blob: &[u8], max_bytes: usize) -> &[u8] {
+    &blob[..len_v4(blob).min(max_bytes / V4_LEN) * V4_LEN]
+}
+
+/// Returns the leading whole compact IPv6 addresses in a blob which fit in the
+/// byte budget.
+pub fn truncate_v6(blob: &[u8], max_bytes: usize) -> &[u8] {
+    &blob[..len_v6(blob).min(max_bytes / V6_LEN) * V6_LEN]
+}
+
+/// Which address family is given the byte budget first when truncating a pair
+/// of blobs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FamilyPriority {
+    /// IPv4 addresses are kept first and IPv6 addresses fill the remainder.
+    V4,
+    /// IPv6 addresses are kept first and IPv4 addresses fill the remainder.
+    V6,
+    /// Addresses are kept alternately from each family until neither fits.
+    #[default]
+    Balanced,
+}
+
+/// Returns the leading whole compact addresses in a pair of IPv4 and IPv6
+/// blobs which fit in a shared byte budget.
+pub fn truncate_pair<'a>(
+    v4: &'a [u8],
+    v6: &'a [u8],
+    max_bytes: usize,
+    priority: FamilyPriority,
+) -> (&'a [u8], &'a [u8]) {
+    let (len_v4, len_v6) = (len_v4(v4), len_v6(v6));
+    let (count_v4, count_v6) = match priority {
+        FamilyPriority::V4 => {
+            let count_v4 = len_v4.min(max_bytes / V4_LEN);
+            let rest = max_bytes - count_v4 * V4_LEN;
+            (count_v4, len_v6.min(rest / V6_LEN))
+        }
+        FamilyPriority::V6 => {
+            let count_v6 = len_v6.min(max_bytes / V6_LEN);
+            let rest = max_bytes - count_v6 * V6_LEN;
+            (len_v4.min(rest / V4_LEN), count_v6)
+        }
+        FamilyPriority::Balanced => {
+            let (mut count_v4, mut count_v6) = (0, 0);
+            let mut rest = max_bytes;
+            loop {
+                let mut added = false;
+                if count_v4 < len_v4 && rest >= V4_LEN {
+                    count_v4 += 1;
+                    rest -= V4_LEN;
+                    added = true;
+                }
+                if count_v6 < len_v6 && rest >= V6_LEN {
+                    count_v6 += 1;
+                    rest -= V6_LEN;
+                    added = true;
+                }
+                if !added {
+                    break;
+                }
+            }
+            (count_v4, count_v6)
+        }
+    };
+    (&v4[..count_v4 * V4_LEN], &v6[..count_v6 * V6_LEN])
+}
+
+/// Retains only the compact IPv4 addresses in a blob for which the predicate
+/// returns true.
+///
+/// The blob is compacted in place, preserving the order of the retained
+/// addresses. Trailing bytes which do not form a whole address are removed.
+pub fn retain_v4<F>(blob: &mut Vec<u8>, mut f: F)
+where
+    F: FnMut(CompactAddrV4Ref<'_>) -> bool,
+{
+    let (entries, _) = as_chunks_mut_v4(blob);
+    let mut len = 0;
+    for idx in 0..entries.len() {
+        if f(CompactAddrV4Ref::new(&entries[idx])) {
+            entries[len] = entries[idx];
+            len += 1;
+        }
+    }
+    blob.truncate(len * V4_LEN);
+}
+
+/// Retains only the compact IPv6 addresses in a blob for which the predicate
+/// returns true.
+///
+/// The blob is compacted in place, preserving the order of the retained
+/// addresses. Trailing bytes which do not form a whole address are removed.
+pub fn retain_v6<F>(blob: &mut Vec<u8>, mut f: F)
+where
+    F: FnMut(CompactAddrV6Ref<'_>) -> bool,
+{
+    let (entries, _) = as_chunks_mut_v6(blob);
+    let mut len = 0;
+    for idx in 0..entries.len() {
+        if f(CompactAddrV6Ref::new(&entries[idx])) {
+            entries[len] = entries[idx];
+            len += 1;
+        }
+    }
+    blob.truncate(len * V6_LEN);
+}
+
+/// Returns an iterator which splits a blob of compact IPv4 addresses into
+/// pages of at most `max_entries` addresses.
+///
+/// Pages are borrowed from the blob. A limit of zero is treated as one.
+/// Trailing bytes which do not form a whole address are not yielded.
+pub fn pages_v4(blob: &[u8], max_entries: usize) -> Pages<'_> {
+    Pages::new(blob, V4_LEN, max_entries)
+}
+
+/// Returns an iterator which splits a blob of compact IPv6 addresses into
+/// pages of at most `max_entries` addresses.
+///
+/// Pages are borrowed from the blob. A limit of zero is treated as one.
+/// Trailing bytes which do not form a whole address are not yielded.
+pub fn pages_v6(blob: &[u8], max_entries: usize) -> Pages<'_> {
+    Pages::new(blob, V6_LEN, max_entries)
+}
+
+/// Returns an iterator which splits a blob of compact IPv4 addresses into
+/// pages of at most `max_bytes` bytes.
+///
+/// Pages are borrowed from the blob and end at address boundaries. No pages are
+/// yielded if the limit is smaller than one address. Trailing bytes which do
+/// not form a whole address are not yielded.
+pub fn pages_v4_by_bytes(blob: &[u8], max_bytes: usize) -> Pages<'_> {
+    Pages::by_bytes(blob, V4_LEN, max_bytes)
+}
+
+/// Returns an iterator which splits a blob of compact IPv6 addresses into
+/// pages of at most `max_bytes` bytes.
+///
+/// Pages are borrowed from the blob and end at address boundaries. No pages are
+/// yielded if the limit is smaller than one address. Trailing bytes which do
+/// not form a whole address are not yielded.
+pub fn pages_v6_by_bytes(blob: &[u8], max_bytes: usize) -> Pages<'_> {
+    Pages::by_bytes(blob, V6_LEN, max_bytes)
+}
+
+/// An iterator over the compact IPv4 addresses in a blob.
+#[derive(Clone, Debug)]
+pub struct IterV4<'a> {
+    chunks: ChunksExact<'a, u8>,
+}
+
+impl<'a> IterV4<'a> {
+    /// Returns the trailing bytes which do not form a whole address.
+    pub fn remainder(&self) -> &'a [u8] {
+        self.chunks.remainder()
+    }
+}
+
+impl<'a> Iterator for IterV4<'a> {
+    type Item = SocketAddrV4;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chunks.next().map(|chunk| {
+            let mut a: [u8; V4_LEN] = [0; V4_LEN];
+            a.copy_from_slice(chunk);
+            SocketAddrV4::from_compact(a)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for IterV4<'a> {}
+
+/// An iterator over the compact IPv6 addresses in a blob.
+#[derive(Clone, Debug)]
+pub struct IterV6<'a> {
+    chunks: ChunksExact<'a, u8>,
+}
+
+impl<'a> IterV6<'a> {
+    /// Returns the trailing bytes which do not form a whole address.
+    pub fn remainder(&self) -> &'a [u8] {
+        self.chunks.remainder()
+    }
+}
+
+impl<'a> Iterator for IterV6<'a> {
+    type Item = SocketAddrV6;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chunks.next().map(|chunk| {
+            let mut a: [u8; V6_LEN] = [0; V6_LEN];
+            a.copy_from_slice(chunk);
+            SocketAddrV6::from_compact(a)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for IterV6<'a> {}
+
+/// An iterator over pages of whole compact addresses in a blob.
+#[derive(Clone, Debug)]
+pub struct Pages<'a> {
+    chunks: Chunks<'a, u8>,
+    remainder: &'a [u8],
+}
+
+impl<'a> Pages<'a> {
+    fn new(blob: &'a [u8], entry_len: usize, max_entries: usize) -> Self {
+        let (entries, remainder) = blob.split_at(blob.len() - blob.len() % entry_len);
+        Self {
+            chunks: entries.chunks(max_entries.max(1).saturating_mul(entry_len)),
+            remainder,
+        }
+    }
+
+    fn by_bytes(blob: &'a [u8], entry_len: usize, max_bytes: usize) -> Self {
+        let mut pages = Self::new(blob, entry_len, max_bytes / entry_len);
+        if max_bytes < entry_len {
+            pages.chunks = [].chunks(entry_len);
+        }
+        pages
+    }
+
+    /// Returns the trailing bytes which do not form a whole address.
+    pub fn remainder(&self) -> &'a [u8] {
+        self.remainder
+    }
+}
+
+impl<'a> Iterator for Pages<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chunks.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Pages<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.chunks.next_back()
+    }
+}
+
+impl<'a> ExactSizeIterator for Pages<'a> {}
+
+macro_rules! blob_type {
+    (
+        $(#[$meta:meta])*
+        $name:ident, $addr:ty, $compact:ty, $entry_len:expr, $iter:ident, $iter_fn:ident, $get_fn:ident,
+        $(#[$into_iter_meta:meta])*
+        $into_iter:ident, $as_chunks_fn:ident
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name {
+            bytes: Vec<u8>,
+        }
+
+        impl $name {
+            /// Instantiates an empty blob.
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            /// Instantiates an empty blob with capacity for the number of
+            /// addresses.
+            pub fn with_capacity(capacity: usize) -> Self {
+                Self {
+                    bytes: Vec::with_capacity(capacity * $entry_len),
+                }
+            }
+
+            /// Instantiates from the blob bytes.
+            ///
+            /// # Errors
+            ///
+            /// Returns an error if the length is not a multiple of the compact
+            /// address length.
+            pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, DecodeError> {
+                if bytes.len().is_multiple_of($entry_len) {
+                    Ok(Self { bytes })
+                } else {
+                    Err(DecodeError::invalid_length(bytes.len(), $entry_len))
+                }
+            }
+
+            /// Returns the blob bytes.
+            pub fn as_bytes(&self) -> &[u8] {
+                &self.bytes
+            }
+
+            /// Returns the blob bytes.
+            pub fn into_bytes(self) -> Vec<u8> {
+                self.bytes
+            }
+
+            /// Returns the number of addresses.
+            pub fn len(&self) -> usize {
+                self.bytes.len() / $entry_len
+            }
+
+            /// Returns true if there are no addresses.
+            pub fn is_empty(&self) -> bool {
+                self.bytes.is_empty()
+            }
+
+            /// Returns the address at the index.
+            pub fn get(&self, idx: usize) -> Option<$addr> {
+                $get_fn(&self.bytes, idx)
+            }
+
+            /// Returns an iterator which decodes the addresses.
+            pub fn iter(&self) -> $iter<'_> {
+                $iter_fn(&self.bytes)
+            }
+
+            /// Appends an address.
+            pub fn push<A: Into<$compact>>(&mut self, addr: A) {
+                self.bytes.extend_from_slice(addr.into().as_bytes());
+            }
+
+            /// Removes all addresses.
+            pub fn clear(&mut self) {
+                self.bytes.clear();
+            }
+        }
+
+        impl TryFrom<Vec<u8>> for $name {
+            type Error = DecodeError;
+
+            fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+                Self::from_bytes(bytes)
+            }
+        }
+
+        impl TryFrom<&[u8]> for $name {
+            type Error = DecodeError;
+
+            fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+                Self::from_bytes(bytes.to_vec())
+            }
+        }
+
+        impl From<$name> for Vec<u8> {
+            fn from(blob: $name) -> Self {
+                blob.bytes
+            }
+        }
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.bytes
+            }
+        }
+
+        impl Index<usize> for $name {
+            type Output = $compact;
+
+            fn index(&self, idx: usize) -> &Self::Output {
+                <$compact>::from_ref(&$as_chunks_fn(&self.bytes).0[idx])
+            }
+        }
+
+        impl<'a> IntoIterator for &'a $name {
+            type Item = $addr;
+            type IntoIter = $iter<'a>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.iter()
+            }
+        }
+
+        impl IntoIterator for $name {
+            type Item = $addr;
+            type IntoIter = $into_iter;
+
+            fn into_iter(self) -> Self::IntoIter {
+                $into_iter {
+                    bytes: self.bytes,
+                    idx: 0,
+                }
+            }
+        }
+
+        $(#[$into_iter_meta])*
+        #[derive(Clone, Debug)]
+        pub struct $into_iter {
+            bytes: Vec<u8>,
+            idx: usize,
+        }
+
+        impl Iterator for $into_iter {
+            type Item = $addr;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                let addr = $get_fn(&self.bytes, self.idx)?;
+                self.idx += 1;
+                Some(addr)
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                let len = self.bytes.len() / $entry_len - self.idx;
+                (len, Some(len))
+            }
+        }
+
+        impl ExactSizeIterator for $into_iter {}
+
+        impl<A: Into<$compact>> FromIterator<A> for $name {
+            fn from_iter<I: IntoIterator<Item = A>>(iter: I) -> Self {
+                let mut blob = Self::new();
+                blob.extend(iter);
+                blob
+            }
+        }
+
+        impl<A: Into<$compact>> Extend<A> for $name {
+            fn extend<I: IntoIterator<Item = A>>(&mut self, iter: I) {
+                let iter = iter.into_iter();
+                self.bytes.reserve(iter.size_hint().0 * $entry_len);
+                for addr in iter {
+                    self.push(addr);
+                }
+            }
+        }
+    };
+}
+
+blob_type!(
+    /// An owned blob of concatenated compact IPv4 addresses.
+    CompactBlobV4,
+    SocketAddrV4,
+    CompactAddrV4,
+    V4_LEN,
+    IterV4,
+    iter_v4,
+    get_v4,
+    /// An owning iterator which decodes the addresses in a [`CompactBlobV4`].
+    IntoIterV4,
+    as_chunks_v4
+);
+
+blob_type!(
+    /// An owned blob of concatenated compact IPv6 addresses.
+    CompactBlobV6,
+    SocketAddrV6,
+    CompactAddrV6,
+    V6_LEN,
+    IterV6,
+    iter_v6,
+    get_v6,
+    /// An owning iterator which decodes the addresses in a [`CompactBlobV6`].
+    IntoIterV6,
+    as_chunks_v6
+);
+
+/// Options for decoding blobs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// The maximum blob length in bytes.
+    ///
+    /// Blobs longer than the limit are rejected.
+    pub max_len: Option<usize>,
+    /// If trailing bytes which do not form a whole address are ignored.
+    ///
+    /// If false, the blob is rejected.
+    pub lossy: bool,
+    /// If addresses with an unspecified IP address or a zero port are skipped.
+    pub skip_invalid: bool,
+}
+
+impl DecodeOptions {
+    /// Decodes a blob of compact IPv4 addresses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blob exceeds the maximum length or, if not
+    /// lossy, the blob length is not a multiple of the compact address length.
+    pub fn decode_v4(&self, blob: &[u8]) -> Result<Vec<SocketAddrV4>, DecodeError> {
+        self.check_len(blob.len(), V4_LEN)?;
+        let iter = iter_v4(blob);
+        log_skipped(iter.remainder().len(), V4_LEN);
+        Ok(self.filter_valid(iter, |addr| addr.ip().is_unspecified() || addr.port() == 0))
+    }
+
+    /// Decodes a blob of compact IPv6 addresses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blob exceeds the maximum length or, if not
+    /// lossy, the blob length is not a multiple of the compact address length.
+    pub fn decode_v6(&self, blob: &[u8]) -> Result<Vec<SocketAddrV6>, DecodeError> {
+        self.check_len(blob.len(), V6_LEN)?;
+        let iter = iter_v6(blob);
+        log_skipped(iter.remainder().len(), V6_LEN);
+        Ok(self.filter_valid(iter, |addr| addr.ip().is_unspecified() || addr.port() == 0))
+    }
+
+    fn check_len(&self, len: usize, entry_len: usize) -> Result<(), DecodeError> {
+        if let Some(max_len) = self.max_len {
+            if len > max_len {
+                debug_event!(len, max_len, "blob exceeds maximum length");
+                return Err(DecodeError::TooLarge { len, max_len });
+            }
+        }
+
+        if !self.lossy && !len.is_multiple_of(entry_len) {
+            debug_event!(
+                len,
+                entry_len,
+                "blob length is not a multiple of entry length"
+            );
+            return Err(DecodeError::invalid_length(len, entry_len));
+        }
+
+        Ok(())
+    }
+
+    fn filter_valid<T, I, F>(&self, iter: I, is_invalid: F) -> Vec<T>
+    where
+        T: fmt::Display,
+        I: ExactSizeIterator<Item = T>,
+        F: Fn(&T) -> bool,
+    {
+        let mut addrs = Vec::with_capacity(iter.len());
+        for addr in iter {
+            if self.skip_invalid && is_invalid(&addr) {
+                debug_event!(%addr, "skipped invalid address");
+                continue;
+            }
+            addrs.push(addr);
+        }
+        addrs
+    }
+}
+
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+fn log_skipped(skipped: usize, entry_len: usize) {
+    if skipped != 0 {
+        debug_event!(skipped, entry_len, "skipped trailing bytes");
+    }
+}
+
+/// An error when decoding a blob.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The blob length is not a multiple of the compact address length.
+    InvalidLength {
+        /// The blob length.
+        len: usize,
+        /// The compact address length.
+        entry_len: usize,
+        /// The byte offset of the incomplete trailing entry.
+        offset: usize,
+        /// The length of the incomplete trailing entry.
+        actual_len: usize,
+        /// The number of whole entries before the incomplete entry.
+        valid_entries: usize,
+    },
+    /// The blob exceeds the maximum length.
+    TooLarge {
+        /// The blob length.
+        len: usize,
+        /// The maximum length.
+        max_len: usize,
+    },
+}
+
+impl DecodeError {
+    pub(crate) fn invalid_length(len: usize, entry_len: usize) -> Self {
+        let valid_entries = len / entry_len;
+        let offset = valid_entries * entry_len;
+        DecodeError::InvalidLength {
+            len,
+            entry_len,
+            offset,
+            actual_len: len - offset,
+            valid_entries,
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidLength {
+                len,
+                entry_len,
+                offset,
+                actual_len,
+                valid_entries,
+            } => write!(
+                f,
+                "blob length {} is not a multiple of {}: entry at offset {} has {} of {} bytes after {} valid entries",
+                len, entry_len, offset, actual_len, entry_len, valid_entries
+            ),
+            DecodeError::TooLarge { len, max_len } => {
+                write!(f, "blob length {} exceeds maximum length {}", len, max_len)
+            }
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{socket_v4, socket_v6};
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    #[test]
+    fn decode_round_trip() {
+        let addrs = vec![socket_v4(1, 80), socket_v4(2, 443)];
+        let blob = encode_v4(addrs.iter().copied());
+        assert_eq!(blob.len(), 2 * V4_LEN);
+        assert_eq!(decode_v4(&blob), Ok(addrs));
+
+        let addrs = vec![socket_v6(1, 80), socket_v6(2, 443)];
+        let blob = encode_v6(addrs.iter().copied());
+        assert_eq!(blob.len(), 2 * V6_LEN);
+        assert_eq!(decode_v6(&blob), Ok(addrs));
+    }
+
+    #[test]
+    fn decode_options_max_len() {
+        let blob = encode_v4(vec![socket_v4(1, 80), socket_v4(2, 80)]);
+        let options = DecodeOptions {
+            max_len: Some(V4_LEN),
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            options.decode_v4(&blob),
+            Err(DecodeError::TooLarge {
+                len: 12,
+                max_len: 6
+            })
+        );
+        assert_eq!(
+            options.decode_v4(&blob[..V4_LEN]),
+            Ok(vec![socket_v4(1, 80)])
+        );
+    }
+
+    #[test]
+    fn decode_options_lossy() {
+        let mut blob = encode_v6(vec![socket_v6(1, 80)]);
+        blob.extend_from_slice(&[1, 2, 3]);
+        assert!(decode_v6(&blob).is_err());
+
+        let options = DecodeOptions {
+            lossy: true,
+            ..DecodeOptions::default()
+        };
+        assert_eq!(options.decode_v6(&blob), Ok(vec![socket_v6(1, 80)]));
+        assert_eq!(decode_v6_lossy(&blob), vec![socket_v6(1, 80)]);
+    }
+
+    #[test]
+    fn decode_options_skip_invalid() {
+        let blob = encode_v4(vec![
+            socket_v4(1, 80),
+            SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 80),
+            socket_v4(2, 0),
+        ]);
+        let options = DecodeOptions {
+            skip_invalid: true,
+            ..DecodeOptions::default()
+        };
+        assert_eq!(options.decode_v4(&blob), Ok(vec![socket_v4(1, 80)]));
+        assert_eq!(decode_v4(&blob).map(|addrs| addrs.len()), Ok(3));
+    }
+
+    #[test]
+    fn owned_blob() {
+        let mut blob = CompactBlobV4::with_capacity(2);
+        assert!(blob.is_empty());
+        blob.push(socket_v4(1, 80));
+        blob.push(socket_v4(2, 81));
+        assert_eq!(blob.len(), 2);
+        assert_eq!(blob.get(1), Some(socket_v4(2, 81)));
+        assert_eq!(blob.get(2), None);
+        assert_eq!(
+            blob.iter().collect::<Vec<_>>(),
+            vec![socket_v4(1, 80), socket_v4(2, 81)]
+        );
+        assert_eq!(
+            CompactBlobV4::from_bytes(blob.as_bytes().to_vec()),
+            Ok(blob.clone())
+        );
+        assert_eq!(Vec::from(blob.clone()), blob.as_bytes());
+        blob.clear();
+        assert!(blob.is_empty());
+
+        let mut blob = CompactBlobV6::new();
+        blob.push(socket_v6(1, 80));
+        assert_eq!(blob.as_bytes().len(), V6_LEN);
+        assert_eq!(blob.get(0), Some(socket_v6(1, 80)));
+    }
+
+    #[test]
+    fn owned_blob_invalid_length() {
+        assert_eq!(
+            CompactBlobV4::from_bytes(vec![0; 7]),
+            Err(DecodeError::InvalidLength {
+                len: 7,
+                entry_len: V4_LEN,
+                offset: 6,
+                actual_len: 1,
+                valid_entries: 1,
+            })
+        );
+        assert!(CompactBlobV6::try_from(&[0u8; 17][..]).is_err());
+    }
+
+    #[test]
+    fn as_chunks() {
+        let mut blob = encode_v4(vec![socket_v4(1, 80), socket_v4(2, 81)]);
+        blob.push(0xee);
+        let (entries, rest) = as_chunks_v4(&blob);
+        assert_eq!(entries, [[10, 0, 0, 1, 0, 80], [10, 0, 0, 2, 0, 81]]);
+        assert_eq!(rest, [0xee]);
+        assert_eq!(entries.as_ptr().cast::<u8>(), blob.as_ptr());
+
+        let (entries, rest) = as_chunks_v6(&[0; V6_LEN - 1]);
+        assert!(entries.is_empty());
+        assert_eq!(rest.len(), V6_LEN - 1);
+    }
+
+    #[test]
+    fn as_chunks_mut() {
+        let mut blob = encode_v4(vec![socket_v4(1, 80), socket_v4(2, 81)]);
+        as_chunks_mut_v4(&mut blob).0.swap(0, 1);
+        assert_eq!(
+            decode_v4(&blob),
+            Ok(vec![socket_v4(2, 81), socket_v4(1, 80)])
+        );
+
+        let mut blob = encode_v6(vec![socket_v6(1, 80)]);
+        blob.push(0xee);
+        let (entries, rest) = as_chunks_mut_v6(&mut blob);
+        entries[0][17] = 81;
+        rest[0] = 0xff;
+        assert_eq!(decode_v6_lossy(&blob), vec![socket_v6(1, 81)]);
+        assert_eq!(blob[V6_LEN], 0xff);
+    }
+
+    #[test]
+    fn retain() {
+        let mut blob = encode_v4(vec![socket_v4(1, 80), socket_v4(2, 81), socket_v4(3, 80)]);
+        blob.push(0xee);
+        retain_v4(&mut blob, |addr| addr.port() == 80);
+        assert_eq!(
+            decode_v4(&blob),
+            Ok(vec![socket_v4(1, 80), socket_v4(3, 80)])
+        );
+
+        retain_v4(&mut blob, |_| false);
+        assert!(blob.is_empty());
+
+        let mut blob = encode_v6(vec![socket_v6(1, 80), socket_v6(2, 81)]);
+        retain_v6(&mut blob, |addr| addr.ip().segments()[7] == 2);
+        assert_eq!(decode_v6(&blob), Ok(vec![socket_v6(2, 81)]));
+    }
+
+    #[test]
+    fn truncate() {
+        let mut blob = encode_v4(vec![socket_v4(1, 80), socket_v4(2, 80), socket_v4(3, 80)]);
+        blob.push(0xee);
+        assert_eq!(truncate_v4(&blob, 17), &blob[..12]);
+        assert_eq!(truncate_v4(&blob, 5), []);
+        assert_eq!(truncate_v4(&blob, 100), &blob[..18]);
+
+        let blob = encode_v6(vec![socket_v6(1, 80), socket_v6(2, 80)]);
+        assert_eq!(truncate_v6(&blob, 35), &blob[..V6_LEN]);
+        assert_eq!(truncate_v6(&blob, 36), &blob[..]);
+    }
+
+    #[test]
+    fn truncate_pair_priority() {
+        let b4 = encode_v4((1..=3).map(|n| socket_v4(n, 80)));
+        let b6 = encode_v6((1..=3).map(|n| socket_v6(n, 80)));
+        let lens = |(a, b): (&[u8], &[u8])| (a.len() / V4_LEN, b.len() / V6_LEN);
+
+        assert_eq!(
+            lens(truncate_pair(&b4, &b6, 30, FamilyPriority::V4)),
+            (3, 0)
+        );
+        assert_eq!(
+            lens(truncate_pair(&b4, &b6, 36, FamilyPriority::V4)),
+            (3, 1)
+        );
+        assert_eq!(
+            lens(truncate_pair(&b4, &b6, 30, FamilyPriority::V6)),
+            (2, 1)
+        );
+        assert_eq!(
+            lens(truncate_pair(&b4, &b6, 30, FamilyPriority::Balanced)),
+            (2, 1)
+        );
+        assert_eq!(
+            lens(truncate_pair(&b4, &b6, 1000, FamilyPriority::Balanced)),
+            (3, 3)
+        );
+        assert_eq!(
+            lens(truncate_pair(&b4, &[], 13, FamilyPriority::Balanced)),
+            (2, 0)
+        );
+        assert_eq!(
+            lens(truncate_pair(&b4, &b6, 5, FamilyPriority::default())),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn partition() {
+        let addrs = vec![
+            SocketAddr::V6(socket_v6(1, 80)),
+            SocketAddr::V4(socket_v4(1, 80)),
+            SocketAddr::V6(socket_v6(2, 81)),
+            SocketAddr::V4(socket_v4(2, 81)),
+        ];
+        let (b4, b6) = partition_encode(addrs);
+        assert_eq!(decode_v4(&b4), Ok(vec![socket_v4(1, 80), socket_v4(2, 81)]));
+        assert_eq!(decode_v6(&b6), Ok(vec![socket_v6(1, 80), socket_v6(2, 81)]));
+
+        let (b4, b6) = partition_encode(Vec::<SocketAddr>::new());
+        assert!(b4.is_empty());
+        assert!(b6.is_empty());
+    }
+
+    #[test]
+    fn blob_from_iter_and_extend() {
+        let mut blob: CompactBlobV4 = vec![socket_v4(1, 80), socket_v4(2, 81)]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            blob.as_bytes(),
+            encode_v4(vec![socket_v4(1, 80), socket_v4(2, 81)])
+        );
+        blob.extend(vec![socket_v4(3, 82)]);
+        assert_eq!(blob.len(), 3);
+        assert_eq!(blob.get(2), Some(socket_v4(3, 82)));
+
+        let blob: CompactBlobV6 = std::iter::repeat_n(socket_v6(1, 80), 2).collect();
+        assert_eq!(blob.as_bytes(), encode_v6(vec![socket_v6(1, 80); 2]));
+        assert!(CompactBlobV6::from_iter(Vec::<SocketAddrV6>::new()).is_empty());
+    }
+
+    #[test]
+    fn blob_index_and_into_iter() {
+        let blob: CompactBlobV4 = vec![socket_v4(1, 80), socket_v4(2, 81)]
+            .into_iter()
+            .collect();
+        assert_eq!(blob[1], CompactAddrV4::from(socket_v4(2, 81)));
+        assert_eq!(blob[0].as_bytes().as_ptr(), blob.as_bytes().as_ptr());
+        assert_eq!(
+            (&blob).into_iter().collect::<Vec<_>>(),
+            vec![socket_v4(1, 80), socket_v4(2, 81)]
+        );
+
+        let mut iter = blob.into_iter();
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.next(), Some(socket_v4(1, 80)));
+        assert_eq!(iter.len(), 1);
+        assert_eq!(iter.next(), Some(socket_v4(2, 81)));
+        assert_eq!(iter.next(), None);
+
+        let blob: CompactBlobV6 = vec![socket_v6(1, 80)].into_iter().collect();
+        assert_eq!(blob[0], CompactAddrV6::from(socket_v6(1, 80)));
+        assert_eq!(blob.into_iter().collect::<Vec<_>>(), vec![socket_v6(1, 80)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn blob_index_out_of_bounds() {
+        let blob: CompactBlobV4 = vec![socket_v4(1, 80)].into_iter().collect();
+        let _ = blob[1];
+    }
+
+    #[test]
+    fn decode_error_details() {
+        let mut blob = encode_v4(vec![socket_v4(1, 80), socket_v4(2, 80)]);
+        blob.extend_from_slice(&[1, 2, 3]);
+        let err = decode_v4(&blob).unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::InvalidLength {
+                len: 15,
+                entry_len: V4_LEN,
+                offset: 12,
+                actual_len: 3,
+                valid_entries: 2,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "blob length 15 is not a multiple of 6: entry at offset 12 has 3 of 6 bytes after 2 valid entries"
+        );
+
+        assert_eq!(
+            decode_v6(&[0; 5]),
+            Err(DecodeError::InvalidLength {
+                len: 5,
+                entry_len: V6_LEN,
+                offset: 0,
+                actual_len: 5,
+                valid_entries: 0,
+            })
+        );
+        assert_eq!(
+            DecodeError::TooLarge {
+                len: 12,
+                max_len: 6
+            }
+            .to_string(),
+            "blob length 12 exceeds maximum length 6"
+        );
+    }
+
+    #[test]
+    fn pages() {
+        let mut blob = encode_v4((1..=5).map(|n| socket_v4(n, 80)));
+        blob.push(0xee);
+        let pages = pages_v4(&blob, 2);
+        assert_eq!(pages.remainder(), [0xee]);
+        assert_eq!(pages.len(), 3);
+        let lens: Vec<_> = pages.map(len_v4).collect();
+        assert_eq!(lens, [2, 2, 1]);
+
+        let mut pages = pages_v4(&blob, 2);
+        assert_eq!(pages.next_back(), Some(&blob[24..30]));
+        assert_eq!(pages.next(), Some(&blob[..12]));
+
+        assert_eq!(pages_v4(&blob, 0).count(), 5);
+        assert_eq!(pages_v4(&blob, usize::MAX).count(), 1);
+        assert_eq!(pages_v6(&[], 3).count(), 0);
+    }
+
+    #[test]
+    fn pages_by_bytes() {
+        let blob = encode_v6((1..=3).map(|n| socket_v6(n, 80)));
+        let pages: Vec<_> = pages_v6_by_bytes(&blob, 40).collect();
+        assert_eq!(pages, [&blob[..36], &blob[36..]]);
+        assert_eq!(pages_v6_by_bytes(&blob, V6_LEN).count(), 3);
+
+        let blob = encode_v4((1..=3).map(|n| socket_v4(n, 80)));
+        assert_eq!(pages_v4_by_bytes(&blob, 17).count(), 2);
+        assert!(pages_v4_by_bytes(&blob, 17).all(|page| page.len() <= 17));
+    }
+
+    #[test]
+    fn pages_by_bytes_smaller_than_an_address() {
+        let mut blob = encode_v4((1..=3).map(|n| socket_v4(n, 80)));
+        blob.push(0xee);
+        let pages = pages_v4_by_bytes(&blob, V4_LEN - 1);
+        assert_eq!(pages.remainder(), [0xee]);
+        assert_eq!(pages.len(), 0);
+        assert_eq!(pages_v4_by_bytes(&blob, 0).count(), 0);
+
+        let blob = encode_v6((1..=3).map(|n| socket_v6(n, 80)));
+        assert_eq!(pages_v6_by_bytes(&blob, V6_LEN - 1).count(), 0);
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Range membership testing keyed on compact addresses.
+//!
+//! A [`Blocklist`] is compiled from inclusive IP address ranges and tests
+//! membership directly against compact address bytes, without constructing
+//! intermediate IP address values.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Accumulates IP address ranges to compile into a [`Blocklist`].
+#[derive(Clone, Debug, Default)]
+pub struct BlocklistBuilder {
+    v4: Vec<(u32, u32)>,
+    v6: Vec<(u128, u128)>,
+}
+
+impl BlocklistBuilder {
+    /// Instantiates an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an inclusive IPv4 address range.
+    ///
+    /// If `start` is greater than `end`, the bounds are swapped.
+    pub fn insert_v4(&mut self, start: Ipv4Addr, end: Ipv4Addr) -> &mut Self {
+        let (start, end) = (u32::from(start), u32::from(end));
+        self.v4.push((start.min(end), start.max(end)));
+        self
+    }
+
+    /// Adds an inclusive IPv6 address range.
+    ///
+    /// If `start` is greater than `end`, the bounds are swapped.
+    pub fn insert_v6(&mut self, start: Ipv6Addr, end: Ipv6Addr) -> &mut Self {
+        let (start, end) = (u128::from(start), u128::from(end));
+        self.v6.push((start.min(end), start.max(end)));
+        self
+    }
+
+    /// Compiles the accumulated ranges.
+    ///
+    /// Overlapping and adjacent ranges are merged.
+    pub fn build(self) -> Blocklist {
+        let v4 = Intervals::compile(self.v4, |end| end.checked_add(1));
+        let v6 = Intervals::compile(self.v6, |end| end.checked_add(1));
+        Blocklist { v4, v6 }
+    }
+}
+
+/// A compiled set of IP address ranges.
+///
+/// Lookups are a binary search over sorted, non-overlapping ranges.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Blocklist {
+    v4: Intervals<u32>,
+    v6: Intervals<u128>,
+}
+
+impl Blocklist {
+    /// Returns true if the IP address of the compact IPv4 address is in a range.
+    ///
+    /// The port is ignored.
+    pub fn contains(&self, addr: &[u8; 6]) -> bool {
+        let mut ip: [u8; 4] = [0; 4];
+        ip.copy_from_slice(&addr[0..4]);
+        self.v4.contains(u32::from_be_bytes(ip))
+    }
+
+    /// Returns true if the IP address of the compact IPv6 address is in a range.
+    ///
+    /// The port is ignored. IPv4-mapped IPv6 addresses are only tested against
+    /// IPv6 ranges.
+    pub fn contains_v6(&self, addr: &[u8; 18]) -> bool {
+        let mut ip: [u8; 16] = [0; 16];
+        ip.copy_from_slice(&addr[0..16]);
+        self.v6.contains(u128::from_be_bytes(ip))
+    }
+
+    /// Returns the number of compiled IPv4 ranges.
+    pub fn v4_ranges_len(&self) -> usize {
+        self.v4.starts.len()
+    }
+
+    /// Returns the number of compiled IPv6 ranges.
+    pub fn v6_ranges_len(&self) -> usize {
+        self.v6.starts.len()
+    }
+
+    /// Returns true if there are no ranges.
+    pub fn is_empty(&self) -> bool {
+        self.v4.starts.is_empty() && self.v6.starts.is_empty()
+    }
+}
+
+/// Sorted, non-overlapping inclusive ranges stored as parallel vectors.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Intervals<T> {
+    starts: Vec<T>,
+    ends: Vec<T>,
+}
+
+impl<T: Copy + Ord> Intervals<T> {
+    fn compile<F>(mut ranges: Vec<(T, T)>, successor: F) -> Self
+    where
+        F: Fn(T) -> Option<T>,
+    {
+        ranges.sort_unstable();
+
+        let mut starts: Vec<T> = Vec::with_capacity(ranges.len());
+        let mut ends: Vec<T> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            if let Some(last_end) = ends.last_mut() {
+                if successor(*last_end).is_none_or(|next| start <= next) {
+                    if end > *last_end {
+                        *last_end = end;
+                    }
+                    continue;
+                }
+            }
+            starts.push(start);
+            ends.push(end);
+        }
+
+        starts.shrink_to_fit();
+        ends.shrink_to_fit();
+        Self { starts, ends }
+    }
+
+    fn contains(&self, value: T) -> bool {
+        let idx = self.starts.partition_point(|start| *start <= value);
+        idx > 0 && value <= self.ends[idx - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{v4_bytes, v6_bytes};
+
+    #[test]
+    fn contains_v4_ranges() {
+        let mut builder = BlocklistBuilder::new();
+        builder
+            .insert_v4(Ipv4Addr::new(10, 0, 0, 0), Ipv4Addr::new(10, 0, 0, 255))
+            .insert_v4(Ipv4Addr::new(192, 168, 1, 9), Ipv4Addr::new(192, 168, 1, 1));
+        let list = builder.build();
+
+        assert!(list.contains(&v4_bytes([10, 0, 0, 0])));
+        assert!(list.contains(&v4_bytes([10, 0, 0, 255])));
+        assert!(!list.contains(&v4_bytes([10, 0, 1, 0])));
+        assert!(!list.contains(&v4_bytes([9, 255, 255, 255])));
+        assert!(list.contains(&v4_bytes([192, 168, 1, 5])));
+        assert!(!list.contains(&v4_bytes([192, 168, 1, 10])));
+        assert!(!list.contains_v6(&v6_bytes("::ffff:10.0.0.1")));
+    }
+
+    #[test]
+    fn merges_overlapping_and_adjacent_ranges() {
+        let mut builder = BlocklistBuilder::new();
+        builder
+            .insert_v4(Ipv4Addr::new(1, 0, 0, 0), Ipv4Addr::new(1, 0, 0, 10))
+            .insert_v4(Ipv4Addr::new(1, 0, 0, 5), Ipv4Addr::new(1, 0, 0, 20))
+            .insert_v4(Ipv4Addr::new(1, 0, 0, 21), Ipv4Addr::new(1, 0, 0, 30))
+            .insert_v4(Ipv4Addr::new(1, 0, 0, 32), Ipv4Addr::new(1, 0, 0, 40))
+            .insert_v4(Ipv4Addr::new(255, 0, 0, 0), Ipv4Addr::BROADCAST)
+            .insert_v4(Ipv4Addr::new(255, 255, 0, 0), Ipv4Addr::BROADCAST);
+        let list = builder.build();
+
+        assert_eq!(list.v4_ranges_len(), 3);
+        assert!(list.contains(&v4_bytes([1, 0, 0, 30])));
+        assert!(!list.contains(&v4_bytes([1, 0, 0, 31])));
+        assert!(list.contains(&v4_bytes([255, 255, 255, 255])));
+    }
+
+    #[test]
+    fn contains_v6_ranges() {
+        let mut builder = BlocklistBuilder::new();
+        builder
+            .insert_v6(
+                "2001:db8::".parse().unwrap(),
+                "2001:db8::ffff".parse().unwrap(),
+            )
+            .insert_v6(Ipv6Addr::UNSPECIFIED, Ipv6Addr::LOCALHOST);
+        let list = builder.build();
+
+        assert_eq!(list.v6_ranges_len(), 2);
+        assert_eq!(list.v4_ranges_len(), 0);
+        assert!(list.contains_v6(&v6_bytes("2001:db8::1234")));
+        assert!(!list.contains_v6(&v6_bytes("2001:db8::1:0")));
+        assert!(list.contains_v6(&v6_bytes("::1")));
+        assert!(!list.contains_v6(&v6_bytes("::2")));
+    }
+
+    #[test]
+    fn empty() {
+        let list = BlocklistBuilder::new().build();
+        assert!(list.is_empty());
+        assert!(!list.contains(&v4_bytes([0, 0, 0, 0])));
+        assert!(!list.contains_v6(&v6_bytes("::")));
+    }
+}
@@ -0,0 +1,331 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rolling bloom filter for suppressing recently seen addresses.
+//!
+//! The filter has a current and a previous generation. Addresses are inserted
+//! into the current generation and are seen while they are in either
+//! generation. The generations rotate when the current generation reaches its
+//! capacity or the rotation interval elapses, so an address is remembered for
+//! at least one interval (unless the capacity is reached first) and at most
+//! two.
+//!
+//! Addresses are hashed by their compact bytes with keyed SipHash-2-4.
+//!
+//! The interval starts at the first insertion. `wasm32-unknown-unknown` has no
+//! clock, so only the methods which take the current instant are available
+//! there.
+
+use crate::siphash;
+use std::{
+    f64::consts::LN_2,
+    mem,
+    time::{Duration, Instant},
+};
+
+/// A time-rotating bloom filter keyed on compact bytes.
+#[derive(Clone, Debug)]
+pub struct RollingBloomFilter {
+    key: [u8; 16],
+    capacity: usize,
+    hash_count: u32,
+    bit_len: u64,
+    current: Vec<u64>,
+    previous: Vec<u64>,
+    current_len: usize,
+    interval: Duration,
+    rotated_at: Option<Instant>,
+}
+
+impl RollingBloomFilter {
+    /// Instantiates a filter with an all-zero hash key.
+    ///
+    /// Each generation holds `capacity` addresses with the false positive
+    /// rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the false positive rate is not between 0 and 1 exclusive.
+    pub fn new(capacity: usize, false_positive_rate: f64, interval: Duration) -> Self {
+        Self::with_key(capacity, false_positive_rate, interval, [0; 16])
+    }
+
+    /// Instantiates a filter with the hash key.
+    ///
+    /// A secret random key prevents others from choosing addresses which
+    /// collide in the filter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the false positive rate is not between 0 and 1 exclusive.
+    pub fn with_key(
+        capacity: usize,
+        false_positive_rate: f64,
+        interval: Duration,
+        key: [u8; 16],
+    ) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "false positive rate must be between 0 and 1 exclusive"
+        );
+        let capacity = capacity.max(1);
+
+        // m = -n ln(p) / ln(2)^2 and k = m / n ln(2)
+        let bit_len = (-(capacity as f64) * false_positive_rate.ln() / (LN_2 * LN_2)).ceil();
+        let bit_len = (bit_len as u64).max(64);
+        let hash_count = ((bit_len as f64 / capacity as f64) * LN_2).round();
+        let hash_count = (hash_count as u32).clamp(1, 32);
+
+        let words = bit_len.div_ceil(64) as usize;
+        Self {
+            key,
+            capacity,
+            hash_count,
+            bit_len: words as u64 * 64,
+            current: vec![0; words],
+            previous: vec![0; words],
+            current_len: 0,
+            interval,
+            rotated_at: None,
+        }
+    }
+
+    /// Returns the number of addresses each generation holds.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the rotation interval.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Returns the number of bits in each generation.
+    pub fn bit_len(&self) -> u64 {
+        self.bit_len
+    }
+
+    /// Returns the number of hash functions.
+    pub fn hash_count(&self) -> u32 {
+        self.hash_count
+    }
+
+    /// Inserts the compact bytes of an address.
+    ///
+    /// Returns true if the address was not seen.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn insert<A: AsRef<[u8]>>(&mut self, addr: A) -> bool {
+        self.insert_at(addr, Instant::now())
+    }
+
+    /// Inserts the compact bytes of an address at the instant.
+    ///
+    /// Returns true if the address was not seen.
+    pub fn insert_at<A: AsRef<[u8]>>(&mut self, addr: A, now: Instant) -> bool {
+        self.rotate_if_due(now);
+
+        let (h1, h2) = self.hashes(addr.as_ref());
+        if self.is_set(&self.current, h1, h2) {
+            return false;
+        }
+        let seen = self.is_set(&self.previous, h1, h2);
+
+        if self.current_len >= self.capacity {
+            self.rotate(now);
+        }
+        for idx in self.bit_indexes(h1, h2) {
+            self.current[(idx / 64) as usize] |= 1 << (idx % 64);
+        }
+        self.current_len += 1;
+        !seen
+    }
+
+    /// Returns true if the address was seen.
+    ///
+    /// False positives are possible, but false negatives are not while the
+    /// address is within its generations.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn contains<A: AsRef<[u8]>>(&self, addr: A) -> bool {
+        self.contains_at(addr, Instant::now())
+    }
+
+    /// Returns true if the address was seen, as of the instant.
+    pub fn contains_at<A: AsRef<[u8]>>(&self, addr: A, now: Instant) -> bool {
+        let elapsed = self.elapsed(now);
+        let (h1, h2) = self.hashes(addr.as_ref());
+        if elapsed >= self.interval.saturating_mul(2) {
+            false
+        } else if elapsed >= self.interval {
+            self.is_set(&self.current, h1, h2)
+        } else {
+            self.is_set(&self.current, h1, h2) || self.is_set(&self.previous, h1, h2)
+        }
+    }
+
+    /// Rotates the generations if the rotation interval has elapsed.
+    pub fn rotate_if_due(&mut self, now: Instant) {
+        if self.rotated_at.is_none() {
+            self.rotated_at = Some(now);
+        }
+        let elapsed = self.elapsed(now);
+        if elapsed >= self.interval.saturating_mul(2) {
+            self.clear_at(now);
+        } else if elapsed >= self.interval {
+            self.rotate(now);
+        }
+    }
+
+    /// Removes all addresses.
+    ///
+    /// The interval restarts at the next insertion.
+    pub fn clear(&mut self) {
+        self.current.iter_mut().for_each(|word| *word = 0);
+        self.previous.iter_mut().for_each(|word| *word = 0);
+        self.current_len = 0;
+        self.rotated_at = None;
+    }
+
+    fn clear_at(&mut self, now: Instant) {
+        self.clear();
+        self.rotated_at = Some(now);
+    }
+
+    fn rotate(&mut self, now: Instant) {
+        mem::swap(&mut self.current, &mut self.previous);
+        self.current.iter_mut().for_each(|word| *word = 0);
+        self.current_len = 0;
+        self.rotated_at = Some(now);
+    }
+
+    fn elapsed(&self, now: Instant) -> Duration {
+        self.rotated_at.map_or(Duration::ZERO, |rotated_at| {
+            now.saturating_duration_since(rotated_at)
+        })
+    }
+
+    fn hashes(&self, bytes: &[u8]) -> (u64, u64) {
+        let hash = siphash::hash(&self.key, bytes);
+        (hash, hash.rotate_left(32) | 1)
+    }
+
+    fn bit_indexes(&self, h1: u64, h2: u64) -> impl Iterator<Item = u64> {
+        let bit_len = self.bit_len;
+        (0..u64::from(self.hash_count)).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % bit_len)
+    }
+
+    fn is_set(&self, bits: &[u64], h1: u64, h2: u64) -> bool {
+        self.bit_indexes(h1, h2)
+            .all(|idx| bits[(idx / 64) as usize] & (1 << (idx % 64)) != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(10);
+
+    fn addr(n: u32) -> [u8; 6] {
+        let ip = n.to_be_bytes();
+        [ip[0], ip[1], ip[2], ip[3], 0x1a, 0xe1]
+    }
+
+    #[test]
+    fn rotates_on_capacity() {
+        let now = Instant::now();
+        let mut filter = RollingBloomFilter::new(4, 0.001, INTERVAL);
+        for n in 0..4 {
+            assert!(filter.insert_at(addr(n), now));
+        }
+        assert_eq!(filter.current_len, 4);
+
+        assert!(filter.insert_at(addr(4), now));
+        assert_eq!(filter.current_len, 1);
+        assert!((0..5).all(|n| filter.contains_at(addr(n), now)));
+
+        for n in 5..9 {
+            assert!(filter.insert_at(addr(n), now));
+        }
+        assert!((0..4).all(|n| !filter.contains_at(addr(n), now)));
+        assert!((4..9).all(|n| filter.contains_at(addr(n), now)));
+    }
+
+    #[test]
+    fn rotates_on_interval() {
+        let now = Instant::now();
+        let mut filter = RollingBloomFilter::new(100, 0.001, INTERVAL);
+        filter.insert_at(addr(1), now);
+        assert!(filter.contains_at(addr(1), now + INTERVAL / 2));
+
+        let later = now + INTERVAL + Duration::from_secs(1);
+        assert!(filter.contains_at(addr(1), later));
+        filter.insert_at(addr(2), later);
+        assert!(filter.contains_at(addr(1), later));
+        assert!(filter.contains_at(addr(2), later));
+
+        let later = later + INTERVAL;
+        filter.rotate_if_due(later);
+        assert!(!filter.contains_at(addr(1), later));
+        assert!(filter.contains_at(addr(2), later));
+    }
+
+    #[test]
+    fn clears_after_two_intervals() {
+        let now = Instant::now();
+        let mut filter = RollingBloomFilter::new(100, 0.001, INTERVAL);
+        filter.insert_at(addr(1), now);
+        filter.insert_at(addr(2), now + INTERVAL);
+
+        let later = now + INTERVAL * 3;
+        assert!(!filter.contains_at(addr(1), later));
+        assert!(!filter.contains_at(addr(2), later));
+        assert!(filter.insert_at(addr(1), later));
+        assert!(!filter.contains_at(addr(2), later));
+        assert_eq!(filter.current_len, 1);
+    }
+
+    #[test]
+    fn insert_of_previous_generation_is_seen() {
+        let now = Instant::now();
+        let mut filter = RollingBloomFilter::new(100, 0.001, INTERVAL);
+        assert!(filter.insert_at(addr(1), now));
+        assert!(!filter.insert_at(addr(1), now));
+
+        let later = now + INTERVAL;
+        assert!(!filter.insert_at(addr(1), later));
+        assert!(filter.contains_at(addr(1), later + INTERVAL / 2));
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let now = Instant::now();
+        let mut filter = RollingBloomFilter::with_key(1000, 0.01, INTERVAL, [7; 16]);
+        for n in 0..1000 {
+            filter.insert_at(addr(n), now);
+        }
+        assert!((0..1000).all(|n| filter.contains_at(addr(n), now)));
+
+        let false_positives = (1000..11_000)
+            .filter(|n| filter.contains_at(addr(*n), now))
+            .count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn clear_restarts_interval() {
+        let now = Instant::now();
+        let mut filter = RollingBloomFilter::new(100, 0.001, INTERVAL);
+        filter.insert_at(addr(1), now);
+        filter.clear();
+        assert!(!filter.contains_at(addr(1), now));
+
+        let later = now + INTERVAL * 5;
+        filter.insert_at(addr(2), later);
+        assert!(filter.contains_at(addr(2), later + INTERVAL / 2));
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Socket addresses cached with their compact encoding.
+//!
+//! A [`Compact`] holds a socket address and its compact bytes, which are
+//! computed once when the value is created. Both forms can then be read
+//! without re-encoding or decoding.
+
+use crate::{private, CompactAddrV4, CompactAddrV4Info, CompactAddrV6, CompactAddrV6Info};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6},
+};
+
+/// A socket address type with a fixed-length compact encoding.
+///
+/// This trait is sealed and cannot be implemented for types outside this crate.
+pub trait CompactRepr: private::Sealed + Copy {
+    /// The compact encoding.
+    type Bytes: Copy + AsRef<[u8]>;
+
+    /// Returns the compact encoding of the address.
+    fn to_compact_bytes(&self) -> Self::Bytes;
+
+    /// Decodes the address from the compact encoding.
+    fn from_compact_bytes(bytes: &Self::Bytes) -> Self;
+}
+
+impl CompactRepr for SocketAddrV4 {
+    type Bytes = [u8; 6];
+
+    fn to_compact_bytes(&self) -> Self::Bytes {
+        self.to_compact_address()
+    }
+
+    fn from_compact_bytes(bytes: &Self::Bytes) -> Self {
+        Self::from_compact(*bytes)
+    }
+}
+
+impl CompactRepr for SocketAddrV6 {
+    type Bytes = [u8; 18];
+
+    fn to_compact_bytes(&self) -> Self::Bytes {
+        self.to_compact_address()
+    }
+
+    fn from_compact_bytes(bytes: &Self::Bytes) -> Self {
+        Self::from_compact(*bytes)
+    }
+}
+
+/// A socket address together with its compact encoding.
+///
+/// Values are equal if the socket addresses are equal. The hash is computed
+/// from the compact bytes only.
+#[derive(Clone, Copy, Debug)]
+pub struct Compact<T: CompactRepr> {
+    addr: T,
+    bytes: T::Bytes,
+}
+
+impl<T: CompactRepr> Compact<T> {
+    /// Instantiates a new value by encoding the socket address.
+    pub fn new(addr: T) -> Self {
+        Self {
+            addr,
+            bytes: addr.to_compact_bytes(),
+        }
+    }
+
+    /// Instantiates a new value by decoding the compact bytes.
+    pub fn from_bytes(bytes: T::Bytes) -> Self {
+        Self {
+            addr: T::from_compact_bytes(&bytes),
+            bytes,
+        }
+    }
+
+    /// Returns the socket address.
+    pub fn addr(&self) -> &T {
+        &self.addr
+    }
+
+    /// Returns the compact bytes.
+    pub fn as_bytes(&self) -> &T::Bytes {
+        &self.bytes
+    }
+
+    /// Returns the socket address.
+    pub fn into_inner(self) -> T {
+        self.addr
+    }
+}
+
+impl Compact<SocketAddrV4> {
+    /// Returns the IP address.
+    pub fn ip(&self) -> Ipv4Addr {
+        *self.addr.ip()
+    }
+
+    /// Returns the port.
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+}
+
+impl Compact<SocketAddrV6> {
+    /// Returns the IP address.
+    pub fn ip(&self) -> Ipv6Addr {
+        *self.addr.ip()
+    }
+
+    /// Returns the port.
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+}
+
+impl<T: CompactRepr + PartialEq> PartialEq for Compact<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.addr == other.addr
+    }
+}
+
+impl<T: CompactRepr + Eq> Eq for Compact<T> {}
+
+impl<T: CompactRepr> Hash for Compact<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bytes.as_ref().hash(state);
+    }
+}
+
+impl<T: CompactRepr> AsRef<[u8]> for Compact<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.bytes.as_ref()
+    }
+}
+
+impl<T: CompactRepr + fmt::Display> fmt::Display for Compact<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.addr, f)
+    }
+}
+
+impl From<SocketAddrV4> for Compact<SocketAddrV4> {
+    fn from(addr: SocketAddrV4) -> Self {
+        Self::new(addr)
+    }
+}
+
+impl From<SocketAddrV6> for Compact<SocketAddrV6> {
+    fn from(addr: SocketAddrV6) -> Self {
+        Self::new(addr)
+    }
+}
+
+impl From<CompactAddrV4> for Compact<SocketAddrV4> {
+    fn from(addr: CompactAddrV4) -> Self {
+        Self::from_bytes(*addr.as_bytes())
+    }
+}
+
+impl From<CompactAddrV6> for Compact<SocketAddrV6> {
+    fn from(addr: CompactAddrV6) -> Self {
+        Self::from_bytes(*addr.as_bytes())
+    }
+}
+
+impl From<Compact<SocketAddrV4>> for SocketAddrV4 {
+    fn from(value: Compact<SocketAddrV4>) -> Self {
+        value.addr
+    }
+}
+
+impl From<Compact<SocketAddrV6>> for SocketAddrV6 {
+    fn from(value: Compact<SocketAddrV6>) -> Self {
+        value.addr
+    }
+}
+
+impl From<Compact<SocketAddrV4>> for CompactAddrV4 {
+    fn from(value: Compact<SocketAddrV4>) -> Self {
+        CompactAddrV4::new(value.bytes)
+    }
+}
+
+impl From<Compact<SocketAddrV6>> for CompactAddrV6 {
+    fn from(value: Compact<SocketAddrV6>) -> Self {
+        CompactAddrV6::new(value.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn v4_forms() {
+        let addr = SocketAddrV4::new(Ipv4Addr::new(1, 2, 3, 4), 6881);
+        let value = Compact::new(addr);
+        assert_eq!(value.addr(), &addr);
+        assert_eq!(value.as_bytes(), &[1, 2, 3, 4, 0x1a, 0xe1]);
+        assert_eq!(value.ip(), Ipv4Addr::new(1, 2, 3, 4));
+        assert_eq!(value.port(), 6881);
+        assert_eq!(value.to_string(), "1.2.3.4:6881");
+        assert_eq!(
+            Compact::<SocketAddrV4>::from_bytes(*value.as_bytes()),
+            value
+        );
+        assert_eq!(SocketAddrV4::from(value), addr);
+        assert_eq!(CompactAddrV4::from(value), CompactAddrV4::from(addr));
+        assert_eq!(Compact::from(CompactAddrV4::from(addr)), value);
+    }
+
+    #[test]
+    fn v6_forms() {
+        let addr = SocketAddrV6::new(Ipv6Addr::LOCALHOST, 80, 0, 0);
+        let value = Compact::from(addr);
+        assert_eq!(value.ip(), Ipv6Addr::LOCALHOST);
+        assert_eq!(value.port(), 80);
+        assert_eq!(value.as_ref(), &addr.to_compact_address()[..]);
+        assert_eq!(value.into_inner(), addr);
+        assert_eq!(Compact::from(CompactAddrV6::from(addr)), value);
+        assert_eq!(CompactAddrV6::from(value), CompactAddrV6::from(addr));
+    }
+
+    #[test]
+    fn eq_and_hash() {
+        let addr = SocketAddrV6::new(Ipv6Addr::LOCALHOST, 80, 0, 0);
+        let scoped = SocketAddrV6::new(Ipv6Addr::LOCALHOST, 80, 0, 1);
+        assert_eq!(
+            Compact::new(addr),
+            Compact::from_bytes(addr.to_compact_address())
+        );
+        assert_ne!(Compact::new(addr), Compact::new(scoped));
+        assert_eq!(hash(&Compact::new(addr)), hash(&Compact::new(scoped)));
+        assert_eq!(
+            hash(&Compact::new(addr)),
+            hash(&&addr.to_compact_address()[..])
+        );
+    }
+}
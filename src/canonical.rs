@@ -0,0 +1,168 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Cross-family canonical keys for deduplication.
+//!
+//! The same peer may be seen as an IPv4 address and as an IPv6 address
+//! embedding the IPv4 address, such as the IPv4-mapped `::ffff:1.2.3.4` or a
+//! NAT64 address. A [`CanonicalKey`] maps those representations to the IPv4
+//! address so they compare and hash as equal.
+
+use crate::{CompactAddr, CompactAddrV4, CompactAddrV6};
+use std::{fmt, net::SocketAddrV4};
+
+/// A compact address in its canonical form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CanonicalKey(CompactAddr);
+
+impl CanonicalKey {
+    /// Returns the canonical key of the address.
+    ///
+    /// IPv4-mapped IPv6 addresses are mapped to IPv4 addresses. Use a
+    /// [`Canonicalizer`] to also map NAT64 addresses.
+    pub fn new<A: Into<CompactAddr>>(addr: A) -> Self {
+        let addr = addr.into();
+        match addr {
+            CompactAddr::V6(v6) => Self(mapped_v4(&v6).map_or(addr, CompactAddr::V4)),
+            CompactAddr::V4(_) => Self(addr),
+        }
+    }
+
+    /// Returns the canonical compact address.
+    pub fn addr(&self) -> CompactAddr {
+        self.0
+    }
+}
+
+impl From<CanonicalKey> for CompactAddr {
+    fn from(key: CanonicalKey) -> Self {
+        key.0
+    }
+}
+
+impl fmt::Display for CanonicalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Derives canonical keys with configured NAT64 prefixes.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Canonicalizer {
+    nat64_well_known: bool,
+    nat64_prefixes: Vec<[u8; 12]>,
+}
+
+impl Canonicalizer {
+    /// Instantiates a canonicalizer which only maps IPv4-mapped IPv6
+    /// addresses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets if addresses with the NAT64 well-known prefix (`64:ff9b::/96`)
+    /// are mapped to IPv4 addresses.
+    pub fn nat64_well_known(&mut self, enabled: bool) -> &mut Self {
+        self.nat64_well_known = enabled;
+        self
+    }
+
+    /// Adds a network-specific /96 NAT64 prefix whose addresses are mapped to
+    /// IPv4 addresses.
+    pub fn nat64_prefix(&mut self, prefix: [u8; 12]) -> &mut Self {
+        self.nat64_prefixes.push(prefix);
+        self
+    }
+
+    /// Returns the canonical key of the address.
+    pub fn key<A: Into<CompactAddr>>(&self, addr: A) -> CanonicalKey {
+        let addr = addr.into();
+        let v6 = match addr {
+            CompactAddr::V6(v6) => v6,
+            CompactAddr::V4(_) => return CanonicalKey(addr),
+        };
+
+        let v4 = mapped_v4(&v6)
+            .or_else(|| {
+                if self.nat64_well_known {
+                    v6.nat64_v4()
+                } else {
+                    None
+                }
+            })
+            .or_else(|| {
+                self.nat64_prefixes
+                    .iter()
+                    .find_map(|prefix| v6.nat64_v4_with_prefix(prefix))
+            });
+        CanonicalKey(v4.map_or(addr, CompactAddr::V4))
+    }
+}
+
+fn mapped_v4(addr: &CompactAddrV6) -> Option<CompactAddrV4> {
+    addr.ip()
+        .to_ipv4_mapped()
+        .map(|ip| CompactAddrV4::from(SocketAddrV4::new(ip, addr.port())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::addr;
+    use std::collections::HashSet;
+
+    #[test]
+    fn mapped_v6_is_v4() {
+        let key = CanonicalKey::new(addr("[::ffff:1.2.3.4]:80"));
+        assert_eq!(key, CanonicalKey::new(addr("1.2.3.4:80")));
+        assert_eq!(key.addr(), addr("1.2.3.4:80"));
+        assert_eq!(key.to_string(), "1.2.3.4:80");
+        assert_ne!(key, CanonicalKey::new(addr("[::ffff:1.2.3.4]:81")));
+    }
+
+    #[test]
+    fn other_v6_is_unchanged() {
+        for s in [
+            "[2001:db8::1]:80",
+            "[::1.2.3.4]:80",
+            "[64:ff9b::102:304]:80",
+        ] {
+            assert_eq!(CompactAddr::from(CanonicalKey::new(addr(s))), addr(s));
+        }
+    }
+
+    #[test]
+    fn canonicalizer_nat64() {
+        let nat64 = addr("[64:ff9b::102:304]:80");
+        let specific = addr("[2001:db8:64::102:304]:80");
+        let v4 = CanonicalKey::new(addr("1.2.3.4:80"));
+
+        let canonicalizer = Canonicalizer::new();
+        assert_eq!(canonicalizer.key(nat64).addr(), nat64);
+        assert_eq!(canonicalizer.key(addr("[::ffff:1.2.3.4]:80")), v4);
+
+        let mut canonicalizer = Canonicalizer::new();
+        canonicalizer
+            .nat64_well_known(true)
+            .nat64_prefix([0x20, 0x01, 0x0d, 0xb8, 0, 0x64, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(canonicalizer.key(nat64), v4);
+        assert_eq!(canonicalizer.key(specific), v4);
+        assert_eq!(canonicalizer.key(addr("1.2.3.4:80")), v4);
+    }
+
+    #[test]
+    fn dedup_with_keys() {
+        let addrs = [
+            addr("1.2.3.4:80"),
+            addr("[::ffff:1.2.3.4]:80"),
+            addr("[2001:db8::1]:80"),
+        ];
+        let keys: HashSet<_> = addrs.iter().copied().map(CanonicalKey::new).collect();
+        assert_eq!(keys.len(), 2);
+    }
+}
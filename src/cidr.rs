@@ -0,0 +1,132 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! CIDR containment checks with the [`ipnet`] crate.
+
+use crate::blocklist::{Blocklist, BlocklistBuilder};
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use std::iter::FromIterator;
+
+/// An IPv4 network which can test compact IPv4 addresses for containment.
+///
+/// This trait is sealed and cannot be implemented for types outside this crate.
+pub trait CompactNetV4: crate::private::Sealed {
+    /// Returns true if the IP address of the compact address is in the network.
+    ///
+    /// The port is ignored.
+    fn contains_compact(&self, addr: &[u8; 6]) -> bool;
+}
+
+impl CompactNetV4 for Ipv4Net {
+    fn contains_compact(&self, addr: &[u8; 6]) -> bool {
+        let mut ip: [u8; 4] = [0; 4];
+        ip.copy_from_slice(&addr[0..4]);
+        let ip = u32::from_be_bytes(ip);
+
+        let netmask = u32::from(self.netmask());
+        ip & netmask == u32::from(self.network())
+    }
+}
+
+/// An IPv6 network which can test compact IPv6 addresses for containment.
+///
+/// This trait is sealed and cannot be implemented for types outside this crate.
+pub trait CompactNetV6: crate::private::Sealed {
+    /// Returns true if the IP address of the compact address is in the network.
+    ///
+    /// The port is ignored.
+    fn contains_compact(&self, addr: &[u8; 18]) -> bool;
+}
+
+impl CompactNetV6 for Ipv6Net {
+    fn contains_compact(&self, addr: &[u8; 18]) -> bool {
+        let mut ip: [u8; 16] = [0; 16];
+        ip.copy_from_slice(&addr[0..16]);
+        let ip = u128::from_be_bytes(ip);
+
+        let netmask = u128::from(self.netmask());
+        ip & netmask == u128::from(self.network())
+    }
+}
+
+impl BlocklistBuilder {
+    /// Adds all addresses in an IPv4 network.
+    pub fn insert_net_v4(&mut self, net: Ipv4Net) -> &mut Self {
+        self.insert_v4(net.network(), net.broadcast())
+    }
+
+    /// Adds all addresses in an IPv6 network.
+    pub fn insert_net_v6(&mut self, net: Ipv6Net) -> &mut Self {
+        self.insert_v6(net.network(), net.broadcast())
+    }
+
+    /// Adds all addresses in a network.
+    pub fn insert_net(&mut self, net: IpNet) -> &mut Self {
+        match net {
+            IpNet::V4(net) => self.insert_net_v4(net),
+            IpNet::V6(net) => self.insert_net_v6(net),
+        }
+    }
+}
+
+impl Extend<IpNet> for BlocklistBuilder {
+    fn extend<T: IntoIterator<Item = IpNet>>(&mut self, iter: T) {
+        for net in iter {
+            self.insert_net(net);
+        }
+    }
+}
+
+impl FromIterator<IpNet> for Blocklist {
+    fn from_iter<T: IntoIterator<Item = IpNet>>(iter: T) -> Self {
+        let mut builder = BlocklistBuilder::new();
+        builder.extend(iter);
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{v4_bytes, v6_bytes};
+
+    #[test]
+    fn contains_compact_v4() {
+        let net: Ipv4Net = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains_compact(&v4_bytes([10, 1, 0, 0])));
+        assert!(net.contains_compact(&v4_bytes([10, 1, 255, 255])));
+        assert!(!net.contains_compact(&v4_bytes([10, 2, 0, 0])));
+
+        let all: Ipv4Net = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains_compact(&v4_bytes([255, 255, 255, 255])));
+    }
+
+    #[test]
+    fn contains_compact_v6() {
+        let net: Ipv6Net = "2001:db8::/32".parse().unwrap();
+        assert!(net.contains_compact(&v6_bytes("2001:db8:ffff::1")));
+        assert!(!net.contains_compact(&v6_bytes("2001:db9::1")));
+
+        let host: Ipv6Net = "::1/128".parse().unwrap();
+        assert!(host.contains_compact(&v6_bytes("::1")));
+        assert!(!host.contains_compact(&v6_bytes("::2")));
+    }
+
+    #[test]
+    fn blocklist_from_networks() {
+        let list: Blocklist = ["10.0.0.0/8", "10.1.0.0/16", "11.0.0.0/8", "2001:db8::/32"]
+            .iter()
+            .map(|net| net.parse::<IpNet>().unwrap())
+            .collect();
+        assert_eq!(list.v4_ranges_len(), 1);
+        assert_eq!(list.v6_ranges_len(), 1);
+        assert!(list.contains(&v4_bytes([11, 255, 255, 255])));
+        assert!(!list.contains(&v4_bytes([12, 0, 0, 0])));
+        assert!(list.contains_v6(&v6_bytes("2001:db8::1")));
+    }
+}
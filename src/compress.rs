@@ -0,0 +1,333 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Optional [`zstd`] compression of blob and text dump files.
+//!
+//! Readers detect compressed data by the zstd frame magic bytes, so
+//! compressed and uncompressed files can be read the same way. Blobs of
+//! compact addresses and text or CSV dumps are written with [`write_file`] or
+//! a [`Writer`].
+//!
+//! Peer cache files are written and read with
+//! [`PeerStore::export_v4_file`](crate::store::PeerStore::export_v4_file) and
+//! [`PeerStore::import_v4_file`](crate::store::PeerStore::import_v4_file)
+//! (and the IPv6 equivalents). Text and CSV dump files are written and read
+//! with [`write_text_file`](crate::text::write_text_file),
+//! [`read_text_file`](crate::text::read_text_file),
+//! [`write_csv_file`](crate::text::write_csv_file), and
+//! [`read_csv_file`](crate::text::read_csv_file).
+//!
+//! Compressed files cannot be memory-mapped with `MappedBlob`.
+
+use ::zstd::stream::{read::Decoder, write::Encoder};
+use std::{
+    borrow::Cow,
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Chain, Cursor, Read, Write},
+    path::Path,
+};
+
+/// The magic bytes at the start of a zstd frame.
+pub const MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The compression of written data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// The data is written as is.
+    #[default]
+    None,
+    /// The data is compressed with zstd at the compression level.
+    ///
+    /// A level of 0 uses zstd's default level.
+    Zstd(i32),
+}
+
+impl Compression {
+    /// Returns zstd compression at the default level.
+    pub fn zstd() -> Self {
+        Compression::Zstd(::zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+/// Returns true if the bytes start with the zstd frame magic bytes.
+pub fn is_compressed(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Returns the bytes compressed with zstd at the compression level.
+///
+/// # Errors
+///
+/// Returns an error if the compression level is invalid.
+pub fn compress(bytes: &[u8], level: i32) -> io::Result<Vec<u8>> {
+    ::zstd::stream::encode_all(bytes, level)
+}
+
+/// Returns the decompressed bytes if the bytes are compressed, otherwise the
+/// bytes as is.
+///
+/// # Errors
+///
+/// Returns an error if the compressed data is invalid.
+pub fn decompress(bytes: &[u8]) -> io::Result<Cow<'_, [u8]>> {
+    if is_compressed(bytes) {
+        ::zstd::stream::decode_all(bytes).map(Cow::Owned)
+    } else {
+        Ok(Cow::Borrowed(bytes))
+    }
+}
+
+/// Returns the contents of the file, decompressed if the file is compressed.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or the compressed data is
+/// invalid.
+pub fn read_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    Reader::new(File::open(path)?)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Returns the text contents of the file, decompressed if the file is
+/// compressed.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, the compressed data is
+/// invalid, or the contents are not UTF-8.
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut text = String::new();
+    Reader::new(File::open(path)?)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Writes the bytes to the file with the compression.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written or the compression level
+/// is invalid.
+pub fn write_file<P: AsRef<Path>>(
+    path: P,
+    bytes: &[u8],
+    compression: Compression,
+) -> io::Result<()> {
+    let mut writer = Writer::new(BufWriter::new(File::create(path)?), compression)?;
+    writer.write_all(bytes)?;
+    writer.finish()?.flush()
+}
+
+/// A reader which decompresses the data if it is compressed.
+pub struct Reader<R: Read> {
+    inner: ReaderInner<R>,
+}
+
+type Prefixed<R> = Chain<Cursor<Vec<u8>>, R>;
+
+enum ReaderInner<R: Read> {
+    Plain(Prefixed<R>),
+    Zstd(Decoder<'static, BufReader<Prefixed<R>>>),
+}
+
+impl<R: Read> Reader<R> {
+    /// Instantiates a reader which detects whether the data is compressed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the first bytes cannot be read.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = Vec::with_capacity(MAGIC.len());
+        (&mut reader)
+            .take(MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        let compressed = is_compressed(&magic);
+        let reader = Cursor::new(magic).chain(reader);
+        let inner = if compressed {
+            ReaderInner::Zstd(Decoder::new(reader)?)
+        } else {
+            ReaderInner::Plain(reader)
+        };
+        Ok(Self { inner })
+    }
+
+    /// Returns true if the data is compressed.
+    pub fn is_compressed(&self) -> bool {
+        matches!(self.inner, ReaderInner::Zstd(_))
+    }
+}
+
+impl<R: Read> Read for Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            ReaderInner::Plain(reader) => reader.read(buf),
+            ReaderInner::Zstd(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+impl<R: Read> fmt::Debug for Reader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reader")
+            .field("compressed", &self.is_compressed())
+            .finish()
+    }
+}
+
+/// A writer which compresses the data with the compression.
+///
+/// [`Writer::finish`] must be called to write the end of the compressed data.
+pub struct Writer<W: Write> {
+    inner: WriterInner<W>,
+}
+
+enum WriterInner<W: Write> {
+    Plain(W),
+    Zstd(Encoder<'static, W>),
+}
+
+impl<W: Write> Writer<W> {
+    /// Instantiates a writer with the compression.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compression level is invalid.
+    pub fn new(writer: W, compression: Compression) -> io::Result<Self> {
+        let inner = match compression {
+            Compression::None => WriterInner::Plain(writer),
+            Compression::Zstd(level) => WriterInner::Zstd(Encoder::new(writer, level)?),
+        };
+        Ok(Self { inner })
+    }
+
+    /// Returns true if the data is compressed.
+    pub fn is_compressed(&self) -> bool {
+        matches!(self.inner, WriterInner::Zstd(_))
+    }
+
+    /// Writes the end of the compressed data and returns the inner writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data cannot be written.
+    pub fn finish(self) -> io::Result<W> {
+        match self.inner {
+            WriterInner::Plain(writer) => Ok(writer),
+            WriterInner::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            WriterInner::Plain(writer) => writer.write(buf),
+            WriterInner::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            WriterInner::Plain(writer) => writer.flush(),
+            WriterInner::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+impl<W: Write> fmt::Debug for Writer<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Writer")
+            .field("compressed", &self.is_compressed())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+    use std::fs;
+
+    fn blob() -> Vec<u8> {
+        (0..64u8).flat_map(|n| [10, 0, 0, n, 0x1a, 0xe1]).collect()
+    }
+
+    #[test]
+    fn compress_round_trip() {
+        let bytes = blob();
+        let compressed = compress(&bytes, 0).unwrap();
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < bytes.len());
+        assert_eq!(decompress(&compressed).unwrap(), &bytes[..]);
+    }
+
+    #[test]
+    fn decompress_plain() {
+        let bytes = blob();
+        assert!(!is_compressed(&bytes));
+        assert!(matches!(decompress(&bytes).unwrap(), Cow::Borrowed(_)));
+        assert!(matches!(decompress(&[]).unwrap(), Cow::Borrowed(&[])));
+        assert!(!is_compressed(&MAGIC[..3]));
+    }
+
+    #[test]
+    fn decompress_invalid() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[0xff; 8]);
+        assert!(decompress(&bytes).is_err());
+    }
+
+    #[test]
+    fn writer_and_reader() {
+        let bytes = blob();
+        for compression in [Compression::None, Compression::zstd(), Compression::Zstd(0)] {
+            let mut writer = Writer::new(Vec::new(), compression).unwrap();
+            assert_eq!(writer.is_compressed(), compression != Compression::None);
+            writer.write_all(&bytes).unwrap();
+            let written = writer.finish().unwrap();
+            assert_eq!(is_compressed(&written), compression != Compression::None);
+
+            let mut reader = Reader::new(&written[..]).unwrap();
+            assert_eq!(reader.is_compressed(), compression != Compression::None);
+            let mut read = Vec::new();
+            reader.read_to_end(&mut read).unwrap();
+            assert_eq!(read, bytes);
+        }
+    }
+
+    #[test]
+    fn reader_short_input() {
+        let mut read = Vec::new();
+        let mut reader = Reader::new(&[1, 2][..]).unwrap();
+        assert!(!reader.is_compressed());
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, [1, 2]);
+
+        let mut reader = Reader::new(&[][..]).unwrap();
+        assert_eq!(reader.read(&mut [0; 4]).unwrap(), 0);
+        assert_eq!(format!("{:?}", reader), "Reader { compressed: false }");
+    }
+
+    #[test]
+    fn files() {
+        let text = "10.0.0.1:6881\n10.0.0.2:6881\n";
+        for (name, compression) in [("plain", Compression::None), ("zstd", Compression::zstd())] {
+            let path = temp_path(name);
+            write_file(&path, text.as_bytes(), compression).unwrap();
+            assert_eq!(
+                is_compressed(&fs::read(&path).unwrap()),
+                compression != Compression::None
+            );
+            assert_eq!(read_file(&path).unwrap(), text.as_bytes());
+            assert_eq!(read_to_string(&path).unwrap(), text);
+            fs::remove_file(&path).unwrap();
+        }
+        assert!(read_file(temp_path("missing")).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Compact addresses paired with a transport.
+//!
+//! A [`CompactEndpoint`] is encoded as a transport byte followed by the
+//! [tagged] compact address:
+//!
+//! | Transport byte | Transport |
+//! |----------------|-----------|
+//! | `0x01`         | TCP       |
+//! | `0x02`         | UDP       |
+//! | `0x03`         | uTP       |
+//!
+//! Lists are encoded as the concatenation of endpoints.
+
+use crate::{tagged, CompactAddr};
+use std::{error, fmt, net::IpAddr};
+
+/// The transport protocol of an endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Transport {
+    /// TCP.
+    Tcp,
+    /// UDP.
+    Udp,
+    /// uTP (the micro transport protocol over UDP).
+    Utp,
+}
+
+impl Transport {
+    /// Returns the transport byte.
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Transport::Tcp => 0x01,
+            Transport::Udp => 0x02,
+            Transport::Utp => 0x03,
+        }
+    }
+
+    /// Returns the transport for the transport byte.
+    pub fn from_byte(value: u8) -> Option<Self> {
+        match value {
+            0x01 => Some(Transport::Tcp),
+            0x02 => Some(Transport::Udp),
+            0x03 => Some(Transport::Utp),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Transport::Tcp => "tcp",
+            Transport::Udp => "udp",
+            Transport::Utp => "utp",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A compact address reachable over a transport.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CompactEndpoint {
+    addr: CompactAddr,
+    transport: Transport,
+}
+
+impl CompactEndpoint {
+    /// Instantiates a new endpoint.
+    pub fn new<A: Into<CompactAddr>>(addr: A, transport: Transport) -> Self {
+        Self {
+            addr: addr.into(),
+            transport,
+        }
+    }
+
+    /// Returns the compact address.
+    pub fn addr(&self) -> CompactAddr {
+        self.addr
+    }
+
+    /// Returns the transport.
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Returns the IP address.
+    pub fn ip(&self) -> IpAddr {
+        self.addr.ip()
+    }
+
+    /// Returns the port.
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Returns the length of the encoding.
+    pub fn encoded_len(&self) -> usize {
+        1 + self.addr.tagged_len()
+    }
+
+    /// Appends the encoding to the buffer.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.transport.to_byte());
+        self.addr.encode_tagged(buf);
+    }
+
+    /// Returns the encoding.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.encode(&mut buf);
+        buf
+    }
+
+    /// Decodes an endpoint from the start of the bytes.
+    ///
+    /// Returns the endpoint and the remaining bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transport or address tag is unknown or there
+    /// are not enough bytes for the endpoint.
+    pub fn decode(bytes: &[u8]) -> Result<(Self, &[u8]), DecodeError> {
+        let (&transport, rest) =
+            bytes
+                .split_first()
+                .ok_or(DecodeError::Addr(tagged::DecodeError::UnexpectedEnd {
+                    expected: 1,
+                    actual: 0,
+                }))?;
+        let transport =
+            Transport::from_byte(transport).ok_or(DecodeError::UnknownTransport(transport))?;
+        let (addr, rest) = CompactAddr::decode_tagged(rest)?;
+        Ok((Self { addr, transport }, rest))
+    }
+}
+
+impl fmt::Display for CompactEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.transport)
+    }
+}
+
+/// Returns the concatenated encodings of the endpoints.
+pub fn encode_list<'a, I>(endpoints: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = &'a CompactEndpoint>,
+{
+    let mut buf = Vec::new();
+    for endpoint in endpoints {
+        endpoint.encode(&mut buf);
+    }
+    buf
+}
+
+/// Decodes concatenated endpoints.
+///
+/// # Errors
+///
+/// Returns an error if any transport or address tag is unknown or the bytes
+/// end in the middle of an endpoint.
+pub fn decode_list(mut bytes: &[u8]) -> Result<Vec<CompactEndpoint>, DecodeError> {
+    let mut endpoints = Vec::new();
+    while !bytes.is_empty() {
+        let (endpoint, rest) = CompactEndpoint::decode(bytes)?;
+        endpoints.push(endpoint);
+        bytes = rest;
+    }
+    Ok(endpoints)
+}
+
+/// An error when decoding compact endpoints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The transport byte is not a known transport.
+    UnknownTransport(u8),
+    /// The tagged address is invalid.
+    Addr(tagged::DecodeError),
+}
+
+impl From<tagged::DecodeError> for DecodeError {
+    fn from(e: tagged::DecodeError) -> Self {
+        DecodeError::Addr(e)
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnknownTransport(value) => {
+                write!(f, "unknown transport: {:#04x}", value)
+            }
+            DecodeError::Addr(e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DecodeError::UnknownTransport(_) => None,
+            DecodeError::Addr(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    fn endpoint(transport: Transport) -> CompactEndpoint {
+        CompactEndpoint::new("1.2.3.4:6881".parse::<CompactAddr>().unwrap(), transport)
+    }
+
+    #[test]
+    fn transport_bytes() {
+        for transport in [Transport::Tcp, Transport::Udp, Transport::Utp] {
+            assert_eq!(Transport::from_byte(transport.to_byte()), Some(transport));
+        }
+        assert_eq!(Transport::from_byte(0), None);
+        assert_eq!(Transport::from_byte(4), None);
+    }
+
+    #[test]
+    fn endpoint_round_trip() {
+        let endpoint = endpoint(Transport::Utp);
+        let bytes = endpoint.to_vec();
+        assert_eq!(bytes, [0x03, tagged::TAG_V4, 1, 2, 3, 4, 0x1a, 0xe1]);
+        assert_eq!(bytes.len(), endpoint.encoded_len());
+        assert_eq!(CompactEndpoint::decode(&bytes), Ok((endpoint, &[][..])));
+        assert_eq!(endpoint.port(), 6881);
+        assert_eq!(endpoint.to_string(), "1.2.3.4:6881/utp");
+    }
+
+    #[test]
+    fn endpoint_malformed() {
+        assert_eq!(
+            CompactEndpoint::decode(&[]),
+            Err(DecodeError::Addr(tagged::DecodeError::UnexpectedEnd {
+                expected: 1,
+                actual: 0
+            }))
+        );
+        assert_eq!(
+            CompactEndpoint::decode(&[0x09, tagged::TAG_V4]),
+            Err(DecodeError::UnknownTransport(0x09))
+        );
+        let err = CompactEndpoint::decode(&[0x01, 0x05]).unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::Addr(tagged::DecodeError::UnknownTag(0x05))
+        );
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    fn endpoint_list() {
+        let endpoints = vec![
+            endpoint(Transport::Tcp),
+            CompactEndpoint::new("[::1]:80".parse::<CompactAddr>().unwrap(), Transport::Udp),
+        ];
+        let bytes = encode_list(&endpoints);
+        assert_eq!(bytes.len(), 8 + 20);
+        assert_eq!(decode_list(&bytes), Ok(endpoints));
+        assert!(decode_list(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,419 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Compact addresses for anonymity network transports alongside IP addresses.
+//!
+//! Each [`ExtendedCompactAddr`] is encoded as a tag byte followed by the
+//! variant's compact bytes:
+//!
+//! | Tag    | Variant | Payload                                          |
+//! |--------|---------|--------------------------------------------------|
+//! | `0x04` | IPv4    | 4 byte IP address, 2 byte port                   |
+//! | `0x06` | IPv6    | 16 byte IP address, 2 byte port                  |
+//! | `0x07` | Scoped  | 16 byte IP address, 2 byte port, 4 byte scope ID |
+//! | `0x10` | Onion   | 32 byte v3 onion service key, 2 byte port        |
+//! | `0x11` | I2P     | 32 byte destination hash, 2 byte port            |
+//!
+//! Lists are encoded as the concatenation of tagged addresses.
+
+use crate::{tagged, zone, CompactAddrV4, CompactAddrV6, ParseAddrError};
+use std::{fmt, net::SocketAddrV6, str::FromStr};
+
+pub use crate::tagged::{DecodeError, TAG_V4, TAG_V6};
+
+/// Tag for a scoped IPv6 compact address.
+pub const TAG_SCOPED_V6: u8 = 0x07;
+/// Tag for an onion service compact address.
+pub const TAG_ONION: u8 = 0x10;
+/// Tag for an I2P destination compact address.
+pub const TAG_I2P: u8 = 0x11;
+
+/// An IPv6 compact address with the scope ID of a link-local address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScopedAddrV6 {
+    /// The compact address.
+    pub addr: CompactAddrV6,
+    /// The scope ID.
+    pub scope_id: u32,
+}
+
+impl From<SocketAddrV6> for ScopedAddrV6 {
+    /// Converts the socket address. The flow info is not kept.
+    fn from(addr: SocketAddrV6) -> Self {
+        Self {
+            addr: CompactAddrV6::from(addr),
+            scope_id: addr.scope_id(),
+        }
+    }
+}
+
+impl From<ScopedAddrV6> for SocketAddrV6 {
+    fn from(addr: ScopedAddrV6) -> Self {
+        let socket_addr = SocketAddrV6::from(addr.addr);
+        SocketAddrV6::new(*socket_addr.ip(), socket_addr.port(), 0, addr.scope_id)
+    }
+}
+
+impl FromStr for ScopedAddrV6 {
+    type Err = ParseAddrError;
+
+    /// Parses an IPv6 socket address with an optional zone identifier such as
+    /// `[fe80::1%3]:6881`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        zone::parse_v6(s).map(Self::from)
+    }
+}
+
+impl fmt::Display for ScopedAddrV6 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&SocketAddrV6::from(*self), f)
+    }
+}
+
+/// A Tor v3 onion service endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OnionAddr {
+    /// The ed25519 public key of the onion service.
+    pub public_key: [u8; 32],
+    /// The virtual port.
+    pub port: u16,
+}
+
+/// An I2P destination endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct I2pAddr {
+    /// The SHA-256 hash of the destination.
+    pub hash: [u8; 32],
+    /// The port.
+    pub port: u16,
+}
+
+/// A compact address for an IP or anonymity network transport.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ExtendedCompactAddr {
+    /// An IPv4 compact address.
+    V4(CompactAddrV4),
+    /// An IPv6 compact address.
+    V6(CompactAddrV6),
+    /// An IPv6 compact address with a scope ID.
+    ScopedV6(ScopedAddrV6),
+    /// An onion service endpoint.
+    Onion(OnionAddr),
+    /// An I2P destination endpoint.
+    I2p(I2pAddr),
+}
+
+impl ExtendedCompactAddr {
+    /// Returns the tag byte for the variant.
+    pub fn tag(&self) -> u8 {
+        match self {
+            ExtendedCompactAddr::V4(_) => TAG_V4,
+            ExtendedCompactAddr::V6(_) => TAG_V6,
+            ExtendedCompactAddr::ScopedV6(_) => TAG_SCOPED_V6,
+            ExtendedCompactAddr::Onion(_) => TAG_ONION,
+            ExtendedCompactAddr::I2p(_) => TAG_I2P,
+        }
+    }
+
+    /// Returns the length of the tagged encoding.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            ExtendedCompactAddr::V4(_) => 7,
+            ExtendedCompactAddr::V6(_) => 19,
+            ExtendedCompactAddr::ScopedV6(_) => 23,
+            ExtendedCompactAddr::Onion(_) | ExtendedCompactAddr::I2p(_) => 35,
+        }
+    }
+
+    /// Appends the tagged encoding to the buffer.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.tag());
+        match self {
+            ExtendedCompactAddr::V4(addr) => buf.extend_from_slice(addr.as_bytes()),
+            ExtendedCompactAddr::V6(addr) => buf.extend_from_slice(addr.as_bytes()),
+            ExtendedCompactAddr::ScopedV6(addr) => {
+                buf.extend_from_slice(addr.addr.as_bytes());
+                buf.extend_from_slice(&addr.scope_id.to_be_bytes());
+            }
+            ExtendedCompactAddr::Onion(addr) => {
+                buf.extend_from_slice(&addr.public_key);
+                buf.extend_from_slice(&addr.port.to_be_bytes());
+            }
+            ExtendedCompactAddr::I2p(addr) => {
+                buf.extend_from_slice(&addr.hash);
+                buf.extend_from_slice(&addr.port.to_be_bytes());
+            }
+        }
+    }
+
+    /// Returns the tagged encoding.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.encode(&mut buf);
+        buf
+    }
+
+    /// Decodes a tagged address from the start of the bytes.
+    ///
+    /// Returns the address and the remaining bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tag is unknown or there are not enough bytes
+    /// for the tagged variant.
+    pub fn decode(bytes: &[u8]) -> Result<(Self, &[u8]), DecodeError> {
+        let (tag, payload, rest) = tagged::split_tagged(bytes, payload_len)?;
+        let addr = match tag {
+            TAG_V4 => {
+                let mut a: [u8; 6] = [0; 6];
+                a.copy_from_slice(payload);
+                ExtendedCompactAddr::V4(CompactAddrV4::new(a))
+            }
+            TAG_V6 => {
+                let mut a: [u8; 18] = [0; 18];
+                a.copy_from_slice(payload);
+                ExtendedCompactAddr::V6(CompactAddrV6::new(a))
+            }
+            TAG_SCOPED_V6 => {
+                let mut a: [u8; 18] = [0; 18];
+                a.copy_from_slice(&payload[0..18]);
+                let scope_id =
+                    u32::from_be_bytes([payload[18], payload[19], payload[20], payload[21]]);
+                ExtendedCompactAddr::ScopedV6(ScopedAddrV6 {
+                    addr: CompactAddrV6::new(a),
+                    scope_id,
+                })
+            }
+            TAG_ONION => {
+                let (public_key, port) = key_and_port(payload);
+                ExtendedCompactAddr::Onion(OnionAddr { public_key, port })
+            }
+            TAG_I2P => {
+                let (hash, port) = key_and_port(payload);
+                ExtendedCompactAddr::I2p(I2pAddr { hash, port })
+            }
+            _ => unreachable!(),
+        };
+
+        Ok((addr, rest))
+    }
+}
+
+impl From<CompactAddrV4> for ExtendedCompactAddr {
+    fn from(addr: CompactAddrV4) -> Self {
+        ExtendedCompactAddr::V4(addr)
+    }
+}
+
+impl From<CompactAddrV6> for ExtendedCompactAddr {
+    fn from(addr: CompactAddrV6) -> Self {
+        ExtendedCompactAddr::V6(addr)
+    }
+}
+
+impl From<ScopedAddrV6> for ExtendedCompactAddr {
+    fn from(addr: ScopedAddrV6) -> Self {
+        ExtendedCompactAddr::ScopedV6(addr)
+    }
+}
+
+impl From<OnionAddr> for ExtendedCompactAddr {
+    fn from(addr: OnionAddr) -> Self {
+        ExtendedCompactAddr::Onion(addr)
+    }
+}
+
+impl From<I2pAddr> for ExtendedCompactAddr {
+    fn from(addr: I2pAddr) -> Self {
+        ExtendedCompactAddr::I2p(addr)
+    }
+}
+
+impl FromStr for ExtendedCompactAddr {
+    type Err = ParseAddrError;
+
+    /// Parses an IP socket address.
+    ///
+    /// An IPv6 address with a non-zero zone identifier such as
+    /// `[fe80::1%3]:6881` is parsed as a scoped IPv6 address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('[') {
+            let addr = zone::parse_v6(s)?;
+            if addr.scope_id() == 0 {
+                Ok(ExtendedCompactAddr::V6(CompactAddrV6::from(addr)))
+            } else {
+                Ok(ExtendedCompactAddr::ScopedV6(ScopedAddrV6::from(addr)))
+            }
+        } else {
+            s.parse::<CompactAddrV4>().map(ExtendedCompactAddr::V4)
+        }
+    }
+}
+
+/// Returns the concatenated tagged encodings of the addresses.
+pub fn encode_list<'a, I>(addrs: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = &'a ExtendedCompactAddr>,
+{
+    tagged::encode_tagged_list(addrs, ExtendedCompactAddr::encode)
+}
+
+/// Decodes concatenated tagged addresses.
+///
+/// # Errors
+///
+/// Returns an error if any tag is unknown or the bytes end in the middle of
+/// an address.
+pub fn decode_list(bytes: &[u8]) -> Result<Vec<ExtendedCompactAddr>, DecodeError> {
+    tagged::decode_tagged_list(bytes, ExtendedCompactAddr::decode)
+}
+
+fn payload_len(tag: u8) -> Option<usize> {
+    match tag {
+        TAG_V4 => Some(6),
+        TAG_V6 => Some(18),
+        TAG_SCOPED_V6 => Some(22),
+        TAG_ONION | TAG_I2P => Some(34),
+        _ => None,
+    }
+}
+
+fn key_and_port(payload: &[u8]) -> ([u8; 32], u16) {
+    let mut key: [u8; 32] = [0; 32];
+    key.copy_from_slice(&payload[0..32]);
+    let port = u16::from_be_bytes([payload[32], payload[33]]);
+    (key, port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addrs() -> Vec<ExtendedCompactAddr> {
+        vec![
+            ExtendedCompactAddr::V4(CompactAddrV4::new([1, 2, 3, 4, 0x1a, 0xe1])),
+            ExtendedCompactAddr::V6(CompactAddrV6::new([
+                0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 80,
+            ])),
+            ExtendedCompactAddr::Onion(OnionAddr {
+                public_key: [0xab; 32],
+                port: 443,
+            }),
+            ExtendedCompactAddr::I2p(I2pAddr {
+                hash: [0xcd; 32],
+                port: 0,
+            }),
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        for addr in addrs() {
+            let bytes = addr.to_vec();
+            assert_eq!(bytes.len(), addr.encoded_len());
+            assert_eq!(bytes[0], addr.tag());
+            assert_eq!(ExtendedCompactAddr::decode(&bytes), Ok((addr, &[][..])));
+        }
+    }
+
+    #[test]
+    fn round_trip_list() {
+        let addrs = addrs();
+        let bytes = encode_list(&addrs);
+        assert_eq!(bytes.len(), 7 + 19 + 35 + 35);
+        assert_eq!(decode_list(&bytes), Ok(addrs));
+        assert_eq!(decode_list(&[]), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn decode_returns_rest() {
+        let mut bytes = addrs()[0].to_vec();
+        bytes.extend_from_slice(&[TAG_V4, 1]);
+        let (_, rest) = ExtendedCompactAddr::decode(&bytes).unwrap();
+        assert_eq!(rest, &[TAG_V4, 1]);
+    }
+
+    #[test]
+    fn decode_unknown_tag() {
+        assert_eq!(
+            ExtendedCompactAddr::decode(&[0x05, 0, 0, 0, 0, 0, 0]),
+            Err(DecodeError::UnknownTag(0x05))
+        );
+        let e: tagged::DecodeError = decode_list(&[TAG_ONION]).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "unexpected end of address: expected 35 bytes, found 1"
+        );
+    }
+
+    #[test]
+    fn decode_truncated() {
+        assert_eq!(
+            ExtendedCompactAddr::decode(&[]),
+            Err(DecodeError::UnexpectedEnd {
+                expected: 1,
+                actual: 0
+            })
+        );
+
+        let bytes = addrs()[2].to_vec();
+        assert_eq!(
+            ExtendedCompactAddr::decode(&bytes[..20]),
+            Err(DecodeError::UnexpectedEnd {
+                expected: 35,
+                actual: 20
+            })
+        );
+
+        let mut list = encode_list(&addrs());
+        list.pop();
+        assert!(decode_list(&list).is_err());
+    }
+
+    #[test]
+    fn scoped_round_trip() {
+        let addr = ExtendedCompactAddr::ScopedV6("[fe80::1%3]:6881".parse().unwrap());
+        let bytes = addr.to_vec();
+        assert_eq!(bytes.len(), 23);
+        assert_eq!(bytes[0], TAG_SCOPED_V6);
+        assert_eq!(&bytes[19..], &3u32.to_be_bytes());
+        assert_eq!(ExtendedCompactAddr::decode(&bytes), Ok((addr, &[][..])));
+    }
+
+    #[test]
+    fn scoped_socket_addr() {
+        let socket_addr = SocketAddrV6::new("fe80::1".parse().unwrap(), 6881, 9, 3);
+        let scoped = ScopedAddrV6::from(socket_addr);
+        assert_eq!(scoped.scope_id, 3);
+        assert_eq!(
+            SocketAddrV6::from(scoped),
+            SocketAddrV6::new("fe80::1".parse().unwrap(), 6881, 0, 3)
+        );
+        assert_eq!(scoped.to_string(), "[fe80::1%3]:6881");
+    }
+
+    #[test]
+    fn from_str_keeps_scope() {
+        assert_eq!(
+            "[fe80::1%3]:6881".parse::<ExtendedCompactAddr>(),
+            Ok(ExtendedCompactAddr::ScopedV6(ScopedAddrV6 {
+                addr: "[fe80::1]:6881".parse().unwrap(),
+                scope_id: 3,
+            }))
+        );
+        assert_eq!(
+            "[fe80::1%0]:6881".parse::<ExtendedCompactAddr>(),
+            Ok(ExtendedCompactAddr::V6("[fe80::1]:6881".parse().unwrap()))
+        );
+        assert_eq!(
+            "1.2.3.4:6881".parse::<ExtendedCompactAddr>(),
+            Ok(addrs()[0])
+        );
+        assert!("[fe80::1%]:6881".parse::<ExtendedCompactAddr>().is_err());
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Fingerprints for cheap change detection of blobs and lists.
+//!
+//! Fingerprints are stable across releases and platforms. They are computed
+//! with SipHash-2-4 using an all-zero key:
+//!
+//! * The fingerprint of a blob is the hash of the blob bytes.
+//! * The fingerprint of a list is the hash of the concatenation of each
+//!   address prefixed with a tag byte (`0x04` for IPv4 and `0x06` for IPv6).
+//! * The order-insensitive fingerprint of a blob or list is the wrapping sum
+//!   of the hashes of each compact address. Blobs and lists with the same
+//!   addresses have the same order-insensitive fingerprint.
+//!
+//! Fingerprints are not cryptographic and are not suitable for detecting
+//! adversarial changes.
+
+use crate::{
+    blob::{CompactBlobV4, CompactBlobV6, V4_LEN, V6_LEN},
+    list::CompactAddrList,
+    siphash, CompactAddr,
+};
+
+const KEY: [u8; 16] = [0; 16];
+
+/// Returns the fingerprint of a blob of compact IPv4 or IPv6 addresses.
+pub fn blob_fingerprint(blob: &[u8]) -> u64 {
+    siphash::hash(&KEY, blob)
+}
+
+/// Returns the order-insensitive fingerprint of a blob of compact IPv4
+/// addresses.
+///
+/// Trailing bytes which do not form a whole address are ignored.
+pub fn blob_fingerprint_unordered_v4(blob: &[u8]) -> u64 {
+    unordered(blob, V4_LEN)
+}
+
+/// Returns the order-insensitive fingerprint of a blob of compact IPv6
+/// addresses.
+///
+/// Trailing bytes which do not form a whole address are ignored.
+pub fn blob_fingerprint_unordered_v6(blob: &[u8]) -> u64 {
+    unordered(blob, V6_LEN)
+}
+
+fn unordered(blob: &[u8], entry_len: usize) -> u64 {
+    blob.chunks_exact(entry_len).fold(0, |sum: u64, chunk| {
+        sum.wrapping_add(siphash::hash(&KEY, chunk))
+    })
+}
+
+impl CompactBlobV4 {
+    /// Returns a fingerprint which changes if the addresses or their order
+    /// change.
+    ///
+    /// See the [`fingerprint`](crate::fingerprint) module for the definition.
+    pub fn fingerprint(&self) -> u64 {
+        blob_fingerprint(self.as_bytes())
+    }
+
+    /// Returns a fingerprint which changes if the addresses change, regardless
+    /// of their order.
+    ///
+    /// See the [`fingerprint`](crate::fingerprint) module for the definition.
+    pub fn fingerprint_unordered(&self) -> u64 {
+        blob_fingerprint_unordered_v4(self.as_bytes())
+    }
+}
+
+impl CompactBlobV6 {
+    /// Returns a fingerprint which changes if the addresses or their order
+    /// change.
+    ///
+    /// See the [`fingerprint`](crate::fingerprint) module for the definition.
+    pub fn fingerprint(&self) -> u64 {
+        blob_fingerprint(self.as_bytes())
+    }
+
+    /// Returns a fingerprint which changes if the addresses change, regardless
+    /// of their order.
+    ///
+    /// See the [`fingerprint`](crate::fingerprint) module for the definition.
+    pub fn fingerprint_unordered(&self) -> u64 {
+        blob_fingerprint_unordered_v6(self.as_bytes())
+    }
+}
+
+impl CompactAddrList {
+    /// Returns a fingerprint which changes if the addresses or their order
+    /// change.
+    ///
+    /// See the [`fingerprint`](crate::fingerprint) module for the definition.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = siphash::Hasher::new(&KEY);
+        for addr in self.iter() {
+            let tag = match addr {
+                CompactAddr::V4(_) => 0x04,
+                CompactAddr::V6(_) => 0x06,
+            };
+            hasher.write(&[tag]);
+            hasher.write(addr.as_bytes());
+        }
+        hasher.finish()
+    }
+
+    /// Returns a fingerprint which changes if the addresses change, regardless
+    /// of their order.
+    ///
+    /// See the [`fingerprint`](crate::fingerprint) module for the definition.
+    pub fn fingerprint_unordered(&self) -> u64 {
+        self.iter().fold(0, |sum: u64, addr| {
+            sum.wrapping_add(siphash::hash(&KEY, addr.as_bytes()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompactAddrV4, CompactAddrV6};
+
+    fn blob_v4() -> CompactBlobV4 {
+        CompactBlobV4::from_bytes(vec![1, 2, 3, 4, 0, 80, 5, 6, 7, 8, 0, 81]).unwrap()
+    }
+
+    fn reversed_v4() -> CompactBlobV4 {
+        CompactBlobV4::from_bytes(vec![5, 6, 7, 8, 0, 81, 1, 2, 3, 4, 0, 80]).unwrap()
+    }
+
+    #[test]
+    fn blob_fingerprint_is_siphash() {
+        let blob = blob_v4();
+        assert_eq!(blob.fingerprint(), siphash::hash(&[0; 16], blob.as_bytes()));
+        assert_eq!(blob_fingerprint(&[]), siphash::hash(&[0; 16], &[]));
+    }
+
+    #[test]
+    fn ordered_fingerprint() {
+        assert_eq!(blob_v4().fingerprint(), blob_v4().fingerprint());
+        assert_ne!(blob_v4().fingerprint(), reversed_v4().fingerprint());
+    }
+
+    #[test]
+    fn unordered_fingerprint() {
+        assert_eq!(
+            blob_v4().fingerprint_unordered(),
+            reversed_v4().fingerprint_unordered()
+        );
+        assert_ne!(
+            blob_v4().fingerprint_unordered(),
+            CompactBlobV4::new().fingerprint_unordered()
+        );
+        assert_eq!(CompactBlobV6::new().fingerprint_unordered(), 0);
+
+        let mut ragged = blob_v4().into_bytes();
+        ragged.push(9);
+        assert_eq!(
+            blob_fingerprint_unordered_v4(&ragged),
+            blob_v4().fingerprint_unordered()
+        );
+    }
+
+    #[test]
+    fn v6_fingerprint() {
+        let mut blob = CompactBlobV6::new();
+        blob.push(CompactAddrV6::from([1; 18]));
+        blob.push(CompactAddrV6::from([2; 18]));
+        let mut reversed = CompactBlobV6::new();
+        reversed.push(CompactAddrV6::from([2; 18]));
+        reversed.push(CompactAddrV6::from([1; 18]));
+        assert_ne!(blob.fingerprint(), reversed.fingerprint());
+        assert_eq!(
+            blob.fingerprint_unordered(),
+            reversed.fingerprint_unordered()
+        );
+    }
+
+    #[test]
+    fn list_fingerprint() {
+        let v4 = CompactAddr::from(CompactAddrV4::from([1, 2, 3, 4, 0, 80]));
+        let v6 = CompactAddr::from(CompactAddrV6::from([1; 18]));
+        let list: CompactAddrList = vec![v4, v6].into();
+        let reversed: CompactAddrList = vec![v6, v4].into();
+        assert_ne!(list.fingerprint(), reversed.fingerprint());
+        assert_eq!(
+            list.fingerprint_unordered(),
+            reversed.fingerprint_unordered()
+        );
+
+        let mut tagged = vec![0x04];
+        tagged.extend_from_slice(v4.as_bytes());
+        tagged.push(0x06);
+        tagged.extend_from_slice(v6.as_bytes());
+        assert_eq!(list.fingerprint(), siphash::hash(&[0; 16], &tagged));
+
+        let v4_list: CompactAddrList = vec![v4].into();
+        let v4_blob = CompactBlobV4::from_bytes(v4.as_bytes().to_vec()).unwrap();
+        assert_ne!(v4_list.fingerprint(), v4_blob.fingerprint());
+        assert_eq!(
+            v4_list.fingerprint_unordered(),
+            v4_blob.fingerprint_unordered()
+        );
+    }
+}
@@ -0,0 +1,409 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Count-prefixed framing of compact addresses.
+//!
+//! A frame is an entry count followed by the entries. The count is either a
+//! big-endian `u16` or an unsigned LEB128 varint.
+//!
+//! IPv4 and IPv6 frames contain concatenated compact addresses. Mixed frames
+//! contain [tagged] compact addresses.
+
+use crate::{
+    blob::{V4_LEN, V6_LEN},
+    tagged, CompactAddr,
+};
+use std::{convert::TryFrom, error, fmt};
+
+/// The encoding of the entry count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CountPrefix {
+    /// A big-endian `u16`.
+    U16,
+    /// An unsigned LEB128 varint.
+    Varint,
+}
+
+impl CountPrefix {
+    fn encode(self, count: usize, buf: &mut Vec<u8>) -> Result<(), FrameError> {
+        match self {
+            CountPrefix::U16 => {
+                let count = u16::try_from(count).map_err(|_| FrameError::TooManyEntries {
+                    count,
+                    max: usize::from(u16::MAX),
+                })?;
+                buf.extend_from_slice(&count.to_be_bytes());
+            }
+            CountPrefix::Varint => {
+                let mut count = count as u64;
+                loop {
+                    let b = (count & 0x7f) as u8;
+                    count >>= 7;
+                    if count == 0 {
+                        buf.push(b);
+                        break;
+                    }
+                    buf.push(b | 0x80);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn decode(self, input: &[u8]) -> Result<(usize, &[u8]), FrameError> {
+        match self {
+            CountPrefix::U16 => {
+                if input.len() < 2 {
+                    return Err(FrameError::Incomplete {
+                        needed: 2 - input.len(),
+                    });
+                }
+                let count = u16::from_be_bytes([input[0], input[1]]);
+                Ok((usize::from(count), &input[2..]))
+            }
+            CountPrefix::Varint => {
+                let mut count: u64 = 0;
+                for (idx, b) in input.iter().enumerate() {
+                    let shift = 7 * idx as u32;
+                    let value = u64::from(b & 0x7f);
+                    if shift >= 64 || (shift > 0 && value >> (64 - shift) != 0) {
+                        return Err(FrameError::InvalidVarint);
+                    }
+                    count |= value << shift;
+                    if b & 0x80 == 0 {
+                        let count =
+                            usize::try_from(count).map_err(|_| FrameError::InvalidVarint)?;
+                        return Ok((count, &input[idx + 1..]));
+                    }
+                }
+                Err(FrameError::Incomplete { needed: 1 })
+            }
+        }
+    }
+}
+
+/// Appends a frame of the compact IPv4 addresses in a blob.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length or the count cannot be encoded.
+pub fn encode_v4(blob: &[u8], prefix: CountPrefix, buf: &mut Vec<u8>) -> Result<(), FrameError> {
+    encode_blob(blob, V4_LEN, prefix, buf)
+}
+
+/// Appends a frame of the compact IPv6 addresses in a blob.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length or the count cannot be encoded.
+pub fn encode_v6(blob: &[u8], prefix: CountPrefix, buf: &mut Vec<u8>) -> Result<(), FrameError> {
+    encode_blob(blob, V6_LEN, prefix, buf)
+}
+
+/// Decodes a frame of compact IPv4 addresses from the start of the input.
+///
+/// Returns the blob of the frame's addresses and the remaining input.
+///
+/// # Errors
+///
+/// Returns an error if the input does not contain a whole frame or the count
+/// is invalid.
+pub fn decode_v4(input: &[u8], prefix: CountPrefix) -> Result<(&[u8], &[u8]), FrameError> {
+    decode_blob(input, V4_LEN, prefix)
+}
+
+/// Decodes a frame of compact IPv6 addresses from the start of the input.
+///
+/// Returns the blob of the frame's addresses and the remaining input.
+///
+/// # Errors
+///
+/// Returns an error if the input does not contain a whole frame or the count
+/// is invalid.
+pub fn decode_v6(input: &[u8], prefix: CountPrefix) -> Result<(&[u8], &[u8]), FrameError> {
+    decode_blob(input, V6_LEN, prefix)
+}
+
+/// Appends a frame of tagged IPv4 and IPv6 compact addresses.
+///
+/// # Errors
+///
+/// Returns an error if the count cannot be encoded.
+pub fn encode_mixed(
+    addrs: &[CompactAddr],
+    prefix: CountPrefix,
+    buf: &mut Vec<u8>,
+) -> Result<(), FrameError> {
+    prefix.encode(addrs.len(), buf)?;
+    for addr in addrs {
+        addr.encode_tagged(buf);
+    }
+    Ok(())
+}
+
+/// Decodes a frame of tagged IPv4 and IPv6 compact addresses from the start
+/// of the input.
+///
+/// Returns the addresses and the remaining input.
+///
+/// # Errors
+///
+/// Returns an error if the input does not contain a whole frame, the count
+/// is invalid, or an entry has an unknown tag.
+pub fn decode_mixed(
+    input: &[u8],
+    prefix: CountPrefix,
+) -> Result<(Vec<CompactAddr>, &[u8]), FrameError> {
+    let (count, mut rest) = prefix.decode(input)?;
+    // Each entry is at least as long as a tagged IPv4 address.
+    if rest.len() / (V4_LEN + 1) < count {
+        let needed = count
+            .checked_mul(V4_LEN + 1)
+            .map_or(usize::MAX, |len| len - rest.len());
+        return Err(FrameError::Incomplete { needed });
+    }
+
+    let mut addrs = Vec::with_capacity(count);
+    for _ in 0..count {
+        let (addr, next) = CompactAddr::decode_tagged(rest).map_err(|e| match e {
+            tagged::DecodeError::UnknownTag(tag) => FrameError::UnknownTag(tag),
+            tagged::DecodeError::UnexpectedEnd { expected, actual } => FrameError::Incomplete {
+                needed: expected - actual,
+            },
+            tagged::DecodeError::TrailingBytes(_) => unreachable!(),
+        })?;
+        addrs.push(addr);
+        rest = next;
+    }
+    Ok((addrs, rest))
+}
+
+fn encode_blob(
+    blob: &[u8],
+    entry_len: usize,
+    prefix: CountPrefix,
+    buf: &mut Vec<u8>,
+) -> Result<(), FrameError> {
+    if !blob.len().is_multiple_of(entry_len) {
+        return Err(FrameError::InvalidLength {
+            len: blob.len(),
+            entry_len,
+        });
+    }
+    prefix.encode(blob.len() / entry_len, buf)?;
+    buf.extend_from_slice(blob);
+    Ok(())
+}
+
+fn decode_blob(
+    input: &[u8],
+    entry_len: usize,
+    prefix: CountPrefix,
+) -> Result<(&[u8], &[u8]), FrameError> {
+    let (count, rest) = prefix.decode(input)?;
+    let len = count
+        .checked_mul(entry_len)
+        .ok_or(FrameError::Incomplete { needed: usize::MAX })?;
+    if rest.len() < len {
+        return Err(FrameError::Incomplete {
+            needed: len - rest.len(),
+        });
+    }
+    Ok(rest.split_at(len))
+}
+
+/// An error when encoding or decoding a frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    /// The blob length is not a multiple of the compact address length.
+    InvalidLength {
+        /// The blob length.
+        len: usize,
+        /// The compact address length.
+        entry_len: usize,
+    },
+    /// The number of entries cannot be encoded by the count prefix.
+    TooManyEntries {
+        /// The number of entries.
+        count: usize,
+        /// The maximum number of entries.
+        max: usize,
+    },
+    /// The input ended before the frame was complete.
+    Incomplete {
+        /// The minimum number of additional bytes required.
+        needed: usize,
+    },
+    /// The varint count is invalid or overflows.
+    InvalidVarint,
+    /// An entry in a mixed frame has an unknown tag.
+    UnknownTag(u8),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::InvalidLength { len, entry_len } => {
+                write!(f, "blob length {} is not a multiple of {}", len, entry_len)
+            }
+            FrameError::TooManyEntries { count, max } => {
+                write!(f, "{} entries exceeds maximum count {}", count, max)
+            }
+            FrameError::Incomplete { needed } => {
+                write!(f, "incomplete frame: needed {} more bytes", needed)
+            }
+            FrameError::InvalidVarint => write!(f, "invalid varint count"),
+            FrameError::UnknownTag(tag) => write!(f, "unknown address tag: {:#04x}", tag),
+        }
+    }
+}
+
+impl error::Error for FrameError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(count: usize) -> Vec<u8> {
+        let mut buf = Vec::new();
+        CountPrefix::Varint.encode(count, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn varint_encoding() {
+        assert_eq!(varint(0), [0x00]);
+        assert_eq!(varint(127), [0x7f]);
+        assert_eq!(varint(128), [0x80, 0x01]);
+        assert_eq!(varint(300), [0xac, 0x02]);
+        assert_eq!(varint(16_384), [0x80, 0x80, 0x01]);
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        for count in [0, 1, 127, 128, 255, 16_383, 16_384, 1 << 31, usize::MAX] {
+            let mut buf = varint(count);
+            buf.push(0xee);
+            assert_eq!(CountPrefix::Varint.decode(&buf), Ok((count, &[0xee][..])));
+        }
+    }
+
+    #[test]
+    fn varint_malformed() {
+        let prefix = CountPrefix::Varint;
+        assert_eq!(
+            prefix.decode(&[]),
+            Err(FrameError::Incomplete { needed: 1 })
+        );
+        assert_eq!(
+            prefix.decode(&[0x80, 0x80]),
+            Err(FrameError::Incomplete { needed: 1 })
+        );
+        let mut too_long = vec![0xff; 10];
+        too_long.push(0x01);
+        assert_eq!(prefix.decode(&too_long), Err(FrameError::InvalidVarint));
+        let mut overflow = vec![0xff; 9];
+        overflow.push(0x02);
+        assert_eq!(prefix.decode(&overflow), Err(FrameError::InvalidVarint));
+    }
+
+    #[test]
+    fn u16_prefix() {
+        let mut buf = Vec::new();
+        CountPrefix::U16.encode(0x0102, &mut buf).unwrap();
+        assert_eq!(buf, [0x01, 0x02]);
+        assert_eq!(CountPrefix::U16.decode(&buf), Ok((0x0102, &[][..])));
+        assert_eq!(
+            CountPrefix::U16.decode(&[1]),
+            Err(FrameError::Incomplete { needed: 1 })
+        );
+        assert_eq!(
+            CountPrefix::U16.encode(70_000, &mut buf),
+            Err(FrameError::TooManyEntries {
+                count: 70_000,
+                max: 65_535
+            })
+        );
+    }
+
+    #[test]
+    fn blob_frame_round_trip() {
+        let v4 = [1, 2, 3, 4, 0, 80, 5, 6, 7, 8, 0, 81];
+        let v6 = [9; 18];
+        for prefix in [CountPrefix::U16, CountPrefix::Varint] {
+            let mut buf = Vec::new();
+            encode_v4(&v4, prefix, &mut buf).unwrap();
+            encode_v6(&v6, prefix, &mut buf).unwrap();
+            let (decoded, rest) = decode_v4(&buf, prefix).unwrap();
+            assert_eq!(decoded, v4);
+            let (decoded, rest) = decode_v6(rest, prefix).unwrap();
+            assert_eq!(decoded, v6);
+            assert!(rest.is_empty());
+        }
+    }
+
+    #[test]
+    fn blob_frame_malformed() {
+        let mut buf = Vec::new();
+        assert_eq!(
+            encode_v4(&[0; 7], CountPrefix::Varint, &mut buf),
+            Err(FrameError::InvalidLength {
+                len: 7,
+                entry_len: 6
+            })
+        );
+        assert!(buf.is_empty());
+
+        assert_eq!(
+            decode_v4(&[2, 1, 2, 3, 4, 0, 80], CountPrefix::Varint),
+            Err(FrameError::Incomplete { needed: 6 })
+        );
+        let mut huge = varint(usize::MAX);
+        huge.push(0);
+        assert_eq!(
+            decode_v6(&huge, CountPrefix::Varint),
+            Err(FrameError::Incomplete { needed: usize::MAX })
+        );
+    }
+
+    #[test]
+    fn mixed_frame_round_trip() {
+        let addrs = vec![
+            "1.2.3.4:80".parse::<CompactAddr>().unwrap(),
+            "[2001:db8::1]:6881".parse::<CompactAddr>().unwrap(),
+        ];
+        let mut buf = Vec::new();
+        encode_mixed(&addrs, CountPrefix::Varint, &mut buf).unwrap();
+        assert_eq!(buf.len(), 1 + 7 + 19);
+        buf.push(0xee);
+        assert_eq!(
+            decode_mixed(&buf, CountPrefix::Varint),
+            Ok((addrs, &[0xee][..]))
+        );
+    }
+
+    #[test]
+    fn mixed_frame_malformed() {
+        assert_eq!(
+            decode_mixed(&[1, 0x05, 0, 0, 0, 0, 0, 0], CountPrefix::Varint),
+            Err(FrameError::UnknownTag(0x05))
+        );
+        assert_eq!(
+            decode_mixed(&[2, tagged::TAG_V4, 0, 0, 0, 0, 0, 0], CountPrefix::Varint),
+            Err(FrameError::Incomplete { needed: 7 })
+        );
+        let mut v6 = vec![1, tagged::TAG_V6];
+        v6.extend_from_slice(&[0; 10]);
+        assert_eq!(
+            decode_mixed(&v6, CountPrefix::Varint),
+            Err(FrameError::Incomplete { needed: 8 })
+        );
+    }
+}
@@ -0,0 +1,585 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Country lookups of addresses against a MaxMind DB with [`maxminddb`].
+//!
+//! The database is provided by the caller. Any database with a
+//! `country.iso_code` field (such as GeoLite2-Country or GeoIP2-City) can be
+//! used.
+
+use crate::{
+    blob::{self, DecodeError, IterV4, IterV6},
+    CompactAddr,
+};
+use ::maxminddb::{MaxMindDbError, PathElement, Reader};
+use std::{
+    collections::{btree_map, BTreeMap},
+    error, fmt,
+    net::{IpAddr, SocketAddr},
+    str,
+};
+
+const ISO_CODE_PATH: &[PathElement<'static>] =
+    &[PathElement::Key("country"), PathElement::Key("iso_code")];
+
+/// An ISO 3166-1 alpha-2 country code.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CountryCode([u8; 2]);
+
+impl CountryCode {
+    /// Returns the country code if it is two ASCII letters.
+    ///
+    /// The code is converted to uppercase.
+    pub fn new(code: &str) -> Option<Self> {
+        match *code.as_bytes() {
+            [a, b] if a.is_ascii_alphabetic() && b.is_ascii_alphabetic() => {
+                Some(Self([a.to_ascii_uppercase(), b.to_ascii_uppercase()]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the country code as a string.
+    pub fn as_str(&self) -> &str {
+        // SAFETY: The bytes are always ASCII letters.
+        unsafe { str::from_utf8_unchecked(&self.0) }
+    }
+}
+
+impl AsRef<str> for CountryCode {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for CountryCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CountryCode").field(&self.as_str()).finish()
+    }
+}
+
+impl fmt::Display for CountryCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Looks up the countries of addresses in a MaxMind DB.
+#[derive(Debug)]
+pub struct CountryLookup<'a, S: AsRef<[u8]>> {
+    reader: &'a Reader<S>,
+}
+
+impl<'a, S: AsRef<[u8]>> Clone for CountryLookup<'a, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, S: AsRef<[u8]>> Copy for CountryLookup<'a, S> {}
+
+impl<'a, S: AsRef<[u8]>> CountryLookup<'a, S> {
+    /// Instantiates a lookup with the database reader.
+    pub fn new(reader: &'a Reader<S>) -> Self {
+        Self { reader }
+    }
+
+    /// Returns the country of the IP address.
+    ///
+    /// IPv4-mapped IPv6 addresses are looked up as IPv4 addresses. Returns
+    /// `None` if the address is not in the database or has no valid country
+    /// code.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database is invalid or an IPv6 address is
+    /// looked up in an IPv4-only database.
+    pub fn country(&self, ip: IpAddr) -> Result<Option<CountryCode>, MaxMindDbError> {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            IpAddr::V4(_) => ip,
+        };
+        let code = self.reader.lookup(ip)?.decode_path::<&str>(ISO_CODE_PATH)?;
+        Ok(code.and_then(CountryCode::new))
+    }
+
+    /// Returns the addresses paired with their countries.
+    pub fn annotate<I>(&self, addrs: I) -> Annotate<'a, S, I::IntoIter>
+    where
+        I: IntoIterator,
+        I::Item: Into<SocketAddr>,
+    {
+        Annotate {
+            lookup: *self,
+            addrs: addrs.into_iter(),
+        }
+    }
+
+    /// Returns the addresses in a blob of compact IPv4 addresses paired with
+    /// their countries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blob length is not a multiple of the compact
+    /// address length.
+    pub fn annotate_v4<'b>(
+        &self,
+        blob: &'b [u8],
+    ) -> Result<Annotate<'a, S, IterV4<'b>>, DecodeError> {
+        check_len(blob, blob::V4_LEN)?;
+        Ok(self.annotate(blob::iter_v4(blob)))
+    }
+
+    /// Returns the addresses in a blob of compact IPv6 addresses paired with
+    /// their countries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blob length is not a multiple of the compact
+    /// address length.
+    pub fn annotate_v6<'b>(
+        &self,
+        blob: &'b [u8],
+    ) -> Result<Annotate<'a, S, IterV6<'b>>, DecodeError> {
+        check_len(blob, blob::V6_LEN)?;
+        Ok(self.annotate(blob::iter_v6(blob)))
+    }
+
+    /// Returns the number of addresses per country.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a lookup fails.
+    pub fn histogram<I>(&self, addrs: I) -> Result<CountryHistogram, MaxMindDbError>
+    where
+        I: IntoIterator,
+        I::Item: Into<SocketAddr>,
+    {
+        let mut histogram = CountryHistogram::default();
+        for result in self.annotate(addrs) {
+            let (_, code) = result?;
+            histogram.add(code);
+        }
+        Ok(histogram)
+    }
+
+    /// Returns the number of addresses per country in a blob of compact IPv4
+    /// addresses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blob length is not a multiple of the compact
+    /// address length or a lookup fails.
+    pub fn histogram_v4(&self, blob: &[u8]) -> Result<CountryHistogram, GeoIpError> {
+        check_len(blob, blob::V4_LEN)?;
+        Ok(self.histogram(blob::iter_v4(blob))?)
+    }
+
+    /// Returns the number of addresses per country in a blob of compact IPv6
+    /// addresses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blob length is not a multiple of the compact
+    /// address length or a lookup fails.
+    pub fn histogram_v6(&self, blob: &[u8]) -> Result<CountryHistogram, GeoIpError> {
+        check_len(blob, blob::V6_LEN)?;
+        Ok(self.histogram(blob::iter_v6(blob))?)
+    }
+}
+
+/// An iterator of addresses paired with their countries.
+///
+/// See [`CountryLookup::annotate`].
+#[derive(Debug)]
+pub struct Annotate<'a, S: AsRef<[u8]>, I> {
+    lookup: CountryLookup<'a, S>,
+    addrs: I,
+}
+
+impl<'a, S, I> Iterator for Annotate<'a, S, I>
+where
+    S: AsRef<[u8]>,
+    I: Iterator,
+    I::Item: Into<SocketAddr>,
+{
+    type Item = Result<(SocketAddr, Option<CountryCode>), MaxMindDbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let addr = self.addrs.next()?.into();
+        Some(self.lookup.country(addr.ip()).map(|code| (addr, code)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.addrs.size_hint()
+    }
+}
+
+impl<'a, S, I> ExactSizeIterator for Annotate<'a, S, I>
+where
+    S: AsRef<[u8]>,
+    I: ExactSizeIterator,
+    I::Item: Into<SocketAddr>,
+{
+}
+
+/// The number of addresses per country.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CountryHistogram {
+    counts: BTreeMap<CountryCode, usize>,
+    unknown: usize,
+}
+
+impl CountryHistogram {
+    /// Instantiates an empty histogram.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts an address with the country.
+    ///
+    /// An address without a country is counted as unknown.
+    pub fn add(&mut self, code: Option<CountryCode>) {
+        match code {
+            Some(code) => *self.counts.entry(code).or_insert(0) += 1,
+            None => self.unknown += 1,
+        }
+    }
+
+    /// Returns the number of addresses in the country.
+    pub fn get(&self, code: CountryCode) -> usize {
+        self.counts.get(&code).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of addresses without a known country.
+    pub fn unknown(&self) -> usize {
+        self.unknown
+    }
+
+    /// Returns the total number of addresses.
+    pub fn total(&self) -> usize {
+        self.counts.values().sum::<usize>() + self.unknown
+    }
+
+    /// Returns the number of distinct countries.
+    pub fn country_count(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Returns the countries and their counts ordered by country code.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.counts.iter(),
+        }
+    }
+
+    /// Returns the countries and their counts ordered by most addresses
+    /// first.
+    ///
+    /// Countries with the same count are ordered by country code.
+    pub fn ranked(&self) -> Vec<(CountryCode, usize)> {
+        let mut ranked = self.iter().collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+}
+
+impl<'a> IntoIterator for &'a CountryHistogram {
+    type Item = (CountryCode, usize);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Extend<Option<CountryCode>> for CountryHistogram {
+    fn extend<I: IntoIterator<Item = Option<CountryCode>>>(&mut self, iter: I) {
+        for code in iter {
+            self.add(code);
+        }
+    }
+}
+
+/// An iterator over the countries in a [`CountryHistogram`].
+#[derive(Clone, Debug)]
+pub struct Iter<'a> {
+    inner: btree_map::Iter<'a, CountryCode, usize>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (CountryCode, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(code, count)| (*code, *count))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+impl CompactAddr {
+    /// Returns the country of the address.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lookup fails.
+    pub fn country<S: AsRef<[u8]>>(
+        &self,
+        lookup: &CountryLookup<'_, S>,
+    ) -> Result<Option<CountryCode>, MaxMindDbError> {
+        lookup.country(self.ip())
+    }
+}
+
+/// An error when looking up the countries of a blob of addresses.
+#[derive(Debug)]
+pub enum GeoIpError {
+    /// The blob is invalid.
+    Decode(DecodeError),
+    /// The database lookup failed.
+    Lookup(MaxMindDbError),
+}
+
+impl From<DecodeError> for GeoIpError {
+    fn from(e: DecodeError) -> Self {
+        GeoIpError::Decode(e)
+    }
+}
+
+impl From<MaxMindDbError> for GeoIpError {
+    fn from(e: MaxMindDbError) -> Self {
+        GeoIpError::Lookup(e)
+    }
+}
+
+impl fmt::Display for GeoIpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeoIpError::Decode(e) => write!(f, "{}", e),
+            GeoIpError::Lookup(e) => write!(f, "country lookup failed: {}", e),
+        }
+    }
+}
+
+impl error::Error for GeoIpError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            GeoIpError::Decode(e) => Some(e),
+            GeoIpError::Lookup(e) => Some(e),
+        }
+    }
+}
+
+fn check_len(blob: &[u8], entry_len: usize) -> Result<(), DecodeError> {
+    if blob.len().is_multiple_of(entry_len) {
+        Ok(())
+    } else {
+        Err(DecodeError::invalid_length(blob.len(), entry_len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4};
+
+    fn push_str(buf: &mut Vec<u8>, s: &str) {
+        buf.push(0x40 | s.len() as u8);
+        buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn push_country(buf: &mut Vec<u8>, code: &str) {
+        buf.push(0xe1);
+        push_str(buf, "country");
+        buf.push(0xe1);
+        push_str(buf, "iso_code");
+        push_str(buf, code);
+    }
+
+    /// Builds an IPv4 database with `0.0.0.0/2` in US, `64.0.0.0/2` in DE, and
+    /// no data for `128.0.0.0/1`.
+    fn database() -> Reader<Vec<u8>> {
+        const NODE_COUNT: u32 = 2;
+        let mut data = Vec::new();
+        push_country(&mut data, "US");
+        let de_offset = data.len() as u32;
+        push_country(&mut data, "DE");
+
+        let pointer = |offset: u32| NODE_COUNT + 16 + offset;
+        let records = [1, NODE_COUNT, pointer(0), pointer(de_offset)];
+        let mut buf = Vec::new();
+        for record in records {
+            buf.extend_from_slice(&record.to_be_bytes()[1..]);
+        }
+        buf.extend_from_slice(&[0; 16]);
+        buf.extend_from_slice(&data);
+
+        buf.extend_from_slice(b"\xab\xcd\xefMaxMind.com");
+        buf.push(0xe9);
+        push_str(&mut buf, "binary_format_major_version");
+        buf.extend_from_slice(&[0xa1, 2]);
+        push_str(&mut buf, "binary_format_minor_version");
+        buf.push(0xa0);
+        push_str(&mut buf, "build_epoch");
+        buf.extend_from_slice(&[0x00, 0x02]);
+        push_str(&mut buf, "database_type");
+        push_str(&mut buf, "Test");
+        push_str(&mut buf, "description");
+        buf.push(0xe0);
+        push_str(&mut buf, "ip_version");
+        buf.extend_from_slice(&[0xa1, 4]);
+        push_str(&mut buf, "languages");
+        buf.extend_from_slice(&[0x00, 0x04]);
+        push_str(&mut buf, "node_count");
+        buf.extend_from_slice(&[0xc1, NODE_COUNT as u8]);
+        push_str(&mut buf, "record_size");
+        buf.extend_from_slice(&[0xa1, 24]);
+
+        Reader::from_source(buf).unwrap()
+    }
+
+    fn code(s: &str) -> CountryCode {
+        CountryCode::new(s).unwrap()
+    }
+
+    fn v4(a: u8, port: u16) -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::new(a, 0, 0, 1), port)
+    }
+
+    #[test]
+    fn country_code_new() {
+        assert_eq!(code("us").as_str(), "US");
+        assert_eq!(code("De").to_string(), "DE");
+        assert_eq!(format!("{:?}", code("fr")), "CountryCode(\"FR\")");
+        assert_eq!(CountryCode::new("USA"), None);
+        assert_eq!(CountryCode::new("U"), None);
+        assert_eq!(CountryCode::new("U1"), None);
+        assert_eq!(CountryCode::new("ü"), None);
+    }
+
+    #[test]
+    fn country() {
+        let reader = database();
+        let lookup = CountryLookup::new(&reader);
+        let ipv4 = |a| IpAddr::V4(Ipv4Addr::new(a, 0, 0, 1));
+        assert_eq!(lookup.country(ipv4(10)).unwrap(), Some(code("US")));
+        assert_eq!(lookup.country(ipv4(80)).unwrap(), Some(code("DE")));
+        assert_eq!(lookup.country(ipv4(200)).unwrap(), None);
+    }
+
+    #[test]
+    fn country_ipv4_mapped() {
+        let reader = database();
+        let lookup = CountryLookup::new(&reader);
+        let mapped = Ipv4Addr::new(80, 0, 0, 1).to_ipv6_mapped();
+        assert_eq!(
+            lookup.country(IpAddr::V6(mapped)).unwrap(),
+            Some(code("DE"))
+        );
+        assert!(lookup.country(IpAddr::V6(Ipv6Addr::LOCALHOST)).is_err());
+    }
+
+    #[test]
+    fn compact_addr_country() {
+        let reader = database();
+        let lookup = CountryLookup::new(&reader);
+        let addr = CompactAddr::from(v4(10, 6881));
+        assert_eq!(addr.country(&lookup).unwrap(), Some(code("US")));
+    }
+
+    #[test]
+    fn annotate() {
+        let reader = database();
+        let lookup = CountryLookup::new(&reader);
+        let annotated = lookup
+            .annotate(vec![v4(10, 1), v4(200, 2)])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            annotated,
+            [
+                (SocketAddr::V4(v4(10, 1)), Some(code("US"))),
+                (SocketAddr::V4(v4(200, 2)), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn annotate_v4_blob() {
+        let reader = database();
+        let lookup = CountryLookup::new(&reader);
+        let blob = [80, 0, 0, 1, 0, 1];
+        let annotated = lookup.annotate_v4(&blob).unwrap();
+        assert_eq!(annotated.len(), 1);
+        assert_eq!(
+            annotated.map(Result::unwrap).collect::<Vec<_>>(),
+            [(SocketAddr::V4(v4(80, 1)), Some(code("DE")))]
+        );
+        assert!(lookup.annotate_v4(&blob[..5]).is_err());
+        assert!(lookup.annotate_v6(&blob).is_err());
+    }
+
+    #[test]
+    fn histogram() {
+        let reader = database();
+        let lookup = CountryLookup::new(&reader);
+        let histogram = lookup
+            .histogram(vec![v4(10, 1), v4(20, 2), v4(80, 3), v4(200, 4)])
+            .unwrap();
+        assert_eq!(histogram.get(code("US")), 2);
+        assert_eq!(histogram.get(code("DE")), 1);
+        assert_eq!(histogram.get(code("FR")), 0);
+        assert_eq!(histogram.unknown(), 1);
+        assert_eq!(histogram.total(), 4);
+        assert_eq!(histogram.country_count(), 2);
+    }
+
+    #[test]
+    fn histogram_blob() {
+        let reader = database();
+        let lookup = CountryLookup::new(&reader);
+        let histogram = lookup
+            .histogram_v4(&[10, 0, 0, 1, 0, 1, 11, 0, 0, 1, 0, 2])
+            .unwrap();
+        assert_eq!(histogram.get(code("US")), 2);
+        assert!(matches!(
+            lookup.histogram_v4(&[10, 0, 0]),
+            Err(GeoIpError::Decode(_))
+        ));
+        assert!(matches!(
+            lookup.histogram_v6(&[0; 18]),
+            Err(GeoIpError::Lookup(_))
+        ));
+    }
+
+    #[test]
+    fn histogram_ranked() {
+        let mut histogram = CountryHistogram::new();
+        histogram.extend(vec![
+            Some(code("DE")),
+            Some(code("US")),
+            Some(code("FR")),
+            Some(code("US")),
+            None,
+        ]);
+        assert_eq!(
+            histogram.iter().collect::<Vec<_>>(),
+            [(code("DE"), 1), (code("FR"), 1), (code("US"), 2)]
+        );
+        assert_eq!(
+            histogram.ranked(),
+            [(code("US"), 2), (code("DE"), 1), (code("FR"), 1)]
+        );
+        assert_eq!((&histogram).into_iter().len(), 3);
+        assert_eq!(histogram.unknown(), 1);
+    }
+}
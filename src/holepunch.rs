@@ -0,0 +1,286 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! BEP 55 `ut_holepunch` extension messages.
+//!
+//! A message is a message type byte, an address type byte (`0x00` for IPv4 and
+//! `0x01` for IPv6), the compact address, and a big-endian `u32` error code.
+//! The error code is zero for rendezvous and connect messages.
+
+use crate::{
+    blob::{V4_LEN, V6_LEN},
+    CompactAddr, CompactAddrV4, CompactAddrV6,
+};
+use std::{convert::TryFrom, error, fmt};
+
+const MSG_RENDEZVOUS: u8 = 0x00;
+const MSG_CONNECT: u8 = 0x01;
+const MSG_ERROR: u8 = 0x02;
+
+const ADDR_V4: u8 = 0x00;
+const ADDR_V6: u8 = 0x01;
+
+/// The error code of a holepunch error message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// The target endpoint is not connected to the relaying peer.
+    NoSuchPeer,
+    /// The relaying peer is no longer connected to the target peer.
+    NotConnected,
+    /// The target peer does not support the holepunch extension.
+    NoSupport,
+    /// The target endpoint is the relaying peer.
+    NoSelf,
+    /// An error code which is not defined by BEP 55.
+    Other(u32),
+}
+
+impl From<u32> for ErrorCode {
+    fn from(code: u32) -> Self {
+        match code {
+            1 => ErrorCode::NoSuchPeer,
+            2 => ErrorCode::NotConnected,
+            3 => ErrorCode::NoSupport,
+            4 => ErrorCode::NoSelf,
+            code => ErrorCode::Other(code),
+        }
+    }
+}
+
+impl From<ErrorCode> for u32 {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::NoSuchPeer => 1,
+            ErrorCode::NotConnected => 2,
+            ErrorCode::NoSupport => 3,
+            ErrorCode::NoSelf => 4,
+            ErrorCode::Other(code) => code,
+        }
+    }
+}
+
+/// A holepunch extension message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HolepunchMessage {
+    /// Asks the relaying peer to connect the sender with the target endpoint.
+    Rendezvous(CompactAddr),
+    /// Tells the receiver to connect to the endpoint.
+    Connect(CompactAddr),
+    /// Tells the sender of a rendezvous message that it failed.
+    Error(CompactAddr, ErrorCode),
+}
+
+impl HolepunchMessage {
+    /// Returns the endpoint address.
+    pub fn addr(&self) -> CompactAddr {
+        match self {
+            HolepunchMessage::Rendezvous(addr)
+            | HolepunchMessage::Connect(addr)
+            | HolepunchMessage::Error(addr, _) => *addr,
+        }
+    }
+
+    /// Returns the length of the encoding.
+    pub fn encoded_len(&self) -> usize {
+        2 + self.addr().as_bytes().len() + 4
+    }
+
+    /// Appends the encoding to the buffer.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let (msg_type, addr, code) = match self {
+            HolepunchMessage::Rendezvous(addr) => (MSG_RENDEZVOUS, addr, 0),
+            HolepunchMessage::Connect(addr) => (MSG_CONNECT, addr, 0),
+            HolepunchMessage::Error(addr, code) => (MSG_ERROR, addr, u32::from(*code)),
+        };
+        buf.push(msg_type);
+        buf.push(match addr {
+            CompactAddr::V4(_) => ADDR_V4,
+            CompactAddr::V6(_) => ADDR_V6,
+        });
+        buf.extend_from_slice(addr.as_bytes());
+        buf.extend_from_slice(&code.to_be_bytes());
+    }
+
+    /// Returns the encoding.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.encode(&mut buf);
+        buf
+    }
+
+    /// Decodes a message payload.
+    ///
+    /// The error code of rendezvous and connect messages is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message or address type is unknown or the
+    /// payload length does not match the address type.
+    pub fn decode(bytes: &[u8]) -> Result<Self, HolepunchError> {
+        if bytes.len() < 2 {
+            return Err(HolepunchError::InvalidLength {
+                expected: 2,
+                actual: bytes.len(),
+            });
+        }
+        let msg_type = bytes[0];
+        if msg_type > MSG_ERROR {
+            return Err(HolepunchError::UnknownMessageType(msg_type));
+        }
+        let addr_len = match bytes[1] {
+            ADDR_V4 => V4_LEN,
+            ADDR_V6 => V6_LEN,
+            addr_type => return Err(HolepunchError::UnknownAddrType(addr_type)),
+        };
+        let expected = 2 + addr_len + 4;
+        if bytes.len() != expected {
+            return Err(HolepunchError::InvalidLength {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+
+        let (addr, code) = bytes[2..].split_at(addr_len);
+        let addr = if addr_len == V4_LEN {
+            CompactAddr::V4(CompactAddrV4::try_from(addr).expect("length is checked"))
+        } else {
+            CompactAddr::V6(CompactAddrV6::try_from(addr).expect("length is checked"))
+        };
+        let code = u32::from_be_bytes([code[0], code[1], code[2], code[3]]);
+
+        Ok(match msg_type {
+            MSG_RENDEZVOUS => HolepunchMessage::Rendezvous(addr),
+            MSG_CONNECT => HolepunchMessage::Connect(addr),
+            _ => HolepunchMessage::Error(addr, ErrorCode::from(code)),
+        })
+    }
+}
+
+/// An error when decoding a holepunch message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HolepunchError {
+    /// The message type is unknown.
+    UnknownMessageType(u8),
+    /// The address type is unknown.
+    UnknownAddrType(u8),
+    /// The payload length does not match the address type.
+    InvalidLength {
+        /// The expected length.
+        expected: usize,
+        /// The actual length.
+        actual: usize,
+    },
+}
+
+impl fmt::Display for HolepunchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HolepunchError::UnknownMessageType(msg_type) => {
+                write!(f, "unknown holepunch message type: {:#04x}", msg_type)
+            }
+            HolepunchError::UnknownAddrType(addr_type) => {
+                write!(f, "unknown holepunch address type: {:#04x}", addr_type)
+            }
+            HolepunchError::InvalidLength { expected, actual } => write!(
+                f,
+                "invalid holepunch message length: expected {} bytes, found {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl error::Error for HolepunchError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v4() -> CompactAddr {
+        "1.2.3.4:6881".parse().unwrap()
+    }
+
+    fn v6() -> CompactAddr {
+        "[2001:db8::1]:6881".parse().unwrap()
+    }
+
+    #[test]
+    fn rendezvous_encoding() {
+        let msg = HolepunchMessage::Rendezvous(v4());
+        let bytes = msg.to_vec();
+        assert_eq!(
+            bytes,
+            [MSG_RENDEZVOUS, ADDR_V4, 1, 2, 3, 4, 0x1a, 0xe1, 0, 0, 0, 0]
+        );
+        assert_eq!(bytes.len(), msg.encoded_len());
+        assert_eq!(HolepunchMessage::decode(&bytes), Ok(msg));
+    }
+
+    #[test]
+    fn round_trip() {
+        for msg in [
+            HolepunchMessage::Connect(v6()),
+            HolepunchMessage::Error(v4(), ErrorCode::NoSelf),
+            HolepunchMessage::Error(v6(), ErrorCode::Other(9)),
+        ] {
+            let bytes = msg.to_vec();
+            assert_eq!(bytes.len(), msg.encoded_len());
+            assert_eq!(HolepunchMessage::decode(&bytes), Ok(msg));
+        }
+        let bytes = HolepunchMessage::Error(v6(), ErrorCode::NoSuchPeer).to_vec();
+        assert_eq!(bytes[0..2], [MSG_ERROR, ADDR_V6]);
+        assert_eq!(bytes[bytes.len() - 4..], [0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn error_codes() {
+        for code in 0..6 {
+            assert_eq!(u32::from(ErrorCode::from(code)), code);
+        }
+        assert_eq!(ErrorCode::from(2), ErrorCode::NotConnected);
+        assert_eq!(ErrorCode::from(3), ErrorCode::NoSupport);
+        assert_eq!(ErrorCode::from(0), ErrorCode::Other(0));
+    }
+
+    #[test]
+    fn connect_ignores_error_code() {
+        let mut bytes = HolepunchMessage::Connect(v4()).to_vec();
+        bytes[11] = 1;
+        assert_eq!(
+            HolepunchMessage::decode(&bytes),
+            Ok(HolepunchMessage::Connect(v4()))
+        );
+    }
+
+    #[test]
+    fn decode_malformed() {
+        assert_eq!(
+            HolepunchMessage::decode(&[0]),
+            Err(HolepunchError::InvalidLength {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            HolepunchMessage::decode(&[3, 0]),
+            Err(HolepunchError::UnknownMessageType(3))
+        );
+        assert_eq!(
+            HolepunchMessage::decode(&[0, 2]),
+            Err(HolepunchError::UnknownAddrType(2))
+        );
+        let bytes = HolepunchMessage::Connect(v6()).to_vec();
+        assert_eq!(
+            HolepunchMessage::decode(&bytes[..bytes.len() - 1]),
+            Err(HolepunchError::InvalidLength {
+                expected: 24,
+                actual: 23
+            })
+        );
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Interleaving of IPv4 and IPv6 blobs for connection attempts.
+//!
+//! Addresses are yielded in rounds which alternate between the address
+//! families, similar to the sorting of destination addresses in Happy
+//! Eyeballs (RFC 8305). Once one family is exhausted, the remaining addresses
+//! of the other family are yielded.
+
+use crate::blob::{self, IterV4, IterV6};
+use std::net::SocketAddr;
+
+/// Options for interleaving blobs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InterleaveOptions {
+    /// If IPv6 addresses are yielded first.
+    pub prefer_v6: bool,
+    /// The number of addresses of the preferred family in each round.
+    ///
+    /// A count of zero is treated as one.
+    pub preferred_count: usize,
+    /// The number of addresses of the other family in each round.
+    ///
+    /// A count of zero is treated as one.
+    pub other_count: usize,
+}
+
+impl Default for InterleaveOptions {
+    fn default() -> Self {
+        Self {
+            prefer_v6: true,
+            preferred_count: 1,
+            other_count: 1,
+        }
+    }
+}
+
+impl InterleaveOptions {
+    /// Returns an iterator which interleaves the compact addresses in an IPv4
+    /// blob and an IPv6 blob.
+    ///
+    /// Trailing bytes which do not form a whole address are not yielded.
+    pub fn interleave<'a>(&self, v4: &'a [u8], v6: &'a [u8]) -> Interleave<'a> {
+        Interleave {
+            v4: blob::iter_v4(v4),
+            v6: blob::iter_v6(v6),
+            prefer_v6: self.prefer_v6,
+            preferred_count: self.preferred_count.max(1),
+            other_count: self.other_count.max(1),
+            pos: 0,
+        }
+    }
+}
+
+/// Returns an iterator which alternates between the compact addresses in an
+/// IPv6 blob and an IPv4 blob, starting with IPv6.
+///
+/// Trailing bytes which do not form a whole address are not yielded.
+pub fn interleave<'a>(v4: &'a [u8], v6: &'a [u8]) -> Interleave<'a> {
+    InterleaveOptions::default().interleave(v4, v6)
+}
+
+/// An iterator which interleaves the compact addresses in an IPv4 blob and an
+/// IPv6 blob.
+#[derive(Clone, Debug)]
+pub struct Interleave<'a> {
+    v4: IterV4<'a>,
+    v6: IterV6<'a>,
+    prefer_v6: bool,
+    preferred_count: usize,
+    other_count: usize,
+    pos: usize,
+}
+
+impl Interleave<'_> {
+    fn next_v4(&mut self) -> Option<SocketAddr> {
+        self.v4.next().map(SocketAddr::V4)
+    }
+
+    fn next_v6(&mut self) -> Option<SocketAddr> {
+        self.v6.next().map(SocketAddr::V6)
+    }
+}
+
+impl Iterator for Interleave<'_> {
+    type Item = SocketAddr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let preferred = self.pos < self.preferred_count;
+        self.pos = (self.pos + 1) % (self.preferred_count + self.other_count);
+
+        if preferred == self.prefer_v6 {
+            self.next_v6().or_else(|| self.next_v4())
+        } else {
+            self.next_v4().or_else(|| self.next_v6())
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.v4.len() + self.v6.len();
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Interleave<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{socket_v4, socket_v6};
+
+    fn v4(n: u8) -> SocketAddr {
+        socket_v4(n, 80).into()
+    }
+
+    fn v6(n: u16) -> SocketAddr {
+        socket_v6(n, 80).into()
+    }
+
+    fn blobs(n4: u8, n6: u16) -> (Vec<u8>, Vec<u8>) {
+        blob::partition_encode((1..=n4).map(v4).chain((1..=n6).map(v6)))
+    }
+
+    #[test]
+    fn alternates_starting_with_v6() {
+        let (b4, b6) = blobs(3, 2);
+        let iter = interleave(&b4, &b6);
+        assert_eq!(iter.len(), 5);
+        assert_eq!(
+            iter.collect::<Vec<_>>(),
+            [v6(1), v4(1), v6(2), v4(2), v4(3)]
+        );
+    }
+
+    #[test]
+    fn prefer_v4_with_counts() {
+        let (b4, b6) = blobs(4, 3);
+        let options = InterleaveOptions {
+            prefer_v6: false,
+            preferred_count: 2,
+            other_count: 1,
+        };
+        assert_eq!(
+            options.interleave(&b4, &b6).collect::<Vec<_>>(),
+            [v4(1), v4(2), v6(1), v4(3), v4(4), v6(2), v6(3)]
+        );
+    }
+
+    #[test]
+    fn zero_counts_are_one() {
+        let (b4, b6) = blobs(2, 2);
+        let options = InterleaveOptions {
+            prefer_v6: true,
+            preferred_count: 0,
+            other_count: 0,
+        };
+        assert_eq!(
+            options.interleave(&b4, &b6).collect::<Vec<_>>(),
+            interleave(&b4, &b6).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn single_family_and_trailing_bytes() {
+        let (mut b4, _) = blobs(2, 0);
+        b4.push(0xee);
+        assert_eq!(interleave(&b4, &[]).collect::<Vec<_>>(), [v4(1), v4(2)]);
+        assert_eq!(interleave(&[], &[]).count(), 0);
+    }
+}
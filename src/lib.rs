@@ -7,8 +7,117 @@
 // except according to those terms.
 
 //! Standard IP addresses with ports represented as compact byte arrays.
+//!
+//! The crate requires `std`. It builds for `wasm32-unknown-unknown`, where
+//! the APIs which read the clock are not available.
 
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+/// Emits a debug-level `tracing` event if the `tracing` feature is enabled.
+macro_rules! debug_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+mod anonymize;
+pub mod bitmap;
+pub mod blob;
+pub mod blocklist;
+pub mod bloom;
+pub mod cached;
+pub mod canonical;
+#[cfg(feature = "ipnet")]
+pub mod cidr;
+#[cfg(feature = "zstd")]
+pub mod compress;
+pub mod endpoint;
+#[cfg(feature = "extended")]
+pub mod extended;
+pub mod fingerprint;
+pub mod frame;
+#[cfg(feature = "maxminddb")]
+pub mod geoip;
+pub mod holepunch;
+pub mod interleave;
+pub mod list;
+pub mod ltep;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "multiaddr")]
+pub mod multiaddr;
+pub mod node;
+#[cfg(feature = "rayon")]
+pub mod par;
+#[cfg(any(feature = "nom", feature = "winnow"))]
+pub mod parser;
+pub mod pcp;
+pub mod peers;
+pub mod pex;
+pub mod random;
+pub mod resume;
+mod siphash;
+#[cfg(feature = "smallvec")]
+pub mod small;
+#[cfg(all(feature = "libc", unix))]
+mod sockaddr;
+pub mod socks;
+#[cfg(feature = "sqlx")]
+mod sql;
+pub mod stats;
+pub mod store;
+pub mod stun;
+pub mod tagged;
+#[cfg(test)]
+mod test_util;
+pub mod text;
+pub mod tracker;
+mod transition;
+pub mod trie;
+#[cfg(feature = "ufmt")]
+mod ufmt;
+pub mod vuze;
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
+#[cfg(all(feature = "windows-sys", windows))]
+mod winsock;
+pub mod wire;
+pub mod zone;
+
+use std::{
+    convert::TryFrom,
+    error, fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    str::FromStr,
+};
+
+/// The byte order of the port in a compact address.
+///
+/// The compact format used by BitTorrent and most other protocols encodes the
+/// port in network byte order, which is big-endian.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    /// Big-endian (network byte order).
+    #[default]
+    Big,
+    /// Little-endian.
+    Little,
+}
+
+impl Endianness {
+    fn port_to_bytes(self, port: u16) -> [u8; 2] {
+        match self {
+            Endianness::Big => port.to_be_bytes(),
+            Endianness::Little => port.to_le_bytes(),
+        }
+    }
+
+    fn port_from_bytes(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            Endianness::Big => u16::from_be_bytes(bytes),
+            Endianness::Little => u16::from_le_bytes(bytes),
+        }
+    }
+}
 
 /// An IPv4 socket address representable by a compact format.
 ///
@@ -20,25 +129,50 @@ pub trait CompactAddrV4Info: private::Sealed {
     fn to_compact_address(&self) -> [u8; 6];
 
     /// Converts from the compact address to the self type.
-    fn from_compact_address(bytes: &[u8; 6]) -> Self;
+    fn from_compact(bytes: [u8; 6]) -> Self;
+
+    /// Converts from the compact address to the self type.
+    #[deprecated(note = "use `from_compact` which takes the array by value")]
+    fn from_compact_address(bytes: &[u8; 6]) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from_compact(*bytes)
+    }
+
+    /// Returns the address encoded as a compact address with the port in the
+    /// given byte order.
+    fn to_compact_address_endian(&self, endianness: Endianness) -> [u8; 6];
+
+    /// Converts from the compact address with the port in the given byte order
+    /// to the self type.
+    fn from_compact_endian(bytes: [u8; 6], endianness: Endianness) -> Self;
 }
 
 impl CompactAddrV4Info for SocketAddrV4 {
     fn to_compact_address(&self) -> [u8; 6] {
+        self.to_compact_address_endian(Endianness::Big)
+    }
+
+    fn from_compact(bytes: [u8; 6]) -> Self {
+        Self::from_compact_endian(bytes, Endianness::Big)
+    }
+
+    fn to_compact_address_endian(&self, endianness: Endianness) -> [u8; 6] {
         let mut a: [u8; 6] = [0; 6];
         a[0..4].copy_from_slice(&self.ip().octets());
-        a[4..6].copy_from_slice(&self.port().to_be_bytes());
+        a[4..6].copy_from_slice(&endianness.port_to_bytes(self.port()));
         a
     }
 
-    fn from_compact_address(bytes: &[u8; 6]) -> Self {
+    fn from_compact_endian(bytes: [u8; 6], endianness: Endianness) -> Self {
         let mut ip: [u8; 4] = [0; 4];
         ip[0..4].copy_from_slice(&bytes[0..4]);
         let ip = Ipv4Addr::from(ip);
 
         let mut port: [u8; 2] = [0; 2];
         port[0..2].copy_from_slice(&bytes[4..6]);
-        let port = u16::from_be_bytes(port);
+        let port = endianness.port_from_bytes(port);
 
         SocketAddrV4::new(ip, port)
     }
@@ -54,35 +188,988 @@ pub trait CompactAddrV6Info: private::Sealed {
     fn to_compact_address(&self) -> [u8; 18];
 
     /// Converts from the compact address to the self type.
-    fn from_compact_address(bytes: &[u8; 18]) -> Self;
+    fn from_compact(bytes: [u8; 18]) -> Self;
+
+    /// Converts from the compact address to the self type.
+    #[deprecated(note = "use `from_compact` which takes the array by value")]
+    fn from_compact_address(bytes: &[u8; 18]) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from_compact(*bytes)
+    }
+
+    /// Returns the address encoded as a compact address with the port in the
+    /// given byte order.
+    fn to_compact_address_endian(&self, endianness: Endianness) -> [u8; 18];
+
+    /// Converts from the compact address with the port in the given byte order
+    /// to the self type.
+    fn from_compact_endian(bytes: [u8; 18], endianness: Endianness) -> Self;
+
+    /// Returns true if encoding as a compact address does not lose any
+    /// information.
+    ///
+    /// The compact format does not contain the flow info or scope ID, so
+    /// addresses with a non-zero flow info or scope ID (such as link-local
+    /// addresses with a scope) are not encoded losslessly.
+    fn is_compact_lossless(&self) -> bool;
+
+    /// Returns the address encoded as a compact address.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address has a non-zero flow info or scope ID
+    /// which would be lost.
+    fn try_to_compact_address(&self) -> Result<[u8; 18], LossyEncodeError>;
 }
 
 impl CompactAddrV6Info for SocketAddrV6 {
     fn to_compact_address(&self) -> [u8; 18] {
+        self.to_compact_address_endian(Endianness::Big)
+    }
+
+    fn from_compact(bytes: [u8; 18]) -> Self {
+        Self::from_compact_endian(bytes, Endianness::Big)
+    }
+
+    fn to_compact_address_endian(&self, endianness: Endianness) -> [u8; 18] {
         let mut a: [u8; 18] = [0; 18];
         a[0..16].copy_from_slice(&self.ip().octets());
-        a[16..18].copy_from_slice(&self.port().to_be_bytes());
+        a[16..18].copy_from_slice(&endianness.port_to_bytes(self.port()));
         a
     }
 
-    fn from_compact_address(bytes: &[u8; 18]) -> Self {
+    fn from_compact_endian(bytes: [u8; 18], endianness: Endianness) -> Self {
         let mut ip: [u8; 16] = [0; 16];
         ip[0..16].copy_from_slice(&bytes[0..16]);
         let ip = Ipv6Addr::from(ip);
 
         let mut port: [u8; 2] = [0; 2];
         port[0..2].copy_from_slice(&bytes[16..18]);
-        let port = u16::from_be_bytes(port);
+        let port = endianness.port_from_bytes(port);
 
         SocketAddrV6::new(ip, port, 0, 0)
     }
+
+    fn is_compact_lossless(&self) -> bool {
+        self.flowinfo() == 0 && self.scope_id() == 0
+    }
+
+    fn try_to_compact_address(&self) -> Result<[u8; 18], LossyEncodeError> {
+        if self.is_compact_lossless() {
+            Ok(self.to_compact_address())
+        } else {
+            Err(LossyEncodeError {
+                flowinfo: self.flowinfo(),
+                scope_id: self.scope_id(),
+            })
+        }
+    }
+}
+
+/// A socket address of either family representable by a compact format.
+///
+/// Unlike [`CompactAddrV4Info`] and [`CompactAddrV6Info`], the trait is object
+/// safe, so IPv4 and IPv6 addresses can be handled as `dyn DynCompactAddrInfo`.
+///
+/// This trait is sealed and cannot be implemented for types outside this crate.
+pub trait DynCompactAddrInfo: private::Sealed {
+    /// Returns the length of the compact address.
+    fn compact_len(&self) -> usize;
+
+    /// Calls the function with the address encoded as a compact address with
+    /// the port in the given byte order.
+    fn write_compact_endian(&self, endianness: Endianness, f: &mut dyn FnMut(&[u8]));
+
+    /// Calls the function with the address encoded as a compact address.
+    fn write_compact(&self, f: &mut dyn FnMut(&[u8])) {
+        self.write_compact_endian(Endianness::Big, f);
+    }
+
+    /// Appends the address encoded as a compact address to the buffer.
+    fn encode_compact(&self, buf: &mut Vec<u8>) {
+        self.write_compact(&mut |bytes| buf.extend_from_slice(bytes));
+    }
+}
+
+impl DynCompactAddrInfo for SocketAddrV4 {
+    fn compact_len(&self) -> usize {
+        6
+    }
+
+    fn write_compact_endian(&self, endianness: Endianness, f: &mut dyn FnMut(&[u8])) {
+        f(&self.to_compact_address_endian(endianness));
+    }
+}
+
+impl DynCompactAddrInfo for SocketAddrV6 {
+    fn compact_len(&self) -> usize {
+        18
+    }
+
+    fn write_compact_endian(&self, endianness: Endianness, f: &mut dyn FnMut(&[u8])) {
+        f(&self.to_compact_address_endian(endianness));
+    }
+}
+
+impl DynCompactAddrInfo for SocketAddr {
+    fn compact_len(&self) -> usize {
+        match self {
+            SocketAddr::V4(addr) => addr.compact_len(),
+            SocketAddr::V6(addr) => addr.compact_len(),
+        }
+    }
+
+    fn write_compact_endian(&self, endianness: Endianness, f: &mut dyn FnMut(&[u8])) {
+        match self {
+            SocketAddr::V4(addr) => addr.write_compact_endian(endianness, f),
+            SocketAddr::V6(addr) => addr.write_compact_endian(endianness, f),
+        }
+    }
+}
+
+impl DynCompactAddrInfo for CompactAddrV4 {
+    fn compact_len(&self) -> usize {
+        6
+    }
+
+    fn write_compact_endian(&self, endianness: Endianness, f: &mut dyn FnMut(&[u8])) {
+        match endianness {
+            Endianness::Big => f(&self.0),
+            Endianness::Little => SocketAddrV4::from(*self).write_compact_endian(endianness, f),
+        }
+    }
+}
+
+impl DynCompactAddrInfo for CompactAddrV6 {
+    fn compact_len(&self) -> usize {
+        18
+    }
+
+    fn write_compact_endian(&self, endianness: Endianness, f: &mut dyn FnMut(&[u8])) {
+        match endianness {
+            Endianness::Big => f(&self.0),
+            Endianness::Little => SocketAddrV6::from(*self).write_compact_endian(endianness, f),
+        }
+    }
+}
+
+impl DynCompactAddrInfo for CompactAddr {
+    fn compact_len(&self) -> usize {
+        match self {
+            CompactAddr::V4(addr) => addr.compact_len(),
+            CompactAddr::V6(addr) => addr.compact_len(),
+        }
+    }
+
+    fn write_compact_endian(&self, endianness: Endianness, f: &mut dyn FnMut(&[u8])) {
+        match self {
+            CompactAddr::V4(addr) => addr.write_compact_endian(endianness, f),
+            CompactAddr::V6(addr) => addr.write_compact_endian(endianness, f),
+        }
+    }
+}
+
+/// An IPv4 socket address in its compact form.
+///
+/// The address is stored as the 4 byte IP address followed by the 2 byte port
+/// in network byte order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct CompactAddrV4([u8; 6]);
+
+impl CompactAddrV4 {
+    /// The loopback IP address (`127.0.0.1`) with port 0.
+    pub const LOOPBACK: Self = Self([127, 0, 0, 1, 0, 0]);
+
+    /// The unspecified IP address (`0.0.0.0`) with port 0.
+    pub const UNSPECIFIED: Self = Self([0, 0, 0, 0, 0, 0]);
+
+    /// The broadcast IP address (`255.255.255.255`) with port 0.
+    pub const BROADCAST: Self = Self([255, 255, 255, 255, 0, 0]);
+
+    /// Instantiates from the compact address bytes.
+    pub const fn new(bytes: [u8; 6]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the compact address bytes as a compact address reference.
+    pub fn from_ref(bytes: &[u8; 6]) -> &Self {
+        // SAFETY: the type is repr(transparent) over the byte array.
+        unsafe { &*(bytes as *const [u8; 6]).cast::<Self>() }
+    }
+
+    /// Returns the compact address bytes.
+    pub const fn as_bytes(&self) -> &[u8; 6] {
+        &self.0
+    }
+
+    /// Returns the IP address.
+    pub fn ip(&self) -> Ipv4Addr {
+        Ipv4Addr::new(self.0[0], self.0[1], self.0[2], self.0[3])
+    }
+
+    /// Returns the port.
+    pub fn port(&self) -> u16 {
+        u16::from_be_bytes([self.0[4], self.0[5]])
+    }
+}
+
+impl From<[u8; 6]> for CompactAddrV4 {
+    fn from(bytes: [u8; 6]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<CompactAddrV4> for [u8; 6] {
+    fn from(addr: CompactAddrV4) -> Self {
+        addr.0
+    }
+}
+
+impl From<SocketAddrV4> for CompactAddrV4 {
+    fn from(addr: SocketAddrV4) -> Self {
+        Self(addr.to_compact_address())
+    }
+}
+
+impl From<CompactAddrV4> for SocketAddrV4 {
+    fn from(addr: CompactAddrV4) -> Self {
+        SocketAddrV4::from_compact(addr.0)
+    }
+}
+
+impl TryFrom<&[u8]> for CompactAddrV4 {
+    type Error = InvalidLengthError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        <[u8; 6]>::try_from(bytes)
+            .map(Self)
+            .map_err(|_| InvalidLengthError { len: bytes.len() })
+    }
+}
+
+impl AsRef<[u8]> for CompactAddrV4 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for CompactAddrV4 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&SocketAddrV4::from(*self), f)
+    }
+}
+
+/// An IPv6 socket address in its compact form.
+///
+/// The address is stored as the 16 byte IP address followed by the 2 byte port
+/// in network byte order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct CompactAddrV6([u8; 18]);
+
+impl CompactAddrV6 {
+    /// The loopback IP address (`::1`) with port 0.
+    pub const LOOPBACK: Self = Self([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0]);
+
+    /// The unspecified IP address (`::`) with port 0.
+    pub const UNSPECIFIED: Self = Self([0; 18]);
+
+    /// Instantiates from the compact address bytes.
+    pub const fn new(bytes: [u8; 18]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the compact address bytes as a compact address reference.
+    pub fn from_ref(bytes: &[u8; 18]) -> &Self {
+        // SAFETY: the type is repr(transparent) over the byte array.
+        unsafe { &*(bytes as *const [u8; 18]).cast::<Self>() }
+    }
+
+    /// Returns the compact address bytes.
+    pub const fn as_bytes(&self) -> &[u8; 18] {
+        &self.0
+    }
+
+    /// Returns the IP address.
+    pub fn ip(&self) -> Ipv6Addr {
+        let mut ip: [u8; 16] = [0; 16];
+        ip.copy_from_slice(&self.0[0..16]);
+        Ipv6Addr::from(ip)
+    }
+
+    /// Returns the port.
+    pub fn port(&self) -> u16 {
+        u16::from_be_bytes([self.0[16], self.0[17]])
+    }
+}
+
+impl From<[u8; 18]> for CompactAddrV6 {
+    fn from(bytes: [u8; 18]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<CompactAddrV6> for [u8; 18] {
+    fn from(addr: CompactAddrV6) -> Self {
+        addr.0
+    }
+}
+
+impl From<SocketAddrV6> for CompactAddrV6 {
+    fn from(addr: SocketAddrV6) -> Self {
+        Self(addr.to_compact_address())
+    }
+}
+
+impl From<CompactAddrV6> for SocketAddrV6 {
+    fn from(addr: CompactAddrV6) -> Self {
+        SocketAddrV6::from_compact(addr.0)
+    }
+}
+
+impl TryFrom<&[u8]> for CompactAddrV6 {
+    type Error = InvalidLengthError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        <[u8; 18]>::try_from(bytes)
+            .map(Self)
+            .map_err(|_| InvalidLengthError { len: bytes.len() })
+    }
+}
+
+impl AsRef<[u8]> for CompactAddrV6 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for CompactAddrV6 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&SocketAddrV6::from(*self), f)
+    }
+}
+
+/// A borrowed IPv4 socket address in its compact form.
+///
+/// The address is a view of 6 bytes in a buffer, such as an entry in a blob.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CompactAddrV4Ref<'a>(&'a [u8; 6]);
+
+impl<'a> CompactAddrV4Ref<'a> {
+    /// Instantiates from the compact address bytes.
+    pub const fn new(bytes: &'a [u8; 6]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the compact address bytes.
+    pub const fn as_bytes(&self) -> &'a [u8; 6] {
+        self.0
+    }
+
+    /// Returns the IP address.
+    pub fn ip(&self) -> Ipv4Addr {
+        Ipv4Addr::new(self.0[0], self.0[1], self.0[2], self.0[3])
+    }
+
+    /// Returns the port.
+    pub fn port(&self) -> u16 {
+        u16::from_be_bytes([self.0[4], self.0[5]])
+    }
+}
+
+impl<'a> From<&'a CompactAddrV4> for CompactAddrV4Ref<'a> {
+    fn from(addr: &'a CompactAddrV4) -> Self {
+        Self(&addr.0)
+    }
+}
+
+impl From<CompactAddrV4Ref<'_>> for CompactAddrV4 {
+    fn from(addr: CompactAddrV4Ref<'_>) -> Self {
+        Self(*addr.0)
+    }
+}
+
+impl From<CompactAddrV4Ref<'_>> for SocketAddrV4 {
+    fn from(addr: CompactAddrV4Ref<'_>) -> Self {
+        SocketAddrV4::from_compact(*addr.0)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for CompactAddrV4Ref<'a> {
+    type Error = InvalidLengthError;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        <&[u8; 6]>::try_from(bytes)
+            .map(Self)
+            .map_err(|_| InvalidLengthError { len: bytes.len() })
+    }
+}
+
+impl AsRef<[u8]> for CompactAddrV4Ref<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl fmt::Display for CompactAddrV4Ref<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&SocketAddrV4::from(*self), f)
+    }
+}
+
+/// A borrowed IPv6 socket address in its compact form.
+///
+/// The address is a view of 18 bytes in a buffer, such as an entry in a blob.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CompactAddrV6Ref<'a>(&'a [u8; 18]);
+
+impl<'a> CompactAddrV6Ref<'a> {
+    /// Instantiates from the compact address bytes.
+    pub const fn new(bytes: &'a [u8; 18]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the compact address bytes.
+    pub const fn as_bytes(&self) -> &'a [u8; 18] {
+        self.0
+    }
+
+    /// Returns the IP address.
+    pub fn ip(&self) -> Ipv6Addr {
+        let mut ip: [u8; 16] = [0; 16];
+        ip.copy_from_slice(&self.0[0..16]);
+        Ipv6Addr::from(ip)
+    }
+
+    /// Returns the port.
+    pub fn port(&self) -> u16 {
+        u16::from_be_bytes([self.0[16], self.0[17]])
+    }
+}
+
+impl<'a> From<&'a CompactAddrV6> for CompactAddrV6Ref<'a> {
+    fn from(addr: &'a CompactAddrV6) -> Self {
+        Self(&addr.0)
+    }
+}
+
+impl From<CompactAddrV6Ref<'_>> for CompactAddrV6 {
+    fn from(addr: CompactAddrV6Ref<'_>) -> Self {
+        Self(*addr.0)
+    }
+}
+
+impl From<CompactAddrV6Ref<'_>> for SocketAddrV6 {
+    fn from(addr: CompactAddrV6Ref<'_>) -> Self {
+        SocketAddrV6::from_compact(*addr.0)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for CompactAddrV6Ref<'a> {
+    type Error = InvalidLengthError;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        <&[u8; 18]>::try_from(bytes)
+            .map(Self)
+            .map_err(|_| InvalidLengthError { len: bytes.len() })
+    }
+}
+
+impl AsRef<[u8]> for CompactAddrV6Ref<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl fmt::Display for CompactAddrV6Ref<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&SocketAddrV6::from(*self), f)
+    }
+}
+
+/// A compact IPv4 or IPv6 socket address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CompactAddr {
+    /// An IPv4 compact address.
+    V4(CompactAddrV4),
+    /// An IPv6 compact address.
+    V6(CompactAddrV6),
+}
+
+impl CompactAddr {
+    /// Returns the compact address bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            CompactAddr::V4(addr) => addr.as_bytes(),
+            CompactAddr::V6(addr) => addr.as_bytes(),
+        }
+    }
+
+    /// Returns the IP address.
+    pub fn ip(&self) -> IpAddr {
+        match self {
+            CompactAddr::V4(addr) => IpAddr::V4(addr.ip()),
+            CompactAddr::V6(addr) => IpAddr::V6(addr.ip()),
+        }
+    }
+
+    /// Returns the port.
+    pub fn port(&self) -> u16 {
+        match self {
+            CompactAddr::V4(addr) => addr.port(),
+            CompactAddr::V6(addr) => addr.port(),
+        }
+    }
+
+    /// Returns true if the address is an IPv4 compact address.
+    pub fn is_ipv4(&self) -> bool {
+        matches!(self, CompactAddr::V4(_))
+    }
+
+    /// Returns true if the address is an IPv6 compact address.
+    pub fn is_ipv6(&self) -> bool {
+        matches!(self, CompactAddr::V6(_))
+    }
+}
+
+impl From<CompactAddrV4> for CompactAddr {
+    fn from(addr: CompactAddrV4) -> Self {
+        CompactAddr::V4(addr)
+    }
+}
+
+impl From<CompactAddrV6> for CompactAddr {
+    fn from(addr: CompactAddrV6) -> Self {
+        CompactAddr::V6(addr)
+    }
+}
+
+impl From<SocketAddrV4> for CompactAddr {
+    fn from(addr: SocketAddrV4) -> Self {
+        CompactAddr::V4(CompactAddrV4::from(addr))
+    }
+}
+
+impl From<SocketAddrV6> for CompactAddr {
+    fn from(addr: SocketAddrV6) -> Self {
+        CompactAddr::V6(CompactAddrV6::from(addr))
+    }
+}
+
+impl From<SocketAddr> for CompactAddr {
+    fn from(addr: SocketAddr) -> Self {
+        match addr {
+            SocketAddr::V4(addr) => CompactAddr::from(addr),
+            SocketAddr::V6(addr) => CompactAddr::from(addr),
+        }
+    }
+}
+
+impl From<CompactAddr> for SocketAddr {
+    fn from(addr: CompactAddr) -> Self {
+        match addr {
+            CompactAddr::V4(addr) => SocketAddr::V4(SocketAddrV4::from(addr)),
+            CompactAddr::V6(addr) => SocketAddr::V6(SocketAddrV6::from(addr)),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for CompactAddr {
+    type Error = InvalidLengthError;
+
+    /// Converts 6 bytes to an IPv4 compact address and 18 bytes to an IPv6
+    /// compact address.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        match bytes.len() {
+            6 => CompactAddrV4::try_from(bytes).map(CompactAddr::V4),
+            18 => CompactAddrV6::try_from(bytes).map(CompactAddr::V6),
+            len => Err(InvalidLengthError { len }),
+        }
+    }
+}
+
+impl AsRef<[u8]> for CompactAddr {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
 }
 
+impl fmt::Display for CompactAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompactAddr::V4(addr) => fmt::Display::fmt(addr, f),
+            CompactAddr::V6(addr) => fmt::Display::fmt(addr, f),
+        }
+    }
+}
+
+impl FromStr for CompactAddrV4 {
+    type Err = ParseAddrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<SocketAddrV4>()
+            .map(Self::from)
+            .map_err(|_| ParseAddrError::new(ParseAddrErrorKind::InvalidAddr))
+    }
+}
+
+impl FromStr for CompactAddrV6 {
+    type Err = ParseAddrError;
+
+    /// Parses an IPv6 socket address.
+    ///
+    /// A zone identifier such as `[fe80::1%3]:6881` is accepted only if it is
+    /// zero because the compact address cannot store the scope ID.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let addr = zone::parse_v6(s)?;
+        if addr.scope_id() != 0 {
+            return Err(ParseAddrError::new(ParseAddrErrorKind::Scoped));
+        }
+        Ok(Self::from(addr))
+    }
+}
+
+impl FromStr for CompactAddr {
+    type Err = ParseAddrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('[') {
+            CompactAddrV6::from_str(s).map(CompactAddr::V6)
+        } else {
+            CompactAddrV4::from_str(s).map(CompactAddr::V4)
+        }
+    }
+}
+
+/// An error when converting bytes with an invalid length to a compact address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidLengthError {
+    len: usize,
+}
+
+impl InvalidLengthError {
+    /// Returns the length of the bytes.
+    pub fn actual_len(&self) -> usize {
+        self.len
+    }
+}
+
+impl fmt::Display for InvalidLengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid compact address length: {}", self.len)
+    }
+}
+
+impl error::Error for InvalidLengthError {}
+
+/// An error when encoding an IPv6 socket address as a compact address would
+/// lose its flow info or scope ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LossyEncodeError {
+    flowinfo: u32,
+    scope_id: u32,
+}
+
+impl LossyEncodeError {
+    /// Returns the flow info which would be lost.
+    pub fn flowinfo(&self) -> u32 {
+        self.flowinfo
+    }
+
+    /// Returns the scope ID which would be lost.
+    pub fn scope_id(&self) -> u32 {
+        self.scope_id
+    }
+}
+
+impl fmt::Display for LossyEncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "compact address cannot represent flow info {} and scope ID {}",
+            self.flowinfo, self.scope_id
+        )
+    }
+}
+
+impl error::Error for LossyEncodeError {}
+
+/// The kind of error when parsing a socket address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseAddrErrorKind {
+    /// The text is not a socket address of the expected family.
+    InvalidAddr,
+    /// The zone identifier is empty or the numeric zone overflows.
+    InvalidZone,
+    /// The zone identifier is an interface name and interface names cannot
+    /// be resolved.
+    NamedZone,
+    /// The zone identifier is an interface name which does not exist.
+    UnknownInterface,
+    /// The address has a scope ID which a compact address cannot store.
+    Scoped,
+}
+
+/// An error when parsing a socket address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseAddrError {
+    kind: ParseAddrErrorKind,
+}
+
+impl ParseAddrError {
+    fn new(kind: ParseAddrErrorKind) -> Self {
+        Self { kind }
+    }
+
+    /// Returns the kind of error.
+    pub fn kind(&self) -> ParseAddrErrorKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for ParseAddrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.kind {
+            ParseAddrErrorKind::InvalidAddr => "invalid socket address",
+            ParseAddrErrorKind::InvalidZone => "invalid zone identifier",
+            ParseAddrErrorKind::NamedZone => {
+                "interface name zone identifiers are not supported, use the interface index"
+            }
+            ParseAddrErrorKind::UnknownInterface => "unknown interface in zone identifier",
+            ParseAddrErrorKind::Scoped => {
+                "compact address cannot represent the scope ID of the zone identifier"
+            }
+        };
+        f.write_str(reason)
+    }
+}
+
+impl error::Error for ParseAddrError {}
+
 mod private {
-    use std::net::{SocketAddrV4, SocketAddrV6};
+    use crate::{CompactAddr, CompactAddrV4, CompactAddrV6};
+    use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6};
 
     pub trait Sealed {}
 
     impl Sealed for SocketAddrV6 {}
     impl Sealed for SocketAddrV4 {}
+    impl Sealed for SocketAddr {}
+    impl Sealed for CompactAddrV4 {}
+    impl Sealed for CompactAddrV6 {}
+    impl Sealed for CompactAddr {}
+
+    #[cfg(feature = "ipnet")]
+    impl Sealed for ipnet::Ipv4Net {}
+    #[cfg(feature = "ipnet")]
+    impl Sealed for ipnet::Ipv6Net {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v4_endianness_round_trip() {
+        let addr = SocketAddrV4::new(Ipv4Addr::new(1, 2, 3, 4), 0x1ae1);
+        assert_eq!(addr.to_compact_address(), [1, 2, 3, 4, 0x1a, 0xe1]);
+        assert_eq!(
+            addr.to_compact_address_endian(Endianness::Little),
+            [1, 2, 3, 4, 0xe1, 0x1a]
+        );
+        for endianness in [Endianness::Big, Endianness::Little] {
+            let bytes = addr.to_compact_address_endian(endianness);
+            assert_eq!(SocketAddrV4::from_compact_endian(bytes, endianness), addr);
+        }
+        assert_eq!(Endianness::default(), Endianness::Big);
+    }
+
+    #[test]
+    fn v6_endianness_round_trip() {
+        let addr = SocketAddrV6::new(Ipv6Addr::LOCALHOST, 0x1ae1, 0, 0);
+        let bytes = addr.to_compact_address_endian(Endianness::Little);
+        assert_eq!(bytes[16..], [0xe1, 0x1a]);
+        assert_eq!(bytes[..16], Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(
+            SocketAddrV6::from_compact_endian(bytes, Endianness::Little),
+            addr
+        );
+        assert_eq!(
+            SocketAddrV6::from_compact_endian(bytes, Endianness::Big).port(),
+            0xe11a
+        );
+    }
+
+    #[test]
+    fn v4_ref() {
+        let bytes = [1, 2, 3, 4, 0x1a, 0xe1];
+        let addr = CompactAddrV4Ref::new(&bytes);
+        assert_eq!(addr.ip(), Ipv4Addr::new(1, 2, 3, 4));
+        assert_eq!(addr.port(), 6881);
+        assert_eq!(addr.as_bytes(), &bytes);
+        assert_eq!(addr.to_string(), "1.2.3.4:6881");
+        assert_eq!(CompactAddrV4::from(addr), CompactAddrV4::from(bytes));
+        assert_eq!(
+            SocketAddrV4::from(addr),
+            SocketAddrV4::new(Ipv4Addr::new(1, 2, 3, 4), 6881)
+        );
+        assert_eq!(CompactAddrV4Ref::try_from(&bytes[..]), Ok(addr));
+        assert_eq!(
+            CompactAddrV4Ref::try_from(&bytes[..5]),
+            Err(InvalidLengthError { len: 5 })
+        );
+    }
+
+    #[test]
+    fn v6_ref() {
+        let owned = CompactAddrV6::from(SocketAddrV6::new(Ipv6Addr::LOCALHOST, 80, 0, 0));
+        let addr = CompactAddrV6Ref::from(&owned);
+        assert_eq!(addr.ip(), Ipv6Addr::LOCALHOST);
+        assert_eq!(addr.port(), 80);
+        assert_eq!(addr.to_string(), "[::1]:80");
+        assert_eq!(CompactAddrV6::from(addr), owned);
+        assert_eq!(addr.as_ref(), owned.as_bytes());
+        assert!(CompactAddrV6Ref::try_from(&[0u8; 19][..]).is_err());
+    }
+
+    #[test]
+    fn from_ref() {
+        let bytes = [1, 2, 3, 4, 0, 80];
+        let addr = CompactAddrV4::from_ref(&bytes);
+        assert_eq!(addr, &CompactAddrV4::from(bytes));
+        assert_eq!(addr.as_bytes().as_ptr(), bytes.as_ptr());
+
+        let bytes = [6; 18];
+        assert_eq!(CompactAddrV6::from_ref(&bytes), &CompactAddrV6::from(bytes));
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(CompactAddrV4::LOOPBACK.ip(), Ipv4Addr::LOCALHOST);
+        assert_eq!(CompactAddrV4::UNSPECIFIED.ip(), Ipv4Addr::UNSPECIFIED);
+        assert_eq!(CompactAddrV4::BROADCAST.ip(), Ipv4Addr::BROADCAST);
+        assert_eq!(CompactAddrV6::LOOPBACK.ip(), Ipv6Addr::LOCALHOST);
+        assert_eq!(CompactAddrV6::UNSPECIFIED.ip(), Ipv6Addr::UNSPECIFIED);
+        for port in [
+            CompactAddrV4::LOOPBACK.port(),
+            CompactAddrV4::BROADCAST.port(),
+            CompactAddrV6::LOOPBACK.port(),
+        ] {
+            assert_eq!(port, 0);
+        }
+    }
+
+    #[test]
+    fn dyn_compact_addr_info() {
+        let v4 = SocketAddrV4::new(Ipv4Addr::new(1, 2, 3, 4), 0x1ae1);
+        let v6 = SocketAddrV6::new(Ipv6Addr::LOCALHOST, 0x1ae1, 0, 0);
+        let addrs: Vec<Box<dyn DynCompactAddrInfo>> = vec![
+            Box::new(v4),
+            Box::new(v6),
+            Box::new(SocketAddr::V4(v4)),
+            Box::new(CompactAddrV4::from(v4)),
+            Box::new(CompactAddrV6::from(v6)),
+            Box::new(CompactAddr::from(v6)),
+        ];
+        let expected_lens = [6, 18, 6, 6, 18, 18];
+        for (addr, expected_len) in addrs.iter().zip(expected_lens) {
+            assert_eq!(addr.compact_len(), expected_len);
+
+            let mut buf = Vec::new();
+            addr.encode_compact(&mut buf);
+            assert_eq!(buf.len(), expected_len);
+            assert_eq!(buf[expected_len - 2..], [0x1a, 0xe1]);
+
+            let mut little = Vec::new();
+            addr.write_compact_endian(Endianness::Little, &mut |bytes| {
+                little.extend_from_slice(bytes)
+            });
+            assert_eq!(little[..expected_len - 2], buf[..expected_len - 2]);
+            assert_eq!(little[expected_len - 2..], [0xe1, 0x1a]);
+        }
+    }
+
+    #[test]
+    fn lossless_v6_encoding() {
+        let addr = SocketAddrV6::new(Ipv6Addr::LOCALHOST, 80, 0, 0);
+        assert!(addr.is_compact_lossless());
+        assert_eq!(addr.try_to_compact_address(), Ok(addr.to_compact_address()));
+
+        let scoped = SocketAddrV6::new("fe80::1".parse().unwrap(), 80, 0, 2);
+        assert!(!scoped.is_compact_lossless());
+        let err = scoped.try_to_compact_address().unwrap_err();
+        assert_eq!(err.flowinfo(), 0);
+        assert_eq!(err.scope_id(), 2);
+
+        let flow = SocketAddrV6::new(Ipv6Addr::LOCALHOST, 80, 7, 0);
+        let err = flow.try_to_compact_address().unwrap_err();
+        assert_eq!(err.flowinfo(), 7);
+        assert_eq!(
+            err.to_string(),
+            "compact address cannot represent flow info 7 and scope ID 0"
+        );
+    }
+
+    #[test]
+    fn from_compact_by_value() {
+        use std::convert::TryInto;
+
+        let buf = [0xff, 10, 0, 0, 1, 0x1a, 0xe1, 0xff];
+        let addr = SocketAddrV4::from_compact(buf[1..7].try_into().unwrap());
+        assert_eq!(addr, SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 6881));
+
+        let mut buf = [0; 20];
+        buf[2..18].copy_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        buf[18..].copy_from_slice(&6881u16.to_be_bytes());
+        let addr = SocketAddrV6::from_compact(buf[2..].try_into().unwrap());
+        assert_eq!(addr, SocketAddrV6::new(Ipv6Addr::LOCALHOST, 6881, 0, 0));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_from_compact_address() {
+        let bytes = [1, 2, 3, 4, 0x1a, 0xe1];
+        assert_eq!(
+            SocketAddrV4::from_compact_address(&bytes),
+            SocketAddrV4::from_compact(bytes)
+        );
+
+        let mut bytes = [0; 18];
+        bytes[15] = 1;
+        bytes[16..].copy_from_slice(&[0x1a, 0xe1]);
+        assert_eq!(
+            SocketAddrV6::from_compact_address(&bytes),
+            SocketAddrV6::from_compact(bytes)
+        );
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            "1.2.3.4:6881".parse::<CompactAddr>(),
+            Ok(CompactAddr::V4(CompactAddrV4::new([
+                1, 2, 3, 4, 0x1a, 0xe1
+            ])))
+        );
+        let v6 = "[::1]:6881".parse::<CompactAddrV6>().unwrap();
+        assert_eq!(SocketAddrV6::from(v6).ip(), &Ipv6Addr::LOCALHOST);
+        assert_eq!("[::1%0]:6881".parse::<CompactAddrV6>(), Ok(v6));
+        assert_eq!(
+            "[::1]:6881".parse::<CompactAddrV4>().unwrap_err().kind(),
+            ParseAddrErrorKind::InvalidAddr
+        );
+    }
+
+    #[test]
+    fn from_str_rejects_scope() {
+        let err = "[fe80::1%3]:6881".parse::<CompactAddr>().unwrap_err();
+        assert_eq!(err.kind(), ParseAddrErrorKind::Scoped);
+        assert_eq!(
+            err.to_string(),
+            "compact address cannot represent the scope ID of the zone identifier"
+        );
+    }
 }
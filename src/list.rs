@@ -0,0 +1,421 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Lists of IPv4 and IPv6 compact addresses.
+
+use crate::{
+    blob::{self, DecodeError, V4_LEN, V6_LEN},
+    CompactAddr, CompactAddrV4, CompactAddrV6,
+};
+use std::{collections::HashSet, convert::TryFrom, iter::FromIterator, ops::Index, slice, vec};
+
+/// An ordered list of IPv4 and IPv6 compact addresses.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CompactAddrList {
+    addrs: Vec<CompactAddr>,
+}
+
+impl CompactAddrList {
+    /// Instantiates an empty list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Instantiates an empty list with capacity for the number of addresses.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            addrs: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Instantiates from blobs of compact IPv4 and IPv6 addresses.
+    ///
+    /// The IPv4 addresses are followed by the IPv6 addresses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either blob length is not a multiple of its compact
+    /// address length.
+    pub fn from_blobs(v4: &[u8], v6: &[u8]) -> Result<Self, DecodeError> {
+        if !v4.len().is_multiple_of(V4_LEN) {
+            return Err(DecodeError::invalid_length(v4.len(), V4_LEN));
+        }
+        if !v6.len().is_multiple_of(V6_LEN) {
+            return Err(DecodeError::invalid_length(v6.len(), V6_LEN));
+        }
+
+        let mut addrs = Vec::with_capacity(v4.len() / V4_LEN + v6.len() / V6_LEN);
+        addrs.extend(v4.chunks_exact(V4_LEN).map(|chunk| {
+            CompactAddr::V4(CompactAddrV4::try_from(chunk).expect("chunk is a compact address"))
+        }));
+        addrs.extend(v6.chunks_exact(V6_LEN).map(|chunk| {
+            CompactAddr::V6(CompactAddrV6::try_from(chunk).expect("chunk is a compact address"))
+        }));
+        Ok(Self { addrs })
+    }
+
+    /// Returns blobs of the compact IPv4 and IPv6 addresses.
+    ///
+    /// The order of the addresses within each family is preserved.
+    pub fn to_blobs(&self) -> (Vec<u8>, Vec<u8>) {
+        blob::partition_encode(self.addrs.iter().copied())
+    }
+
+    /// Returns the addresses.
+    pub fn as_slice(&self) -> &[CompactAddr] {
+        &self.addrs
+    }
+
+    /// Returns the number of addresses.
+    pub fn len(&self) -> usize {
+        self.addrs.len()
+    }
+
+    /// Returns true if there are no addresses.
+    pub fn is_empty(&self) -> bool {
+        self.addrs.is_empty()
+    }
+
+    /// Returns the address at the index.
+    pub fn get(&self, idx: usize) -> Option<CompactAddr> {
+        self.addrs.get(idx).copied()
+    }
+
+    /// Returns an iterator over the addresses.
+    pub fn iter(&self) -> slice::Iter<'_, CompactAddr> {
+        self.addrs.iter()
+    }
+
+    /// Appends an address.
+    pub fn push<A: Into<CompactAddr>>(&mut self, addr: A) {
+        self.addrs.push(addr.into());
+    }
+
+    /// Removes all addresses.
+    pub fn clear(&mut self) {
+        self.addrs.clear();
+    }
+}
+
+impl From<Vec<CompactAddr>> for CompactAddrList {
+    fn from(addrs: Vec<CompactAddr>) -> Self {
+        Self { addrs }
+    }
+}
+
+impl From<CompactAddrList> for Vec<CompactAddr> {
+    fn from(list: CompactAddrList) -> Self {
+        list.addrs
+    }
+}
+
+impl AsRef<[CompactAddr]> for CompactAddrList {
+    fn as_ref(&self) -> &[CompactAddr] {
+        &self.addrs
+    }
+}
+
+impl Index<usize> for CompactAddrList {
+    type Output = CompactAddr;
+
+    fn index(&self, idx: usize) -> &Self::Output {
+        &self.addrs[idx]
+    }
+}
+
+impl<'a> IntoIterator for &'a CompactAddrList {
+    type Item = &'a CompactAddr;
+    type IntoIter = slice::Iter<'a, CompactAddr>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.addrs.iter()
+    }
+}
+
+impl IntoIterator for CompactAddrList {
+    type Item = CompactAddr;
+    type IntoIter = vec::IntoIter<CompactAddr>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.addrs.into_iter()
+    }
+}
+
+impl<A: Into<CompactAddr>> FromIterator<A> for CompactAddrList {
+    fn from_iter<I: IntoIterator<Item = A>>(iter: I) -> Self {
+        Self {
+            addrs: iter.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl<A: Into<CompactAddr>> Extend<A> for CompactAddrList {
+    fn extend<I: IntoIterator<Item = A>>(&mut self, iter: I) {
+        self.addrs.extend(iter.into_iter().map(Into::into));
+    }
+}
+
+/// The address families kept by a [`CompactAddrListBuilder`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FamilyFilter {
+    /// Both IPv4 and IPv6 addresses are kept.
+    #[default]
+    Any,
+    /// Only IPv4 addresses are kept.
+    V4,
+    /// Only IPv6 addresses are kept.
+    V6,
+}
+
+impl FamilyFilter {
+    fn matches(self, addr: &CompactAddr) -> bool {
+        match self {
+            FamilyFilter::Any => true,
+            FamilyFilter::V4 => addr.is_ipv4(),
+            FamilyFilter::V6 => addr.is_ipv6(),
+        }
+    }
+}
+
+/// Accumulates addresses to finalize into a [`CompactAddrList`] or blobs.
+///
+/// When built, addresses are filtered by family, then sorted, then
+/// deduplicated, and then limited to the maximum number of entries.
+#[derive(Clone, Debug, Default)]
+pub struct CompactAddrListBuilder {
+    addrs: Vec<CompactAddr>,
+    sort: bool,
+    dedup: bool,
+    family: FamilyFilter,
+    max_len: Option<usize>,
+}
+
+impl CompactAddrListBuilder {
+    /// Instantiates an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an address.
+    pub fn push<A: Into<CompactAddr>>(&mut self, addr: A) -> &mut Self {
+        self.addrs.push(addr.into());
+        self
+    }
+
+    /// Adds the addresses.
+    pub fn extend<I>(&mut self, addrs: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: Into<CompactAddr>,
+    {
+        self.addrs.extend(addrs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets whether addresses are sorted.
+    ///
+    /// Otherwise, addresses are kept in the order they were added.
+    pub fn sort(&mut self, sort: bool) -> &mut Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Sets whether duplicate addresses are removed.
+    ///
+    /// The first occurrence of an address is kept.
+    pub fn dedup(&mut self, dedup: bool) -> &mut Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Sets the address families which are kept.
+    pub fn family(&mut self, family: FamilyFilter) -> &mut Self {
+        self.family = family;
+        self
+    }
+
+    /// Sets the maximum number of addresses which are kept.
+    pub fn max_len(&mut self, max_len: Option<usize>) -> &mut Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Finalizes the accumulated addresses into a list.
+    pub fn build(self) -> CompactAddrList {
+        let family = self.family;
+        let mut addrs = self.addrs;
+        addrs.retain(|addr| family.matches(addr));
+
+        if self.sort {
+            addrs.sort();
+            if self.dedup {
+                addrs.dedup();
+            }
+        } else if self.dedup {
+            let mut seen = HashSet::with_capacity(addrs.len());
+            addrs.retain(|addr| seen.insert(*addr));
+        }
+
+        if let Some(max_len) = self.max_len {
+            addrs.truncate(max_len);
+        }
+        CompactAddrList { addrs }
+    }
+
+    /// Finalizes the accumulated addresses into blobs of the compact IPv4 and
+    /// IPv6 addresses.
+    pub fn build_blobs(self) -> (Vec<u8>, Vec<u8>) {
+        self.build().to_blobs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{compact_v4, compact_v6};
+
+    #[test]
+    fn blobs_round_trip() {
+        let list: CompactAddrList = vec![compact_v6(1), compact_v4(1), compact_v4(2)].into();
+        let (b4, b6) = list.to_blobs();
+        assert_eq!(b4.len(), 2 * V4_LEN);
+        assert_eq!(b6.len(), V6_LEN);
+        assert_eq!(
+            CompactAddrList::from_blobs(&b4, &b6).unwrap().as_slice(),
+            [compact_v4(1), compact_v4(2), compact_v6(1)]
+        );
+    }
+
+    #[test]
+    fn from_blobs_invalid_length() {
+        assert_eq!(
+            CompactAddrList::from_blobs(&[0; 5], &[]),
+            Err(DecodeError::invalid_length(5, V4_LEN))
+        );
+        assert_eq!(
+            CompactAddrList::from_blobs(&[], &[0; 19]),
+            Err(DecodeError::invalid_length(19, V6_LEN))
+        );
+    }
+
+    #[test]
+    fn list_accessors() {
+        let mut list = CompactAddrList::with_capacity(2);
+        assert!(list.is_empty());
+        list.push(compact_v4(1));
+        list.push(compact_v6(1));
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.get(1), Some(compact_v6(1)));
+        assert_eq!(list.get(2), None);
+        assert_eq!(list.iter().count(), 2);
+        list.clear();
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn builder_defaults_keep_order() {
+        let mut builder = CompactAddrListBuilder::new();
+        builder
+            .push(compact_v4(2))
+            .push(compact_v6(1))
+            .extend(vec![compact_v4(2), compact_v4(1)]);
+        assert_eq!(
+            builder.build().as_slice(),
+            [compact_v4(2), compact_v6(1), compact_v4(2), compact_v4(1)]
+        );
+    }
+
+    #[test]
+    fn builder_sort_dedup() {
+        let addrs = vec![
+            compact_v6(1),
+            compact_v4(2),
+            compact_v4(1),
+            compact_v4(2),
+            compact_v6(1),
+        ];
+
+        let mut builder = CompactAddrListBuilder::new();
+        builder.extend(addrs.clone()).sort(true).dedup(true);
+        assert_eq!(
+            builder.build().as_slice(),
+            [compact_v4(1), compact_v4(2), compact_v6(1)]
+        );
+
+        let mut builder = CompactAddrListBuilder::new();
+        builder.extend(addrs.clone()).dedup(true);
+        assert_eq!(
+            builder.build().as_slice(),
+            [compact_v6(1), compact_v4(2), compact_v4(1)]
+        );
+
+        let mut builder = CompactAddrListBuilder::new();
+        builder.extend(addrs).sort(true);
+        assert_eq!(builder.build().len(), 5);
+    }
+
+    #[test]
+    fn builder_family_and_limit() {
+        let addrs = vec![
+            compact_v6(1),
+            compact_v4(3),
+            compact_v4(1),
+            compact_v6(2),
+            compact_v4(2),
+        ];
+
+        let mut builder = CompactAddrListBuilder::new();
+        builder.extend(addrs.clone()).family(FamilyFilter::V4);
+        assert_eq!(
+            builder.build().as_slice(),
+            [compact_v4(3), compact_v4(1), compact_v4(2)]
+        );
+
+        let mut builder = CompactAddrListBuilder::new();
+        builder
+            .extend(addrs.clone())
+            .family(FamilyFilter::V6)
+            .max_len(Some(1));
+        assert_eq!(builder.build().as_slice(), [compact_v6(1)]);
+
+        let mut builder = CompactAddrListBuilder::new();
+        builder.extend(addrs).sort(true).max_len(Some(2));
+        let (b4, b6) = builder.build_blobs();
+        assert_eq!(
+            b4,
+            [compact_v4(1).as_bytes(), compact_v4(2).as_bytes()].concat()
+        );
+        assert!(b6.is_empty());
+    }
+
+    #[test]
+    fn list_from_iter_and_extend() {
+        let mut list: CompactAddrList = vec![compact_v4(1), compact_v6(1)].into_iter().collect();
+        list.extend(vec![CompactAddrV4::from([10, 0, 0, 2, 0x1a, 0xe1])]);
+        assert_eq!(
+            list.as_slice(),
+            [compact_v4(1), compact_v6(1), compact_v4(2)]
+        );
+
+        let list: CompactAddrList = vec!["10.0.0.1:6881".parse::<std::net::SocketAddr>().unwrap()]
+            .into_iter()
+            .collect();
+        assert_eq!(list.as_slice(), [compact_v4(1)]);
+    }
+
+    #[test]
+    fn list_index_and_into_iter() {
+        let list = CompactAddrList::from(vec![compact_v4(1), compact_v6(1)]);
+        assert_eq!(list[1], compact_v6(1));
+        assert_eq!((&list).into_iter().count(), 2);
+        assert_eq!(
+            list.into_iter().collect::<Vec<_>>(),
+            vec![compact_v4(1), compact_v6(1)]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Extension protocol (BEP 10) handshake IP fields.
+//!
+//! The handshake dictionary may contain:
+//!
+//! | Key      | Value                                             |
+//! |----------|---------------------------------------------------|
+//! | `yourip` | The receiver's IP address, 4 or 16 bytes           |
+//! | `ipv4`   | The sender's IPv4 address, 4 bytes                 |
+//! | `ipv6`   | The sender's IPv6 address, 16 bytes                |
+//!
+//! The values are IP addresses in network byte order without a port, unlike
+//! the 6 and 18 byte compact socket addresses used for peers.
+
+use std::{
+    error, fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+/// Returns the `yourip` value for the IP address.
+pub fn encode_yourip(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => encode_ipv4(ip).to_vec(),
+        IpAddr::V6(ip) => encode_ipv6(ip).to_vec(),
+    }
+}
+
+/// Decodes a `yourip` value.
+///
+/// The address family is determined by the length of the value.
+///
+/// # Errors
+///
+/// Returns an error if the value is neither 4 nor 16 bytes.
+pub fn decode_yourip(value: &[u8]) -> Result<IpAddr, IpLengthError> {
+    match value.len() {
+        4 => decode_ipv4(value).map(IpAddr::V4),
+        16 => decode_ipv6(value).map(IpAddr::V6),
+        len => Err(IpLengthError { len }),
+    }
+}
+
+/// Returns the `ipv4` value for the IP address.
+pub fn encode_ipv4(ip: Ipv4Addr) -> [u8; 4] {
+    ip.octets()
+}
+
+/// Decodes an `ipv4` value.
+///
+/// # Errors
+///
+/// Returns an error if the value is not 4 bytes.
+pub fn decode_ipv4(value: &[u8]) -> Result<Ipv4Addr, IpLengthError> {
+    let mut ip: [u8; 4] = [0; 4];
+    if value.len() != ip.len() {
+        return Err(IpLengthError { len: value.len() });
+    }
+    ip.copy_from_slice(value);
+    Ok(Ipv4Addr::from(ip))
+}
+
+/// Returns the `ipv6` value for the IP address.
+pub fn encode_ipv6(ip: Ipv6Addr) -> [u8; 16] {
+    ip.octets()
+}
+
+/// Decodes an `ipv6` value.
+///
+/// # Errors
+///
+/// Returns an error if the value is not 16 bytes.
+pub fn decode_ipv6(value: &[u8]) -> Result<Ipv6Addr, IpLengthError> {
+    let mut ip: [u8; 16] = [0; 16];
+    if value.len() != ip.len() {
+        return Err(IpLengthError { len: value.len() });
+    }
+    ip.copy_from_slice(value);
+    Ok(Ipv6Addr::from(ip))
+}
+
+/// An error when decoding a compact IP address with an invalid length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpLengthError {
+    len: usize,
+}
+
+impl IpLengthError {
+    /// Returns the length of the value.
+    pub fn actual_len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the length is that of a compact socket address, which
+    /// includes a port.
+    pub fn is_socket_addr_len(&self) -> bool {
+        self.len == 6 || self.len == 18
+    }
+}
+
+impl fmt::Display for IpLengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid compact IP address length: {}", self.len)?;
+        if self.is_socket_addr_len() {
+            f.write_str(" (a compact socket address with a port)")?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for IpLengthError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yourip_round_trip() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        assert_eq!(encode_yourip(v4), [192, 0, 2, 1]);
+        assert_eq!(decode_yourip(&encode_yourip(v4)), Ok(v4));
+
+        let v6 = IpAddr::V6("2001:db8::1".parse().unwrap());
+        assert_eq!(encode_yourip(v6).len(), 16);
+        assert_eq!(decode_yourip(&encode_yourip(v6)), Ok(v6));
+    }
+
+    #[test]
+    fn ipv4_and_ipv6_fields() {
+        let ip = Ipv4Addr::new(10, 0, 0, 1);
+        assert_eq!(decode_ipv4(&encode_ipv4(ip)), Ok(ip));
+        assert_eq!(decode_ipv4(&[0; 16]).map_err(|e| e.actual_len()), Err(16));
+
+        let ip = Ipv6Addr::LOCALHOST;
+        assert_eq!(decode_ipv6(&encode_ipv6(ip)), Ok(ip));
+        assert_eq!(decode_ipv6(&[0; 4]).map_err(|e| e.actual_len()), Err(4));
+    }
+
+    #[test]
+    fn invalid_lengths() {
+        for len in [0, 5, 17] {
+            let err = decode_yourip(&vec![0; len]).unwrap_err();
+            assert_eq!(err.actual_len(), len);
+            assert!(!err.is_socket_addr_len());
+            assert_eq!(
+                err.to_string(),
+                format!("invalid compact IP address length: {}", len)
+            );
+        }
+
+        let err = decode_yourip(&[0; 6]).unwrap_err();
+        assert!(err.is_socket_addr_len());
+        assert_eq!(
+            err.to_string(),
+            "invalid compact IP address length: 6 (a compact socket address with a port)"
+        );
+        assert!(decode_yourip(&[0; 18]).unwrap_err().is_socket_addr_len());
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Memory-mapped blob files.
+//!
+//! A [`MappedBlob`] maps a file of concatenated compact addresses so that
+//! addresses are decoded on access, without reading the file into memory.
+
+use crate::blob::{self, IterV4, IterV6};
+use memmap2::Mmap;
+use std::{
+    fs::File,
+    io,
+    net::{SocketAddrV4, SocketAddrV6},
+    path::Path,
+};
+
+/// A memory-mapped file of concatenated compact addresses.
+#[derive(Debug)]
+pub struct MappedBlob {
+    mmap: Mmap,
+}
+
+impl MappedBlob {
+    /// Maps the file at the path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or mapped.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is mapped. See
+    /// [`Mmap::map`].
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mmap = Mmap::map(&file)?;
+        Ok(Self { mmap })
+    }
+
+    /// Returns the mapped bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// Returns the number of whole compact IPv4 addresses.
+    pub fn len_v4(&self) -> usize {
+        blob::len_v4(&self.mmap)
+    }
+
+    /// Returns the number of whole compact IPv6 addresses.
+    pub fn len_v6(&self) -> usize {
+        blob::len_v6(&self.mmap)
+    }
+
+    /// Returns the compact IPv4 address at the index.
+    pub fn get_v4(&self, idx: usize) -> Option<SocketAddrV4> {
+        blob::get_v4(&self.mmap, idx)
+    }
+
+    /// Returns the compact IPv6 address at the index.
+    pub fn get_v6(&self, idx: usize) -> Option<SocketAddrV6> {
+        blob::get_v6(&self.mmap, idx)
+    }
+
+    /// Returns an iterator which decodes the compact IPv4 addresses.
+    pub fn iter_v4(&self) -> IterV4<'_> {
+        blob::iter_v4(&self.mmap)
+    }
+
+    /// Returns an iterator which decodes the compact IPv6 addresses.
+    pub fn iter_v6(&self) -> IterV6<'_> {
+        blob::iter_v6(&self.mmap)
+    }
+}
+
+impl From<Mmap> for MappedBlob {
+    fn from(mmap: Mmap) -> Self {
+        Self { mmap }
+    }
+}
+
+impl AsRef<[u8]> for MappedBlob {
+    fn as_ref(&self) -> &[u8] {
+        &self.mmap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+    use std::{fs, net::Ipv4Addr};
+
+    #[test]
+    fn maps_blob_file() {
+        let path = temp_path("mmap");
+        let mut bytes = vec![1, 2, 3, 4, 0, 80, 5, 6, 7, 8, 0x1a, 0xe1];
+        bytes.extend_from_slice(&[9, 9]);
+        fs::write(&path, &bytes).unwrap();
+
+        // SAFETY: The file is not modified while it is mapped.
+        let blob = unsafe { MappedBlob::open(&path) }.unwrap();
+        assert_eq!(blob.as_bytes(), &bytes[..]);
+        assert_eq!(blob.len_v4(), 2);
+        assert_eq!(blob.len_v6(), 0);
+        assert_eq!(
+            blob.get_v4(1),
+            Some(SocketAddrV4::new(Ipv4Addr::new(5, 6, 7, 8), 6881))
+        );
+        assert_eq!(blob.get_v4(2), None);
+        assert_eq!(blob.iter_v4().count(), 2);
+        assert_eq!(blob.iter_v4().remainder(), &[9, 9]);
+        assert_eq!(blob.iter_v6().count(), 0);
+
+        drop(blob);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn open_missing_file() {
+        let path = temp_path("mmap-missing");
+        // SAFETY: The file does not exist.
+        assert!(unsafe { MappedBlob::open(path) }.is_err());
+    }
+}
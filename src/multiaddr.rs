@@ -0,0 +1,224 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversions between compact addresses and [multiaddrs](::multiaddr).
+//!
+//! | Transport | Multiaddr                  |
+//! |-----------|----------------------------|
+//! | TCP       | `/ip4/<ip>/tcp/<port>`     |
+//! | UDP       | `/ip4/<ip>/udp/<port>`     |
+//! | uTP       | `/ip4/<ip>/udp/<port>/utp` |
+//!
+//! IPv6 addresses use `/ip6/<ip>` instead. Compact addresses without a
+//! transport are converted as TCP.
+
+use crate::{
+    endpoint::{CompactEndpoint, Transport},
+    CompactAddr, CompactAddrV4, CompactAddrV6,
+};
+use ::multiaddr::{Multiaddr, Protocol};
+use std::{
+    convert::TryFrom,
+    error, fmt,
+    net::{IpAddr, SocketAddr},
+};
+
+impl From<CompactEndpoint> for Multiaddr {
+    fn from(endpoint: CompactEndpoint) -> Self {
+        let ip = match endpoint.ip() {
+            IpAddr::V4(ip) => Protocol::Ip4(ip),
+            IpAddr::V6(ip) => Protocol::Ip6(ip),
+        };
+        let port = endpoint.port();
+        let addr = Multiaddr::with_capacity(24).with(ip);
+        match endpoint.transport() {
+            Transport::Tcp => addr.with(Protocol::Tcp(port)),
+            Transport::Udp => addr.with(Protocol::Udp(port)),
+            Transport::Utp => addr.with(Protocol::Udp(port)).with(Protocol::Utp),
+        }
+    }
+}
+
+impl From<CompactAddr> for Multiaddr {
+    fn from(addr: CompactAddr) -> Self {
+        Multiaddr::from(CompactEndpoint::new(addr, Transport::Tcp))
+    }
+}
+
+impl From<CompactAddrV4> for Multiaddr {
+    fn from(addr: CompactAddrV4) -> Self {
+        Multiaddr::from(CompactAddr::V4(addr))
+    }
+}
+
+impl From<CompactAddrV6> for Multiaddr {
+    fn from(addr: CompactAddrV6) -> Self {
+        Multiaddr::from(CompactAddr::V6(addr))
+    }
+}
+
+impl TryFrom<&Multiaddr> for CompactEndpoint {
+    type Error = MultiaddrError;
+
+    fn try_from(addr: &Multiaddr) -> Result<Self, Self::Error> {
+        let mut iter = addr.iter();
+        let ip = match iter.next() {
+            Some(Protocol::Ip4(ip)) => IpAddr::V4(ip),
+            Some(Protocol::Ip6(ip)) => IpAddr::V6(ip),
+            Some(p) => return Err(MultiaddrError::UnsupportedProtocol(p.tag())),
+            None => return Err(MultiaddrError::MissingIp),
+        };
+        let (port, mut transport) = match iter.next() {
+            Some(Protocol::Tcp(port)) => (port, Transport::Tcp),
+            Some(Protocol::Udp(port)) => (port, Transport::Udp),
+            Some(p) => return Err(MultiaddrError::UnsupportedProtocol(p.tag())),
+            None => return Err(MultiaddrError::MissingPort),
+        };
+        match iter.next() {
+            Some(Protocol::Utp) if transport == Transport::Udp => transport = Transport::Utp,
+            Some(p) => return Err(MultiaddrError::UnsupportedProtocol(p.tag())),
+            None => {}
+        }
+        if let Some(p) = iter.next() {
+            return Err(MultiaddrError::UnsupportedProtocol(p.tag()));
+        }
+
+        Ok(CompactEndpoint::new(SocketAddr::new(ip, port), transport))
+    }
+}
+
+impl TryFrom<&Multiaddr> for CompactAddr {
+    type Error = MultiaddrError;
+
+    fn try_from(addr: &Multiaddr) -> Result<Self, Self::Error> {
+        CompactEndpoint::try_from(addr).map(|endpoint| endpoint.addr())
+    }
+}
+
+impl TryFrom<&Multiaddr> for CompactAddrV4 {
+    type Error = MultiaddrError;
+
+    fn try_from(addr: &Multiaddr) -> Result<Self, Self::Error> {
+        match CompactAddr::try_from(addr)? {
+            CompactAddr::V4(addr) => Ok(addr),
+            CompactAddr::V6(_) => Err(MultiaddrError::UnsupportedProtocol("ip6")),
+        }
+    }
+}
+
+impl TryFrom<&Multiaddr> for CompactAddrV6 {
+    type Error = MultiaddrError;
+
+    fn try_from(addr: &Multiaddr) -> Result<Self, Self::Error> {
+        match CompactAddr::try_from(addr)? {
+            CompactAddr::V4(_) => Err(MultiaddrError::UnsupportedProtocol("ip4")),
+            CompactAddr::V6(addr) => Ok(addr),
+        }
+    }
+}
+
+/// An error when converting a multiaddr to a compact address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MultiaddrError {
+    /// The multiaddr has a protocol which cannot be represented.
+    UnsupportedProtocol(&'static str),
+    /// The multiaddr does not have an IP address.
+    MissingIp,
+    /// The multiaddr does not have a TCP or UDP port.
+    MissingPort,
+}
+
+impl fmt::Display for MultiaddrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MultiaddrError::UnsupportedProtocol(tag) => {
+                write!(f, "unsupported multiaddr protocol: {}", tag)
+            }
+            MultiaddrError::MissingIp => write!(f, "multiaddr is missing an IP address"),
+            MultiaddrError::MissingPort => write!(f, "multiaddr is missing a port"),
+        }
+    }
+}
+
+impl error::Error for MultiaddrError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::addr;
+
+    fn multiaddr(s: &str) -> Multiaddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn to_multiaddr() {
+        assert_eq!(
+            Multiaddr::from(addr("1.2.3.4:6881")),
+            multiaddr("/ip4/1.2.3.4/tcp/6881")
+        );
+        assert_eq!(
+            Multiaddr::from(CompactEndpoint::new(addr("[::1]:80"), Transport::Udp)),
+            multiaddr("/ip6/::1/udp/80")
+        );
+        assert_eq!(
+            Multiaddr::from(CompactEndpoint::new(addr("1.2.3.4:80"), Transport::Utp)),
+            multiaddr("/ip4/1.2.3.4/udp/80/utp")
+        );
+    }
+
+    #[test]
+    fn from_multiaddr() {
+        for (s, transport) in [
+            ("/ip4/1.2.3.4/tcp/6881", Transport::Tcp),
+            ("/ip6/::1/udp/6881", Transport::Udp),
+            ("/ip4/1.2.3.4/udp/6881/utp", Transport::Utp),
+        ] {
+            let endpoint = CompactEndpoint::try_from(&multiaddr(s)).unwrap();
+            assert_eq!(endpoint.transport(), transport);
+            assert_eq!(endpoint.port(), 6881);
+            assert_eq!(Multiaddr::from(endpoint), multiaddr(s));
+        }
+        assert_eq!(
+            CompactAddrV4::try_from(&multiaddr("/ip4/1.2.3.4/tcp/80")),
+            Ok(CompactAddrV4::from([1, 2, 3, 4, 0, 80]))
+        );
+    }
+
+    #[test]
+    fn from_multiaddr_errors() {
+        assert_eq!(
+            CompactAddr::try_from(&Multiaddr::empty()),
+            Err(MultiaddrError::MissingIp)
+        );
+        assert_eq!(
+            CompactAddr::try_from(&multiaddr("/ip4/1.2.3.4")),
+            Err(MultiaddrError::MissingPort)
+        );
+        assert_eq!(
+            CompactAddr::try_from(&multiaddr("/dns/example.com/tcp/80")),
+            Err(MultiaddrError::UnsupportedProtocol("dns"))
+        );
+        assert_eq!(
+            CompactAddr::try_from(&multiaddr("/ip4/1.2.3.4/tcp/80/utp")),
+            Err(MultiaddrError::UnsupportedProtocol("utp"))
+        );
+        assert_eq!(
+            CompactAddr::try_from(&multiaddr("/ip4/1.2.3.4/udp/80/utp/utp")),
+            Err(MultiaddrError::UnsupportedProtocol("utp"))
+        );
+        assert_eq!(
+            CompactAddrV6::try_from(&multiaddr("/ip4/1.2.3.4/tcp/80")),
+            Err(MultiaddrError::UnsupportedProtocol("ip4"))
+        );
+        assert_eq!(
+            CompactAddrV4::try_from(&multiaddr("/ip6/::1/tcp/80")),
+            Err(MultiaddrError::UnsupportedProtocol("ip6"))
+        );
+    }
+}
@@ -0,0 +1,467 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! DHT compact node info.
+//!
+//! Compact node info (BEP 5) is a 20 byte node ID followed by the compact
+//! address of the node: 26 bytes for IPv4 and 38 bytes for IPv6 (BEP 32).
+//! KRPC `find_node` and `get_peers` responses contain concatenated compact
+//! node info in the `nodes` (IPv4) and `nodes6` (IPv6) fields.
+//!
+//! Like blobs of compact addresses, fields can be decoded strictly, which
+//! rejects trailing bytes, or iterated, which reports the trailing bytes that
+//! do not form whole node info.
+
+use crate::{
+    blob::{DecodeError, V4_LEN, V6_LEN},
+    CompactAddr, CompactAddrV4, CompactAddrV6,
+};
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    slice::ChunksExact,
+};
+
+/// The length of a node ID.
+pub const ID_LEN: usize = 20;
+
+/// The length of compact IPv4 node info.
+pub const NODE_V4_LEN: usize = ID_LEN + V4_LEN;
+
+/// The length of compact IPv6 node info.
+pub const NODE_V6_LEN: usize = ID_LEN + V6_LEN;
+
+/// The default number of nodes in a response.
+pub const K: usize = 8;
+
+/// A DHT node's ID and compact address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeInfo {
+    id: [u8; ID_LEN],
+    addr: CompactAddr,
+}
+
+impl NodeInfo {
+    /// Instantiates new node info.
+    pub fn new<A: Into<CompactAddr>>(id: [u8; ID_LEN], addr: A) -> Self {
+        Self {
+            id,
+            addr: addr.into(),
+        }
+    }
+
+    /// Returns the node ID.
+    pub fn id(&self) -> &[u8; ID_LEN] {
+        &self.id
+    }
+
+    /// Returns the compact address.
+    pub fn addr(&self) -> CompactAddr {
+        self.addr
+    }
+
+    /// Returns the XOR distance between the node ID and the target.
+    ///
+    /// Distances compare as big-endian integers, so a smaller array is a
+    /// closer node.
+    pub fn distance(&self, target: &[u8; ID_LEN]) -> [u8; ID_LEN] {
+        let mut distance: [u8; ID_LEN] = [0; ID_LEN];
+        for (d, (a, b)) in distance.iter_mut().zip(self.id.iter().zip(target)) {
+            *d = a ^ b;
+        }
+        distance
+    }
+
+    /// Returns the length of the compact node info.
+    pub fn encoded_len(&self) -> usize {
+        ID_LEN + self.addr.as_ref().len()
+    }
+
+    /// Appends the compact node info to the buffer.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.id);
+        buf.extend_from_slice(self.addr.as_ref());
+    }
+
+    /// Returns the compact node info.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.encode(&mut buf);
+        buf
+    }
+}
+
+impl fmt::Display for NodeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in &self.id {
+            write!(f, "{:02x}", b)?;
+        }
+        write!(f, "@{}", self.addr)
+    }
+}
+
+/// Sorts the nodes from closest to farthest from the target by XOR distance.
+///
+/// Nodes at the same distance keep their relative order.
+pub fn ordered_by_distance(nodes: &mut [NodeInfo], target: &[u8; ID_LEN]) {
+    nodes.sort_by_cached_key(|node| node.distance(target));
+}
+
+/// Decodes a `nodes` field of compact IPv4 node info.
+///
+/// # Errors
+///
+/// Returns an error if the field length is not a multiple of the compact node
+/// info length.
+pub fn decode_v4(nodes: &[u8]) -> Result<Vec<NodeInfo>, DecodeError> {
+    check_len(nodes, NODE_V4_LEN)?;
+    Ok(iter_v4(nodes).collect())
+}
+
+/// Decodes a `nodes6` field of compact IPv6 node info.
+///
+/// # Errors
+///
+/// Returns an error if the field length is not a multiple of the compact node
+/// info length.
+pub fn decode_v6(nodes: &[u8]) -> Result<Vec<NodeInfo>, DecodeError> {
+    check_len(nodes, NODE_V6_LEN)?;
+    Ok(iter_v6(nodes).collect())
+}
+
+/// Returns an iterator which decodes the compact IPv4 node info in a `nodes`
+/// field.
+///
+/// Trailing bytes which do not form whole node info are not yielded.
+pub fn iter_v4(nodes: &[u8]) -> NodesIter<'_> {
+    NodesIter {
+        chunks: nodes.chunks_exact(NODE_V4_LEN),
+    }
+}
+
+/// Returns an iterator which decodes the compact IPv6 node info in a
+/// `nodes6` field.
+///
+/// Trailing bytes which do not form whole node info are not yielded.
+pub fn iter_v6(nodes: &[u8]) -> NodesIter<'_> {
+    NodesIter {
+        chunks: nodes.chunks_exact(NODE_V6_LEN),
+    }
+}
+
+/// An iterator over the compact node info in a `nodes` or `nodes6` field.
+#[derive(Clone, Debug)]
+pub struct NodesIter<'a> {
+    chunks: ChunksExact<'a, u8>,
+}
+
+impl<'a> NodesIter<'a> {
+    /// Returns the trailing bytes which do not form whole node info.
+    pub fn remainder(&self) -> &'a [u8] {
+        self.chunks.remainder()
+    }
+}
+
+impl<'a> Iterator for NodesIter<'a> {
+    type Item = NodeInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chunks.next().map(|chunk| {
+            let (id_bytes, addr) = chunk.split_at(ID_LEN);
+            let mut id: [u8; ID_LEN] = [0; ID_LEN];
+            id.copy_from_slice(id_bytes);
+            let addr = if addr.len() == V4_LEN {
+                let mut a: [u8; V4_LEN] = [0; V4_LEN];
+                a.copy_from_slice(addr);
+                CompactAddr::V4(CompactAddrV4::new(a))
+            } else {
+                let mut a: [u8; V6_LEN] = [0; V6_LEN];
+                a.copy_from_slice(addr);
+                CompactAddr::V6(CompactAddrV6::new(a))
+            };
+            NodeInfo { id, addr }
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for NodesIter<'a> {}
+
+/// The address families of nodes requested by a BEP 32 `want` list.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Want {
+    /// If IPv4 nodes (`n4`) are requested.
+    pub n4: bool,
+    /// If IPv6 nodes (`n6`) are requested.
+    pub n6: bool,
+}
+
+impl Want {
+    /// Returns the requested families from the values of a `want` list.
+    ///
+    /// Unknown values are ignored.
+    pub fn from_values<'a, I>(values: I) -> Self
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let mut want = Want::default();
+        for value in values {
+            match value {
+                b"n4" => want.n4 = true,
+                b"n6" => want.n6 = true,
+                _ => {}
+            }
+        }
+        want
+    }
+
+    /// Returns the family of the querying node's address, which is requested
+    /// when a query has no `want` list.
+    pub fn from_querier(addr: SocketAddr) -> Self {
+        match addr.ip() {
+            IpAddr::V4(_) => Want {
+                n4: true,
+                n6: false,
+            },
+            IpAddr::V6(_) => Want {
+                n4: false,
+                n6: true,
+            },
+        }
+    }
+}
+
+/// The `nodes` and `nodes6` fields of a KRPC response.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Nodes {
+    /// The `nodes` field, if IPv4 nodes are requested.
+    pub nodes: Option<Vec<u8>>,
+    /// The `nodes6` field, if IPv6 nodes are requested.
+    pub nodes6: Option<Vec<u8>>,
+}
+
+/// Builds the `nodes` and `nodes6` fields of KRPC responses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodesBuilder {
+    k: usize,
+}
+
+impl Default for NodesBuilder {
+    fn default() -> Self {
+        Self { k: K }
+    }
+}
+
+impl NodesBuilder {
+    /// Instantiates a builder with the default number of nodes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of nodes of each family.
+    pub fn k(&mut self, k: usize) -> &mut Self {
+        self.k = k;
+        self
+    }
+
+    /// Returns the fields with the requested families' nodes closest to the
+    /// target.
+    ///
+    /// Each field has at most K nodes ordered from closest to farthest by XOR
+    /// distance. A field which is not requested is `None`.
+    pub fn build(&self, target: &[u8; ID_LEN], candidates: &[NodeInfo], want: Want) -> Nodes {
+        let mut candidates = candidates.to_vec();
+        ordered_by_distance(&mut candidates, target);
+
+        let encode = |is_v4: bool| {
+            let mut field = Vec::new();
+            for node in candidates
+                .iter()
+                .filter(|node| matches!(node.addr, CompactAddr::V4(_)) == is_v4)
+                .take(self.k)
+            {
+                node.encode(&mut field);
+            }
+            field
+        };
+        Nodes {
+            nodes: if want.n4 { Some(encode(true)) } else { None },
+            nodes6: if want.n6 { Some(encode(false)) } else { None },
+        }
+    }
+}
+
+fn check_len(nodes: &[u8], entry_len: usize) -> Result<(), DecodeError> {
+    if nodes.len().is_multiple_of(entry_len) {
+        Ok(())
+    } else {
+        Err(DecodeError::invalid_length(nodes.len(), entry_len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u8) -> [u8; ID_LEN] {
+        let mut id = [0; ID_LEN];
+        id[ID_LEN - 1] = n;
+        id
+    }
+
+    fn node_v4(n: u8) -> NodeInfo {
+        NodeInfo::new(
+            id(n),
+            format!("10.0.0.{}:6881", n).parse::<CompactAddr>().unwrap(),
+        )
+    }
+
+    fn node_v6(n: u8) -> NodeInfo {
+        NodeInfo::new(
+            id(n),
+            format!("[2001:db8::{}]:6881", n)
+                .parse::<CompactAddr>()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn node_info_encoding() {
+        let node = node_v4(1);
+        let bytes = node.to_vec();
+        assert_eq!(bytes.len(), NODE_V4_LEN);
+        assert_eq!(bytes.len(), node.encoded_len());
+        assert_eq!(bytes[..ID_LEN], id(1));
+        assert_eq!(bytes[ID_LEN..], [10, 0, 0, 1, 0x1a, 0xe1]);
+        assert_eq!(node_v6(1).encoded_len(), NODE_V6_LEN);
+        assert_eq!(
+            node.to_string(),
+            "0000000000000000000000000000000000000001@10.0.0.1:6881"
+        );
+    }
+
+    #[test]
+    fn want() {
+        let want = Want::from_values(vec![&b"n6"[..], b"x", b"n4"]);
+        assert!(want.n4 && want.n6);
+        assert_eq!(Want::from_values(Vec::<&[u8]>::new()), Want::default());
+        assert_eq!(
+            Want::from_querier("1.2.3.4:1".parse().unwrap()),
+            Want {
+                n4: true,
+                n6: false
+            }
+        );
+        assert_eq!(
+            Want::from_querier("[::1]:1".parse().unwrap()),
+            Want {
+                n4: false,
+                n6: true
+            }
+        );
+    }
+
+    #[test]
+    fn builder_families_and_k() {
+        let candidates: Vec<_> = (1..=10).map(node_v4).chain((1..=3).map(node_v6)).collect();
+        let both = Want { n4: true, n6: true };
+        let nodes = NodesBuilder::new().build(&id(0), &candidates, both);
+        assert_eq!(nodes.nodes.as_ref().map(Vec::len), Some(K * NODE_V4_LEN));
+        assert_eq!(nodes.nodes6.as_ref().map(Vec::len), Some(3 * NODE_V6_LEN));
+
+        let nodes = NodesBuilder::new().k(2).build(
+            &id(0),
+            &candidates,
+            Want {
+                n4: false,
+                n6: true,
+            },
+        );
+        assert_eq!(nodes.nodes, None);
+        assert_eq!(
+            nodes.nodes6,
+            Some([node_v6(1).to_vec(), node_v6(2).to_vec()].concat())
+        );
+
+        let nodes = NodesBuilder::new().build(&id(0), &[], both);
+        assert_eq!(nodes.nodes, Some(Vec::new()));
+    }
+
+    #[test]
+    fn decode_round_trip() {
+        let field = [node_v4(1).to_vec(), node_v4(2).to_vec()].concat();
+        assert_eq!(decode_v4(&field), Ok(vec![node_v4(1), node_v4(2)]));
+
+        let field = node_v6(1).to_vec();
+        assert_eq!(decode_v6(&field), Ok(vec![node_v6(1)]));
+        assert_eq!(decode_v6(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn decode_invalid_length() {
+        let mut field = node_v4(1).to_vec();
+        field.push(0);
+        assert_eq!(
+            decode_v4(&field),
+            Err(DecodeError::invalid_length(NODE_V4_LEN + 1, NODE_V4_LEN))
+        );
+        assert!(decode_v6(&field).is_err());
+    }
+
+    #[test]
+    fn nodes_iter() {
+        let mut field = [node_v6(1).to_vec(), node_v6(2).to_vec()].concat();
+        field.extend_from_slice(&[1, 2, 3]);
+        let mut iter = iter_v6(&field);
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.next(), Some(node_v6(1)));
+        assert_eq!(iter.len(), 1);
+        assert_eq!(iter.remainder(), [1, 2, 3]);
+        assert_eq!(iter.next(), Some(node_v6(2)));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter_v4(&field[..NODE_V4_LEN - 1]).count(), 0);
+    }
+
+    #[test]
+    fn distance() {
+        let node = NodeInfo::new([0xff; ID_LEN], node_v4(1).addr());
+        assert_eq!(node.distance(&[0xff; ID_LEN]), [0; ID_LEN]);
+        assert_eq!(node.distance(&[0; ID_LEN]), [0xff; ID_LEN]);
+        assert_eq!(node_v4(0b101).distance(&id(0b011)), id(0b110));
+    }
+
+    #[test]
+    fn ordering_by_distance() {
+        let mut nodes = vec![node_v4(8), node_v4(1), node_v4(6), node_v4(7)];
+        ordered_by_distance(&mut nodes, &id(7));
+        assert_eq!(nodes, [node_v4(7), node_v4(6), node_v4(1), node_v4(8)]);
+
+        let same_id = NodeInfo::new(id(6), node_v6(1).addr());
+        let mut nodes = vec![same_id, node_v4(6)];
+        ordered_by_distance(&mut nodes, &id(0));
+        assert_eq!(nodes, [same_id, node_v4(6)]);
+    }
+
+    #[test]
+    fn builder_orders_by_closeness() {
+        let candidates: Vec<_> = (1..=12).map(node_v4).collect();
+        let nodes = NodesBuilder::new().k(3).build(
+            &id(12),
+            &candidates,
+            Want {
+                n4: true,
+                n6: false,
+            },
+        );
+        let decoded = decode_v4(&nodes.nodes.unwrap()).unwrap();
+        assert_eq!(decoded, [node_v4(12), node_v4(8), node_v4(9)]);
+    }
+}
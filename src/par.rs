@@ -0,0 +1,232 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Parallel bulk operations on blobs with [`rayon`].
+//!
+//! Blobs are split across threads at compact address boundaries. The results
+//! are the same as the sequential operations.
+
+use crate::{
+    blob::{self, DecodeError, V4_LEN, V6_LEN},
+    CompactAddrV4, CompactAddrV4Info, CompactAddrV6, CompactAddrV6Info,
+};
+use rayon::prelude::*;
+use std::net::{SocketAddrV4, SocketAddrV6};
+
+/// Returns the blob of the concatenated compact IPv4 addresses.
+pub fn par_encode_v4(addrs: &[SocketAddrV4]) -> Vec<u8> {
+    let mut blob = vec![0; addrs.len() * V4_LEN];
+    blob.par_chunks_exact_mut(V4_LEN)
+        .zip(addrs.par_iter())
+        .for_each(|(chunk, addr)| chunk.copy_from_slice(&addr.to_compact_address()));
+    blob
+}
+
+/// Returns the blob of the concatenated compact IPv6 addresses.
+pub fn par_encode_v6(addrs: &[SocketAddrV6]) -> Vec<u8> {
+    let mut blob = vec![0; addrs.len() * V6_LEN];
+    blob.par_chunks_exact_mut(V6_LEN)
+        .zip(addrs.par_iter())
+        .for_each(|(chunk, addr)| chunk.copy_from_slice(&addr.to_compact_address()));
+    blob
+}
+
+/// Decodes a blob of compact IPv4 addresses.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length.
+pub fn par_decode_v4(blob: &[u8]) -> Result<Vec<SocketAddrV4>, DecodeError> {
+    let entries = split_v4(blob)?;
+    Ok(entries
+        .par_iter()
+        .map(|a| SocketAddrV4::from_compact(*a))
+        .collect())
+}
+
+/// Decodes a blob of compact IPv6 addresses.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length.
+pub fn par_decode_v6(blob: &[u8]) -> Result<Vec<SocketAddrV6>, DecodeError> {
+    let entries = split_v6(blob)?;
+    Ok(entries
+        .par_iter()
+        .map(|a| SocketAddrV6::from_compact(*a))
+        .collect())
+}
+
+/// Returns a blob of the compact IPv4 addresses which satisfy the predicate.
+///
+/// The order of the addresses is preserved.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length.
+pub fn par_filter_v4<F>(blob: &[u8], f: F) -> Result<Vec<u8>, DecodeError>
+where
+    F: Fn(CompactAddrV4) -> bool + Sync + Send,
+{
+    let entries = split_v4(blob)?;
+    Ok(entries
+        .par_iter()
+        .filter(|a| f(CompactAddrV4::new(**a)))
+        .flat_map_iter(|a| a.iter().copied())
+        .collect())
+}
+
+/// Returns a blob of the compact IPv6 addresses which satisfy the predicate.
+///
+/// The order of the addresses is preserved.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length.
+pub fn par_filter_v6<F>(blob: &[u8], f: F) -> Result<Vec<u8>, DecodeError>
+where
+    F: Fn(CompactAddrV6) -> bool + Sync + Send,
+{
+    let entries = split_v6(blob)?;
+    Ok(entries
+        .par_iter()
+        .filter(|a| f(CompactAddrV6::new(**a)))
+        .flat_map_iter(|a| a.iter().copied())
+        .collect())
+}
+
+/// Sorts the compact IPv4 addresses in a blob in place.
+///
+/// Addresses are ordered by IP address and then by port.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length. The blob is not modified.
+pub fn par_sort_v4(blob: &mut [u8]) -> Result<(), DecodeError> {
+    let len = blob.len();
+    let (entries, remainder) = blob::as_chunks_mut_v4(blob);
+    if !remainder.is_empty() {
+        return Err(DecodeError::invalid_length(len, V4_LEN));
+    }
+    entries.par_sort_unstable();
+    Ok(())
+}
+
+/// Sorts the compact IPv6 addresses in a blob in place.
+///
+/// Addresses are ordered by IP address and then by port.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length. The blob is not modified.
+pub fn par_sort_v6(blob: &mut [u8]) -> Result<(), DecodeError> {
+    let len = blob.len();
+    let (entries, remainder) = blob::as_chunks_mut_v6(blob);
+    if !remainder.is_empty() {
+        return Err(DecodeError::invalid_length(len, V6_LEN));
+    }
+    entries.par_sort_unstable();
+    Ok(())
+}
+
+fn split_v4(blob: &[u8]) -> Result<&[[u8; V4_LEN]], DecodeError> {
+    let (entries, remainder) = blob::as_chunks_v4(blob);
+    if remainder.is_empty() {
+        Ok(entries)
+    } else {
+        Err(DecodeError::invalid_length(blob.len(), V4_LEN))
+    }
+}
+
+fn split_v6(blob: &[u8]) -> Result<&[[u8; V6_LEN]], DecodeError> {
+    let (entries, remainder) = blob::as_chunks_v6(blob);
+    if remainder.is_empty() {
+        Ok(entries)
+    } else {
+        Err(DecodeError::invalid_length(blob.len(), V6_LEN))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blob;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn v4_addrs() -> Vec<SocketAddrV4> {
+        (0..1000u32)
+            .map(|n| SocketAddrV4::new(Ipv4Addr::from(n.wrapping_mul(2_654_435_761)), n as u16))
+            .collect()
+    }
+
+    fn v6_addrs() -> Vec<SocketAddrV6> {
+        (0..1000u128)
+            .map(|n| SocketAddrV6::new(Ipv6Addr::from(n << 100 | n), n as u16, 0, 0))
+            .collect()
+    }
+
+    #[test]
+    fn matches_sequential_encode_and_decode() {
+        let addrs = v4_addrs();
+        let blob = par_encode_v4(&addrs);
+        assert_eq!(blob, blob::encode_v4(addrs.iter().copied()));
+        assert_eq!(par_decode_v4(&blob), Ok(addrs));
+
+        let addrs = v6_addrs();
+        let blob = par_encode_v6(&addrs);
+        assert_eq!(blob, blob::encode_v6(addrs.iter().copied()));
+        assert_eq!(par_decode_v6(&blob), Ok(addrs));
+    }
+
+    #[test]
+    fn filter_preserves_order() {
+        let addrs = v4_addrs();
+        let blob = par_encode_v4(&addrs);
+        let even = par_filter_v4(&blob, |addr| addr.port() % 2 == 0).unwrap();
+        let expected = addrs.iter().copied().filter(|addr| addr.port() % 2 == 0);
+        assert_eq!(even, blob::encode_v4(expected));
+
+        let blob = par_encode_v6(&v6_addrs());
+        assert_eq!(par_filter_v6(&blob, |_| false), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn sort_orders_by_ip_then_port() {
+        let mut addrs = v4_addrs();
+        let mut blob = par_encode_v4(&addrs);
+        par_sort_v4(&mut blob).unwrap();
+        addrs.sort_by_key(|addr| (*addr.ip(), addr.port()));
+        assert_eq!(par_decode_v4(&blob), Ok(addrs));
+
+        let mut addrs = v6_addrs();
+        addrs.reverse();
+        let mut blob = par_encode_v6(&addrs);
+        par_sort_v6(&mut blob).unwrap();
+        addrs.sort_by_key(|addr| (*addr.ip(), addr.port()));
+        assert_eq!(par_decode_v6(&blob), Ok(addrs));
+    }
+
+    #[test]
+    fn rejects_ragged_blobs() {
+        let mut blob = vec![0; V4_LEN + 1];
+        assert!(par_decode_v4(&blob).is_err());
+        assert!(par_filter_v4(&blob, |_| true).is_err());
+        assert!(par_sort_v4(&mut blob).is_err());
+
+        let mut blob = vec![0; V6_LEN - 1];
+        assert!(par_decode_v6(&blob).is_err());
+        assert!(par_filter_v6(&blob, |_| true).is_err());
+        assert!(par_sort_v6(&mut blob).is_err());
+    }
+}
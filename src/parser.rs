@@ -0,0 +1,18 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Parser combinator functions for compact addresses.
+//!
+//! The parsers consume compact addresses from the start of the input so they
+//! can be composed into larger message grammars. The `nom` and `winnow`
+//! features enable the parsers for the respective crates.
+
+#[cfg(feature = "nom")]
+pub mod nom;
+#[cfg(feature = "winnow")]
+pub mod winnow;
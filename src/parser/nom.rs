@@ -0,0 +1,154 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Parsers for [`nom`].
+//!
+//! The parsers operate on complete input.
+
+use crate::{
+    blob::{V4_LEN, V6_LEN},
+    CompactAddrV4Info, CompactAddrV6Info,
+};
+use ::nom::{
+    error::{ErrorKind, ParseError},
+    Err, IResult,
+};
+use std::net::{SocketAddrV4, SocketAddrV6};
+
+/// Parses a compact IPv4 address.
+///
+/// # Errors
+///
+/// Returns an error if the input is shorter than a compact IPv4 address.
+pub fn compact_v4<'a, E>(input: &'a [u8]) -> IResult<&'a [u8], SocketAddrV4, E>
+where
+    E: ParseError<&'a [u8]>,
+{
+    if input.len() < V4_LEN {
+        return Err(Err::Error(E::from_error_kind(input, ErrorKind::Eof)));
+    }
+    let (entry, rest) = input.split_at(V4_LEN);
+    let mut a: [u8; V4_LEN] = [0; V4_LEN];
+    a.copy_from_slice(entry);
+    Ok((rest, SocketAddrV4::from_compact(a)))
+}
+
+/// Parses a compact IPv6 address.
+///
+/// # Errors
+///
+/// Returns an error if the input is shorter than a compact IPv6 address.
+pub fn compact_v6<'a, E>(input: &'a [u8]) -> IResult<&'a [u8], SocketAddrV6, E>
+where
+    E: ParseError<&'a [u8]>,
+{
+    if input.len() < V6_LEN {
+        return Err(Err::Error(E::from_error_kind(input, ErrorKind::Eof)));
+    }
+    let (entry, rest) = input.split_at(V6_LEN);
+    let mut a: [u8; V6_LEN] = [0; V6_LEN];
+    a.copy_from_slice(entry);
+    Ok((rest, SocketAddrV6::from_compact(a)))
+}
+
+/// Parses all whole compact IPv4 addresses.
+///
+/// Trailing bytes which do not form a whole address are returned as the
+/// remaining input.
+///
+/// # Errors
+///
+/// The parser does not fail.
+pub fn list_v4<'a, E>(mut input: &'a [u8]) -> IResult<&'a [u8], Vec<SocketAddrV4>, E>
+where
+    E: ParseError<&'a [u8]>,
+{
+    let mut addrs = Vec::with_capacity(input.len() / V4_LEN);
+    while input.len() >= V4_LEN {
+        let (rest, addr) = compact_v4(input)?;
+        addrs.push(addr);
+        input = rest;
+    }
+    Ok((input, addrs))
+}
+
+/// Parses all whole compact IPv6 addresses.
+///
+/// Trailing bytes which do not form a whole address are returned as the
+/// remaining input.
+///
+/// # Errors
+///
+/// The parser does not fail.
+pub fn list_v6<'a, E>(mut input: &'a [u8]) -> IResult<&'a [u8], Vec<SocketAddrV6>, E>
+where
+    E: ParseError<&'a [u8]>,
+{
+    let mut addrs = Vec::with_capacity(input.len() / V6_LEN);
+    while input.len() >= V6_LEN {
+        let (rest, addr) = compact_v6(input)?;
+        addrs.push(addr);
+        input = rest;
+    }
+    Ok((input, addrs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::nom::error::Error;
+
+    #[test]
+    fn compact() {
+        let input = [1, 2, 3, 4, 0x1a, 0xe1, 9];
+        assert_eq!(
+            compact_v4::<Error<&[u8]>>(&input),
+            Ok((&[9][..], "1.2.3.4:6881".parse().unwrap()))
+        );
+
+        let mut input = [0; V6_LEN];
+        input[15] = 1;
+        input[17] = 80;
+        assert_eq!(
+            compact_v6::<Error<&[u8]>>(&input),
+            Ok((&[][..], "[::1]:80".parse().unwrap()))
+        );
+    }
+
+    #[test]
+    fn compact_short() {
+        let input = [1, 2, 3, 4, 0];
+        assert_eq!(
+            compact_v4::<Error<&[u8]>>(&input),
+            Err(Err::Error(Error::new(&input[..], ErrorKind::Eof)))
+        );
+        assert!(compact_v6::<Error<&[u8]>>(&[0; V6_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn lists() {
+        let input = [1, 2, 3, 4, 0, 80, 5, 6, 7, 8, 0, 81, 0xff];
+        let (rest, addrs) = list_v4::<Error<&[u8]>>(&input).unwrap();
+        assert_eq!(rest, [0xff]);
+        assert_eq!(
+            addrs,
+            vec![
+                "1.2.3.4:80".parse::<SocketAddrV4>().unwrap(),
+                "5.6.7.8:81".parse().unwrap()
+            ]
+        );
+
+        let (rest, addrs) = list_v6::<Error<&[u8]>>(&[0; V6_LEN * 2 + 3]).unwrap();
+        assert_eq!(rest.len(), 3);
+        assert_eq!(addrs.len(), 2);
+
+        let (rest, addrs) = list_v6::<Error<&[u8]>>(&[]).unwrap();
+        assert!(rest.is_empty());
+        assert!(addrs.is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Parsers for [`winnow`].
+
+use crate::{
+    blob::{V4_LEN, V6_LEN},
+    CompactAddrV4Info, CompactAddrV6Info,
+};
+use ::winnow::{error::ParserError, token::take, Parser};
+use std::net::{SocketAddrV4, SocketAddrV6};
+
+/// Parses a compact IPv4 address.
+///
+/// # Errors
+///
+/// Returns an error if the input is shorter than a compact IPv4 address.
+pub fn compact_v4<'i, E>(input: &mut &'i [u8]) -> Result<SocketAddrV4, E>
+where
+    E: ParserError<&'i [u8]>,
+{
+    let entry = take(V4_LEN).parse_next(input)?;
+    let mut a: [u8; V4_LEN] = [0; V4_LEN];
+    a.copy_from_slice(entry);
+    Ok(SocketAddrV4::from_compact(a))
+}
+
+/// Parses a compact IPv6 address.
+///
+/// # Errors
+///
+/// Returns an error if the input is shorter than a compact IPv6 address.
+pub fn compact_v6<'i, E>(input: &mut &'i [u8]) -> Result<SocketAddrV6, E>
+where
+    E: ParserError<&'i [u8]>,
+{
+    let entry = take(V6_LEN).parse_next(input)?;
+    let mut a: [u8; V6_LEN] = [0; V6_LEN];
+    a.copy_from_slice(entry);
+    Ok(SocketAddrV6::from_compact(a))
+}
+
+/// Parses all whole compact IPv4 addresses.
+///
+/// Trailing bytes which do not form a whole address are left in the input.
+///
+/// # Errors
+///
+/// The parser does not fail.
+pub fn list_v4<'i, E>(input: &mut &'i [u8]) -> Result<Vec<SocketAddrV4>, E>
+where
+    E: ParserError<&'i [u8]>,
+{
+    let mut addrs = Vec::with_capacity(input.len() / V4_LEN);
+    while input.len() >= V4_LEN {
+        addrs.push(compact_v4(input)?);
+    }
+    Ok(addrs)
+}
+
+/// Parses all whole compact IPv6 addresses.
+///
+/// Trailing bytes which do not form a whole address are left in the input.
+///
+/// # Errors
+///
+/// The parser does not fail.
+pub fn list_v6<'i, E>(input: &mut &'i [u8]) -> Result<Vec<SocketAddrV6>, E>
+where
+    E: ParserError<&'i [u8]>,
+{
+    let mut addrs = Vec::with_capacity(input.len() / V6_LEN);
+    while input.len() >= V6_LEN {
+        addrs.push(compact_v6(input)?);
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::winnow::error::ContextError;
+
+    #[test]
+    fn compact() {
+        let bytes = [1, 2, 3, 4, 0x1a, 0xe1, 9];
+        let mut input = &bytes[..];
+        assert_eq!(
+            compact_v4::<ContextError>(&mut input),
+            Ok("1.2.3.4:6881".parse().unwrap())
+        );
+        assert_eq!(input, [9]);
+
+        let mut bytes = [0; V6_LEN];
+        bytes[15] = 1;
+        bytes[17] = 80;
+        let mut input = &bytes[..];
+        assert_eq!(
+            compact_v6::<ContextError>(&mut input),
+            Ok("[::1]:80".parse().unwrap())
+        );
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn compact_short() {
+        let bytes = [1, 2, 3, 4, 0];
+        let mut input = &bytes[..];
+        assert!(compact_v4::<ContextError>(&mut input).is_err());
+
+        let bytes = [0; V6_LEN - 1];
+        let mut input = &bytes[..];
+        assert!(compact_v6::<ContextError>(&mut input).is_err());
+    }
+
+    #[test]
+    fn lists() {
+        let bytes = [1, 2, 3, 4, 0, 80, 5, 6, 7, 8, 0, 81, 0xff];
+        let mut input = &bytes[..];
+        assert_eq!(
+            list_v4::<ContextError>(&mut input),
+            Ok(vec![
+                "1.2.3.4:80".parse::<SocketAddrV4>().unwrap(),
+                "5.6.7.8:81".parse().unwrap()
+            ])
+        );
+        assert_eq!(input, [0xff]);
+
+        let bytes = [0; V6_LEN * 2 + 3];
+        let mut input = &bytes[..];
+        assert_eq!(list_v6::<ContextError>(&mut input).map(|a| a.len()), Ok(2));
+        assert_eq!(input.len(), 3);
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! PCP address and port fields.
+//!
+//! PCP (RFC 6887) address fields are always 16 bytes. IPv6 addresses are
+//! stored as is and IPv4 addresses are stored as IPv4-mapped IPv6 addresses
+//! (`::ffff:a.b.c.d`). Port fields are 2 byte big-endian integers.
+//!
+//! Decoding an IPv4-mapped address field always returns an IPv4 address so
+//! that responses compare equal to the IPv4 addresses in requests.
+
+use crate::CompactAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// Returns the 16 byte address field for the IP address.
+pub fn to_address_field(ip: IpAddr) -> [u8; 16] {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+        IpAddr::V6(ip) => ip.octets(),
+    }
+}
+
+/// Returns the IP address in a 16 byte address field.
+///
+/// IPv4-mapped addresses are returned as IPv4 addresses.
+pub fn from_address_field(field: &[u8; 16]) -> IpAddr {
+    let ip = Ipv6Addr::from(*field);
+    match ip.to_ipv4_mapped() {
+        Some(ip) => IpAddr::V4(ip),
+        None => IpAddr::V6(ip),
+    }
+}
+
+/// Returns the address and port fields for the socket address.
+pub fn to_fields(addr: SocketAddr) -> ([u8; 16], [u8; 2]) {
+    (to_address_field(addr.ip()), addr.port().to_be_bytes())
+}
+
+/// Returns the socket address in an address field and port field.
+///
+/// IPv4-mapped addresses are returned as IPv4 socket addresses.
+pub fn from_fields(address: &[u8; 16], port: &[u8; 2]) -> SocketAddr {
+    SocketAddr::new(from_address_field(address), u16::from_be_bytes(*port))
+}
+
+impl CompactAddr {
+    /// Returns the PCP address and port fields.
+    pub fn to_pcp_fields(&self) -> ([u8; 16], [u8; 2]) {
+        to_fields(SocketAddr::from(*self))
+    }
+
+    /// Instantiates a compact address from PCP address and port fields.
+    ///
+    /// IPv4-mapped addresses are returned as IPv4 compact addresses.
+    pub fn from_pcp_fields(address: &[u8; 16], port: &[u8; 2]) -> Self {
+        CompactAddr::from(from_fields(address, port))
+    }
+}
+
+/// Returns the address field for an unspecified IPv4 address.
+///
+/// PCP uses the IPv4-mapped unspecified address (`::ffff:0.0.0.0`) when an IPv4
+/// address is requested but not known.
+pub fn unspecified_v4_field() -> [u8; 16] {
+    to_address_field(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_field() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let field = to_address_field(v4);
+        assert_eq!(
+            field,
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 192, 0, 2, 1]
+        );
+        assert_eq!(from_address_field(&field), v4);
+
+        let v6 = IpAddr::V6("2001:db8::1".parse().unwrap());
+        assert_eq!(from_address_field(&to_address_field(v6)), v6);
+    }
+
+    #[test]
+    fn unspecified_v4() {
+        let field = unspecified_v4_field();
+        assert_eq!(field[10..12], [0xff, 0xff]);
+        assert_eq!(
+            from_address_field(&field),
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        );
+        assert_ne!(field, [0; 16]);
+        assert_eq!(
+            from_address_field(&[0; 16]),
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        );
+    }
+
+    #[test]
+    fn fields_round_trip() {
+        let addr: SocketAddr = "192.0.2.1:5351".parse().unwrap();
+        let (address, port) = to_fields(addr);
+        assert_eq!(port, [0x14, 0xe7]);
+        assert_eq!(from_fields(&address, &port), addr);
+
+        let compact: CompactAddr = "[2001:db8::1]:5351".parse().unwrap();
+        let (address, port) = compact.to_pcp_fields();
+        assert_eq!(CompactAddr::from_pcp_fields(&address, &port), compact);
+    }
+
+    #[test]
+    fn mapped_v6_decodes_as_v4() {
+        let compact: CompactAddr = "[::ffff:192.0.2.1]:80".parse().unwrap();
+        let (address, port) = compact.to_pcp_fields();
+        assert_eq!(
+            CompactAddr::from_pcp_fields(&address, &port),
+            "192.0.2.1:80".parse::<CompactAddr>().unwrap()
+        );
+    }
+}
@@ -0,0 +1,312 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Borrowed or owned storage of IPv4 and IPv6 peer blobs.
+//!
+//! A [`CompactPeers`] can borrow the `peers` and `peers6` blobs from a network
+//! buffer without copying. Modifying the peers converts the modified blob to
+//! owned storage.
+
+use crate::{
+    blob::{self, DecodeError, IterV4, IterV6, V4_LEN, V6_LEN},
+    CompactAddr,
+};
+use std::{borrow::Cow, iter::FromIterator};
+
+/// Blobs of compact IPv4 and IPv6 addresses in borrowed or owned storage.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CompactPeers<'a> {
+    v4: Cow<'a, [u8]>,
+    v6: Cow<'a, [u8]>,
+}
+
+impl<'a> CompactPeers<'a> {
+    /// Instantiates empty peers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Instantiates from borrowed blobs of compact IPv4 and IPv6 addresses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either blob length is not a multiple of its compact
+    /// address length.
+    pub fn from_borrowed(v4: &'a [u8], v6: &'a [u8]) -> Result<Self, DecodeError> {
+        Self::from_cows(Cow::Borrowed(v4), Cow::Borrowed(v6))
+    }
+
+    /// Instantiates from owned blobs of compact IPv4 and IPv6 addresses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either blob length is not a multiple of its compact
+    /// address length.
+    pub fn from_owned(v4: Vec<u8>, v6: Vec<u8>) -> Result<Self, DecodeError> {
+        Self::from_cows(Cow::Owned(v4), Cow::Owned(v6))
+    }
+
+    fn from_cows(v4: Cow<'a, [u8]>, v6: Cow<'a, [u8]>) -> Result<Self, DecodeError> {
+        check_len(&v4, V4_LEN)?;
+        check_len(&v6, V6_LEN)?;
+        Ok(Self { v4, v6 })
+    }
+
+    /// Returns the blob of compact IPv4 addresses.
+    pub fn v4(&self) -> &[u8] {
+        &self.v4
+    }
+
+    /// Returns the blob of compact IPv6 addresses.
+    pub fn v6(&self) -> &[u8] {
+        &self.v6
+    }
+
+    /// Returns true if neither blob has been converted to owned storage.
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.v4, Cow::Borrowed(_)) && matches!(self.v6, Cow::Borrowed(_))
+    }
+
+    /// Returns the number of IPv4 addresses.
+    pub fn len_v4(&self) -> usize {
+        self.v4.len() / V4_LEN
+    }
+
+    /// Returns the number of IPv6 addresses.
+    pub fn len_v6(&self) -> usize {
+        self.v6.len() / V6_LEN
+    }
+
+    /// Returns the number of addresses.
+    pub fn len(&self) -> usize {
+        self.len_v4() + self.len_v6()
+    }
+
+    /// Returns true if there are no addresses.
+    pub fn is_empty(&self) -> bool {
+        self.v4.is_empty() && self.v6.is_empty()
+    }
+
+    /// Returns an iterator over the IPv4 addresses.
+    pub fn iter_v4(&self) -> IterV4<'_> {
+        blob::iter_v4(&self.v4)
+    }
+
+    /// Returns an iterator over the IPv6 addresses.
+    pub fn iter_v6(&self) -> IterV6<'_> {
+        blob::iter_v6(&self.v6)
+    }
+
+    /// Appends an address.
+    ///
+    /// The blob for the address's family is converted to owned storage.
+    pub fn push<A: Into<CompactAddr>>(&mut self, addr: A) {
+        match addr.into() {
+            CompactAddr::V4(addr) => self.v4.to_mut().extend_from_slice(addr.as_bytes()),
+            CompactAddr::V6(addr) => self.v6.to_mut().extend_from_slice(addr.as_bytes()),
+        }
+    }
+
+    /// Appends the addresses in blobs of compact IPv4 and IPv6 addresses.
+    ///
+    /// Only the blobs which are not empty are converted to owned storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either blob length is not a multiple of its compact
+    /// address length. The peers are not modified.
+    pub fn extend_from_blobs(&mut self, v4: &[u8], v6: &[u8]) -> Result<(), DecodeError> {
+        check_len(v4, V4_LEN)?;
+        check_len(v6, V6_LEN)?;
+        if !v4.is_empty() {
+            self.v4.to_mut().extend_from_slice(v4);
+        }
+        if !v6.is_empty() {
+            self.v6.to_mut().extend_from_slice(v6);
+        }
+        Ok(())
+    }
+
+    /// Retains only the IPv4 and IPv6 addresses for which the predicate returns
+    /// true.
+    ///
+    /// Only the blobs which have addresses removed are converted to owned
+    /// storage.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(CompactAddr) -> bool,
+    {
+        retain_entries::<V4_LEN, _>(&mut self.v4, |a| f(CompactAddr::V4((*a).into())));
+        retain_entries::<V6_LEN, _>(&mut self.v6, |a| f(CompactAddr::V6((*a).into())));
+    }
+
+    /// Removes all addresses.
+    pub fn clear(&mut self) {
+        self.v4 = Cow::Borrowed(&[]);
+        self.v6 = Cow::Borrowed(&[]);
+    }
+
+    /// Returns the peers with owned storage.
+    pub fn into_owned(self) -> CompactPeers<'static> {
+        CompactPeers {
+            v4: Cow::Owned(self.v4.into_owned()),
+            v6: Cow::Owned(self.v6.into_owned()),
+        }
+    }
+
+    /// Returns the blobs of compact IPv4 and IPv6 addresses.
+    pub fn into_blobs(self) -> (Vec<u8>, Vec<u8>) {
+        (self.v4.into_owned(), self.v6.into_owned())
+    }
+}
+
+impl<A: Into<CompactAddr>> FromIterator<A> for CompactPeers<'_> {
+    fn from_iter<I: IntoIterator<Item = A>>(iter: I) -> Self {
+        let mut peers = Self::new();
+        peers.extend(iter);
+        peers
+    }
+}
+
+impl<A: Into<CompactAddr>> Extend<A> for CompactPeers<'_> {
+    fn extend<I: IntoIterator<Item = A>>(&mut self, iter: I) {
+        for addr in iter {
+            self.push(addr);
+        }
+    }
+}
+
+/// Retains the entries for which the predicate returns true.
+///
+/// The predicate is called once per entry and the blob is only converted to
+/// owned storage if an entry is removed.
+fn retain_entries<const N: usize, F>(blob: &mut Cow<'_, [u8]>, mut f: F)
+where
+    F: FnMut(&[u8; N]) -> bool,
+{
+    let first = match blob.as_chunks::<N>().0.iter().position(|a| !f(a)) {
+        Some(first) => first,
+        None => return,
+    };
+
+    let blob = blob.to_mut();
+    let (entries, _) = blob.as_chunks_mut::<N>();
+    let mut len = first;
+    for idx in first + 1..entries.len() {
+        if f(&entries[idx]) {
+            entries[len] = entries[idx];
+            len += 1;
+        }
+    }
+    blob.truncate(len * N);
+}
+
+fn check_len(blob: &[u8], entry_len: usize) -> Result<(), DecodeError> {
+    if blob.len().is_multiple_of(entry_len) {
+        Ok(())
+    } else {
+        Err(DecodeError::invalid_length(blob.len(), entry_len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompactAddrV4, CompactAddrV6};
+
+    const V4: [u8; 12] = [1, 2, 3, 4, 0, 80, 5, 6, 7, 8, 0, 81];
+    const V6: [u8; 18] = [6; 18];
+
+    #[test]
+    fn borrowed() {
+        let peers = CompactPeers::from_borrowed(&V4, &V6).unwrap();
+        assert!(peers.is_borrowed());
+        assert_eq!(peers.v4().as_ptr(), V4.as_ptr());
+        assert_eq!(peers.len_v4(), 2);
+        assert_eq!(peers.len_v6(), 1);
+        assert_eq!(peers.len(), 3);
+        assert_eq!(peers.iter_v4().count(), 2);
+        assert_eq!(peers.iter_v6().count(), 1);
+        assert!(!peers.into_owned().is_borrowed());
+    }
+
+    #[test]
+    fn invalid_length() {
+        assert_eq!(
+            CompactPeers::from_borrowed(&V4[..7], &V6),
+            Err(DecodeError::invalid_length(7, V4_LEN))
+        );
+        assert_eq!(
+            CompactPeers::from_owned(Vec::new(), vec![0; 17]),
+            Err(DecodeError::invalid_length(17, V6_LEN))
+        );
+
+        let mut peers = CompactPeers::from_borrowed(&V4, &[]).unwrap();
+        assert!(peers.extend_from_blobs(&V4, &V6[..17]).is_err());
+        assert_eq!(peers.len(), 2);
+        assert!(peers.is_borrowed());
+    }
+
+    #[test]
+    fn push_converts_one_family() {
+        let mut peers = CompactPeers::from_borrowed(&V4, &V6).unwrap();
+        peers.push(CompactAddrV6::from([7; 18]));
+        assert!(!peers.is_borrowed());
+        assert_eq!(peers.v4().as_ptr(), V4.as_ptr());
+        assert_eq!(peers.len_v6(), 2);
+
+        let mut peers = CompactPeers::from_borrowed(&V4, &V6).unwrap();
+        peers.extend_from_blobs(&[], &V6).unwrap();
+        assert_eq!(peers.v4().as_ptr(), V4.as_ptr());
+        assert_eq!(peers.v6(), [V6, V6].concat());
+    }
+
+    #[test]
+    fn retain() {
+        let mut peers = CompactPeers::from_borrowed(&V4, &V6).unwrap();
+        peers.retain(|_| true);
+        assert!(peers.is_borrowed());
+
+        peers.retain(|addr| addr.port() != 81);
+        assert_eq!(peers.v4(), &V4[..V4_LEN]);
+        assert_eq!(peers.v6(), V6);
+        assert_eq!(peers.v6().as_ptr(), V6.as_ptr());
+
+        peers.retain(|addr| addr.is_ipv4());
+        assert!(peers.v6().is_empty());
+        assert_eq!(
+            peers.iter_v4().next(),
+            Some(CompactAddrV4::from([1, 2, 3, 4, 0, 80]).into())
+        );
+    }
+
+    #[test]
+    fn clear_and_into_blobs() {
+        let mut peers = CompactPeers::from_owned(V4.to_vec(), V6.to_vec()).unwrap();
+        assert!(!peers.is_borrowed());
+        assert_eq!(peers.clone().into_blobs(), (V4.to_vec(), V6.to_vec()));
+        peers.clear();
+        assert!(peers.is_empty());
+        assert!(peers.is_borrowed());
+    }
+
+    #[test]
+    fn from_iter_and_extend() {
+        let v4 = CompactAddrV4::from([1, 2, 3, 4, 0, 80]);
+        let v6 = CompactAddrV6::from(V6);
+        let mut peers: CompactPeers<'_> = vec![CompactAddr::from(v6), CompactAddr::from(v4)]
+            .into_iter()
+            .collect();
+        assert_eq!(peers.v4(), v4.as_bytes());
+        assert_eq!(peers.v6(), V6);
+
+        peers.extend(vec![v4]);
+        assert_eq!(peers.len_v4(), 2);
+        assert_eq!(peers.len_v6(), 1);
+    }
+}
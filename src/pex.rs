@@ -0,0 +1,294 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Peer exchange (BEP 11) `ut_pex` messages.
+//!
+//! A [`PexBuilder`] compares the peers previously sent to a remote peer with
+//! the current peers and builds the message fields:
+//!
+//! | Field       | Value                                        |
+//! |-------------|----------------------------------------------|
+//! | `added`     | Compact IPv4 addresses of the added peers     |
+//! | `added.f`   | One flags byte per `added` entry              |
+//! | `added6`    | Compact IPv6 addresses of the added peers     |
+//! | `added6.f`  | One flags byte per `added6` entry             |
+//! | `dropped`   | Compact IPv4 addresses of the dropped peers   |
+//! | `dropped6`  | Compact IPv6 addresses of the dropped peers   |
+//!
+//! The number of added and dropped peers in a message is capped. Peers over
+//! the cap are left for a later message.
+
+use crate::{
+    blob::{self, V4_LEN, V6_LEN},
+    CompactAddr,
+};
+use std::{collections::HashSet, ops::BitOr};
+
+/// The default maximum number of added peers in a message.
+pub const MAX_ADDED: usize = 50;
+
+/// The default maximum number of dropped peers in a message.
+pub const MAX_DROPPED: usize = 50;
+
+/// The flags of an added peer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PexFlags(u8);
+
+impl PexFlags {
+    /// The peer prefers encryption.
+    pub const PREFERS_ENCRYPTION: PexFlags = PexFlags(0x01);
+    /// The peer is a seed or partial seed.
+    pub const SEED: PexFlags = PexFlags(0x02);
+    /// The peer supports uTP.
+    pub const UTP: PexFlags = PexFlags(0x04);
+    /// The peer supports the `ut_holepunch` extension.
+    pub const HOLEPUNCH: PexFlags = PexFlags(0x08);
+    /// The peer is reachable (an outgoing connection was made to it).
+    pub const REACHABLE: PexFlags = PexFlags(0x10);
+
+    /// Instantiates flags from the flags byte.
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the flags byte.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Returns true if all of the other flags are set.
+    pub fn contains(self, other: PexFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for PexFlags {
+    type Output = PexFlags;
+
+    fn bitor(self, rhs: PexFlags) -> PexFlags {
+        PexFlags(self.0 | rhs.0)
+    }
+}
+
+/// The fields of a `ut_pex` message.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PexMessage {
+    /// The `added` field.
+    pub added: Vec<u8>,
+    /// The `added.f` field.
+    pub added_f: Vec<u8>,
+    /// The `added6` field.
+    pub added6: Vec<u8>,
+    /// The `added6.f` field.
+    pub added6_f: Vec<u8>,
+    /// The `dropped` field.
+    pub dropped: Vec<u8>,
+    /// The `dropped6` field.
+    pub dropped6: Vec<u8>,
+}
+
+impl PexMessage {
+    /// Returns true if the message has no added or dropped peers.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.added6.is_empty()
+            && self.dropped.is_empty()
+            && self.dropped6.is_empty()
+    }
+
+    /// Returns the peers known to the remote peer after the message, given
+    /// the peers known before it.
+    ///
+    /// Use the result as the previous peers when building the next message,
+    /// so peers left out by the caps are sent later.
+    pub fn apply(&self, previous: &[CompactAddr]) -> Vec<CompactAddr> {
+        let dropped: HashSet<&[u8]> = self
+            .dropped
+            .chunks_exact(V4_LEN)
+            .chain(self.dropped6.chunks_exact(V6_LEN))
+            .collect();
+        let mut peers: Vec<CompactAddr> = previous
+            .iter()
+            .filter(|addr| !dropped.contains(addr.as_ref()))
+            .copied()
+            .collect();
+        peers.extend(blob::iter_v4(&self.added).map(CompactAddr::from));
+        peers.extend(blob::iter_v6(&self.added6).map(CompactAddr::from));
+        peers
+    }
+}
+
+/// Builds `ut_pex` messages from snapshots of peers.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PexBuilder {
+    max_added: usize,
+    max_dropped: usize,
+}
+
+impl Default for PexBuilder {
+    fn default() -> Self {
+        Self {
+            max_added: MAX_ADDED,
+            max_dropped: MAX_DROPPED,
+        }
+    }
+}
+
+impl PexBuilder {
+    /// Instantiates a builder with the default caps.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of added IPv4 and IPv6 peers in a message.
+    pub fn max_added(&mut self, max_added: usize) -> &mut Self {
+        self.max_added = max_added;
+        self
+    }
+
+    /// Sets the maximum number of dropped IPv4 and IPv6 peers in a message.
+    pub fn max_dropped(&mut self, max_dropped: usize) -> &mut Self {
+        self.max_dropped = max_dropped;
+        self
+    }
+
+    /// Returns the message for the changes from the previous peers to the
+    /// current peers.
+    ///
+    /// Added peers are in the order of the current peers and dropped peers
+    /// are in the order of the previous peers.
+    pub fn build(
+        &self,
+        previous: &[CompactAddr],
+        current: &[(CompactAddr, PexFlags)],
+    ) -> PexMessage {
+        let previous_set: HashSet<CompactAddr> = previous.iter().copied().collect();
+        let current_set: HashSet<CompactAddr> = current.iter().map(|(addr, _)| *addr).collect();
+
+        let mut message = PexMessage::default();
+        let mut seen = HashSet::new();
+        for (addr, flags) in current
+            .iter()
+            .filter(|(addr, _)| !previous_set.contains(addr) && seen.insert(*addr))
+            .take(self.max_added)
+        {
+            match addr {
+                CompactAddr::V4(addr) => {
+                    message.added.extend_from_slice(addr.as_bytes());
+                    message.added_f.push(flags.bits());
+                }
+                CompactAddr::V6(addr) => {
+                    message.added6.extend_from_slice(addr.as_bytes());
+                    message.added6_f.push(flags.bits());
+                }
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for addr in previous
+            .iter()
+            .filter(|addr| !current_set.contains(addr) && seen.insert(**addr))
+            .take(self.max_dropped)
+        {
+            match addr {
+                CompactAddr::V4(addr) => message.dropped.extend_from_slice(addr.as_bytes()),
+                CompactAddr::V6(addr) => message.dropped6.extend_from_slice(addr.as_bytes()),
+            }
+        }
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{compact_v4, compact_v6};
+
+    #[test]
+    fn flags() {
+        let flags = PexFlags::SEED | PexFlags::UTP;
+        assert_eq!(flags.bits(), 0x06);
+        assert!(flags.contains(PexFlags::SEED));
+        assert!(!flags.contains(PexFlags::SEED | PexFlags::REACHABLE));
+        assert_eq!(PexFlags::from_bits(0x10), PexFlags::REACHABLE);
+    }
+
+    #[test]
+    fn added_and_dropped() {
+        let previous = [compact_v4(1), compact_v4(2), compact_v6(1)];
+        let current = [
+            (compact_v4(2), PexFlags::default()),
+            (compact_v4(3), PexFlags::SEED),
+            (compact_v6(2), PexFlags::UTP),
+        ];
+        let message = PexBuilder::new().build(&previous, &current);
+        assert_eq!(message.added, compact_v4(3).as_bytes());
+        assert_eq!(message.added_f, [0x02]);
+        assert_eq!(message.added6, compact_v6(2).as_bytes());
+        assert_eq!(message.added6_f, [0x04]);
+        assert_eq!(message.dropped, compact_v4(1).as_bytes());
+        assert_eq!(message.dropped6, compact_v6(1).as_bytes());
+
+        assert_eq!(
+            message.apply(&previous),
+            [compact_v4(2), compact_v4(3), compact_v6(2)]
+        );
+    }
+
+    #[test]
+    fn unchanged_is_empty() {
+        let peers = [compact_v4(1), compact_v6(1)];
+        let current: Vec<_> = peers.iter().map(|a| (*a, PexFlags::default())).collect();
+        let message = PexBuilder::new().build(&peers, &current);
+        assert!(message.is_empty());
+        assert_eq!(message.apply(&peers), peers);
+    }
+
+    #[test]
+    fn duplicates_are_sent_once() {
+        let current = [
+            (compact_v4(1), PexFlags::SEED),
+            (compact_v4(1), PexFlags::UTP),
+        ];
+        let message = PexBuilder::new().build(&[compact_v6(1), compact_v6(1)], &current);
+        assert_eq!(message.added, compact_v4(1).as_bytes());
+        assert_eq!(message.added_f, [0x02]);
+        assert_eq!(message.dropped6, compact_v6(1).as_bytes());
+    }
+
+    #[test]
+    fn caps_defer_peers() {
+        let current: Vec<_> = (1..=5)
+            .map(|n| (compact_v4(n), PexFlags::default()))
+            .collect();
+        let previous: Vec<_> = (1..=3).map(compact_v6).collect();
+        let mut builder = PexBuilder::new();
+        builder.max_added(2).max_dropped(1);
+
+        let message = builder.build(&previous, &current);
+        assert_eq!(blob::len_v4(&message.added), 2);
+        assert_eq!(blob::len_v6(&message.dropped6), 1);
+
+        let mut known = previous;
+        let mut messages = 0;
+        loop {
+            let message = builder.build(&known, &current);
+            if message.is_empty() {
+                break;
+            }
+            known = message.apply(&known);
+            messages += 1;
+        }
+        assert_eq!(messages, 3);
+        let mut known_sorted = known.clone();
+        known_sorted.sort();
+        let mut expected: Vec<_> = current.iter().map(|(addr, _)| *addr).collect();
+        expected.sort();
+        assert_eq!(known_sorted, expected);
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Randomized operations on blobs of compact addresses.
+//!
+//! The `_with` functions take a source of randomness as a function which
+//! returns a uniformly distributed value less than the given bound. With the
+//! `rand` feature, the other functions take a [`rand::Rng`].
+
+use crate::{blob, CompactAddrV4Info, CompactAddrV6Info};
+#[cfg(feature = "rand")]
+use rand::{Rng, RngExt};
+use std::{
+    collections::HashMap,
+    net::{SocketAddrV4, SocketAddrV6},
+};
+
+/// Shuffles the compact IPv4 addresses in a blob in place.
+///
+/// `random(bound)` must return a value less than `bound`. Trailing bytes which
+/// do not form a whole address are not moved.
+///
+/// # Panics
+///
+/// Panics if `random` returns a value which is not less than the bound.
+pub fn shuffle_v4_with<F>(blob: &mut [u8], random: F)
+where
+    F: FnMut(usize) -> usize,
+{
+    shuffle(blob::as_chunks_mut_v4(blob).0, random);
+}
+
+/// Shuffles the compact IPv6 addresses in a blob in place.
+///
+/// `random(bound)` must return a value less than `bound`. Trailing bytes which
+/// do not form a whole address are not moved.
+///
+/// # Panics
+///
+/// Panics if `random` returns a value which is not less than the bound.
+pub fn shuffle_v6_with<F>(blob: &mut [u8], random: F)
+where
+    F: FnMut(usize) -> usize,
+{
+    shuffle(blob::as_chunks_mut_v6(blob).0, random);
+}
+
+/// Shuffles the compact IPv4 addresses in a blob in place.
+///
+/// Trailing bytes which do not form a whole address are not moved.
+#[cfg(feature = "rand")]
+pub fn shuffle_v4<R: Rng + ?Sized>(blob: &mut [u8], rng: &mut R) {
+    shuffle_v4_with(blob, |bound| rng.random_range(0..bound));
+}
+
+/// Shuffles the compact IPv6 addresses in a blob in place.
+///
+/// Trailing bytes which do not form a whole address are not moved.
+#[cfg(feature = "rand")]
+pub fn shuffle_v6<R: Rng + ?Sized>(blob: &mut [u8], rng: &mut R) {
+    shuffle_v6_with(blob, |bound| rng.random_range(0..bound));
+}
+
+/// Returns up to `k` distinct compact IPv4 addresses chosen at random from a
+/// blob.
+///
+/// Only the chosen addresses are decoded. If the blob has fewer than `k`
+/// addresses, all of the addresses are returned in a random order.
+/// `random(bound)` must return a value less than `bound`.
+///
+/// # Panics
+///
+/// Panics if `random` returns a value which is not less than the bound.
+pub fn sample_v4_with<F>(blob: &[u8], k: usize, random: F) -> Vec<SocketAddrV4>
+where
+    F: FnMut(usize) -> usize,
+{
+    let (entries, _) = blob::as_chunks_v4(blob);
+    sample_indices(entries.len(), k, random)
+        .into_iter()
+        .map(|idx| SocketAddrV4::from_compact(entries[idx]))
+        .collect()
+}
+
+/// Returns up to `k` distinct compact IPv6 addresses chosen at random from a
+/// blob.
+///
+/// Only the chosen addresses are decoded. If the blob has fewer than `k`
+/// addresses, all of the addresses are returned in a random order.
+/// `random(bound)` must return a value less than `bound`.
+///
+/// # Panics
+///
+/// Panics if `random` returns a value which is not less than the bound.
+pub fn sample_v6_with<F>(blob: &[u8], k: usize, random: F) -> Vec<SocketAddrV6>
+where
+    F: FnMut(usize) -> usize,
+{
+    let (entries, _) = blob::as_chunks_v6(blob);
+    sample_indices(entries.len(), k, random)
+        .into_iter()
+        .map(|idx| SocketAddrV6::from_compact(entries[idx]))
+        .collect()
+}
+
+/// Returns up to `k` distinct compact IPv4 addresses chosen at random from a
+/// blob.
+///
+/// Only the chosen addresses are decoded. If the blob has fewer than `k`
+/// addresses, all of the addresses are returned in a random order.
+#[cfg(feature = "rand")]
+pub fn sample_v4<R: Rng + ?Sized>(blob: &[u8], k: usize, rng: &mut R) -> Vec<SocketAddrV4> {
+    sample_v4_with(blob, k, |bound| rng.random_range(0..bound))
+}
+
+/// Returns up to `k` distinct compact IPv6 addresses chosen at random from a
+/// blob.
+///
+/// Only the chosen addresses are decoded. If the blob has fewer than `k`
+/// addresses, all of the addresses are returned in a random order.
+#[cfg(feature = "rand")]
+pub fn sample_v6<R: Rng + ?Sized>(blob: &[u8], k: usize, rng: &mut R) -> Vec<SocketAddrV6> {
+    sample_v6_with(blob, k, |bound| rng.random_range(0..bound))
+}
+
+/// Fisher-Yates shuffle.
+fn shuffle<T, F>(entries: &mut [T], mut random: F)
+where
+    F: FnMut(usize) -> usize,
+{
+    for idx in (1..entries.len()).rev() {
+        let other = random(idx + 1);
+        entries.swap(idx, other);
+    }
+}
+
+/// Partial Fisher-Yates shuffle of the indices `0..len`.
+///
+/// Only the swapped indices are stored, so memory use is proportional to `k`.
+fn sample_indices<F>(len: usize, k: usize, mut random: F) -> Vec<usize>
+where
+    F: FnMut(usize) -> usize,
+{
+    let k = k.min(len);
+    let mut swapped: HashMap<usize, usize> = HashMap::with_capacity(k);
+    let mut indices = Vec::with_capacity(k);
+    for idx in 0..k {
+        let other = idx + random(len - idx);
+        let value = swapped.get(&other).copied().unwrap_or(other);
+        let current = swapped.get(&idx).copied().unwrap_or(idx);
+        swapped.insert(other, current);
+        indices.push(value);
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::socket_v4;
+    #[cfg(feature = "rand")]
+    use rand::{rngs::SmallRng, SeedableRng};
+    use std::net::{Ipv4Addr, SocketAddrV6};
+
+    fn blob_v4(len: u8) -> Vec<u8> {
+        blob::encode_v4((0..len).map(|n| socket_v4(n, 80)))
+    }
+
+    #[test]
+    fn shuffle_with() {
+        let mut blob = blob_v4(3);
+        blob.push(0xee);
+        shuffle_v4_with(&mut blob, |bound| bound - 1);
+        assert_eq!(
+            blob::decode_v4_lossy(&blob),
+            vec![socket_v4(0, 80), socket_v4(1, 80), socket_v4(2, 80)]
+        );
+
+        shuffle_v4_with(&mut blob, |_| 0);
+        assert_eq!(
+            blob::decode_v4_lossy(&blob),
+            vec![socket_v4(1, 80), socket_v4(2, 80), socket_v4(0, 80)]
+        );
+        assert_eq!(blob[blob.len() - 1], 0xee);
+
+        let mut blob = blob::encode_v6(vec![
+            SocketAddrV6::new(Ipv4Addr::new(1, 2, 3, 4).to_ipv6_mapped(), 80, 0, 0),
+            SocketAddrV6::new(Ipv4Addr::new(5, 6, 7, 8).to_ipv6_mapped(), 80, 0, 0),
+        ]);
+        let expected = [&blob[blob::V6_LEN..], &blob[..blob::V6_LEN]].concat();
+        shuffle_v6_with(&mut blob, |_| 0);
+        assert_eq!(blob, expected);
+    }
+
+    #[test]
+    #[should_panic]
+    fn shuffle_with_out_of_bounds() {
+        shuffle_v4_with(&mut blob_v4(3), |bound| bound);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn shuffle_is_permutation() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut blob = blob_v4(50);
+        shuffle_v4(&mut blob, &mut rng);
+        let mut addrs = blob::decode_v4(&blob).unwrap();
+        assert_ne!(addrs, (0..50).map(|n| socket_v4(n, 80)).collect::<Vec<_>>());
+        addrs.sort();
+        assert_eq!(addrs, (0..50).map(|n| socket_v4(n, 80)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn sample_with() {
+        let blob = blob_v4(3);
+        assert_eq!(sample_v4_with(&blob, 0, |_| 0), vec![]);
+        assert_eq!(
+            sample_v4_with(&blob, 2, |_| 0),
+            vec![socket_v4(0, 80), socket_v4(1, 80)]
+        );
+        assert_eq!(
+            sample_v4_with(&blob, 2, |bound| bound - 1),
+            vec![socket_v4(2, 80), socket_v4(0, 80)]
+        );
+        assert_eq!(
+            sample_v4_with(&blob, 10, |bound| bound - 1),
+            vec![socket_v4(2, 80), socket_v4(0, 80), socket_v4(1, 80)]
+        );
+        assert_eq!(sample_v4_with(&[], 2, |_| unreachable!()), vec![]);
+        assert_eq!(sample_v6_with(&[0; blob::V6_LEN + 1], 2, |_| 0).len(), 1);
+    }
+
+    #[test]
+    fn sample_indices_are_distinct() {
+        let mut state = 7usize;
+        let indices = sample_indices(100, 100, |bound| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1);
+            (state >> 33) % bound
+        });
+        let mut sorted = indices.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..100).collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn sample_is_subset() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        let blob = blob_v4(50);
+        let mut addrs = sample_v4(&blob, 10, &mut rng);
+        assert_eq!(addrs.len(), 10);
+        addrs.sort();
+        addrs.dedup();
+        assert_eq!(addrs.len(), 10);
+        assert!(addrs.iter().all(|addr| addr.ip().octets()[3] < 50));
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Resume data peer fields.
+//!
+//! uTorrent and libtorrent resume data store the known peers of a torrent in
+//! the `peers` and `peers6` fields as concatenated compact IPv4 and IPv6
+//! addresses.
+//!
+//! Fields are read in the lossy decode mode. Trailing bytes which do not form
+//! a whole address are kept so that writing the fields back reproduces the
+//! original bytes.
+
+use crate::{
+    blob::{self, V4_LEN, V6_LEN},
+    CompactAddrV4Info, CompactAddrV6Info,
+};
+use std::net::{SocketAddrV4, SocketAddrV6};
+
+/// The peers of a torrent's resume data.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ResumePeers {
+    v4: Vec<SocketAddrV4>,
+    v6: Vec<SocketAddrV6>,
+    v4_trailing: Vec<u8>,
+    v6_trailing: Vec<u8>,
+}
+
+impl ResumePeers {
+    /// Instantiates an empty set of resume data peers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the `peers` and `peers6` fields.
+    ///
+    /// The order of the addresses is preserved.
+    pub fn read(peers: &[u8], peers6: &[u8]) -> Self {
+        let v4_trailing = peers.len() % V4_LEN;
+        let v6_trailing = peers6.len() % V6_LEN;
+        Self {
+            v4: blob::decode_v4_lossy(peers),
+            v6: blob::decode_v6_lossy(peers6),
+            v4_trailing: peers[peers.len() - v4_trailing..].to_vec(),
+            v6_trailing: peers6[peers6.len() - v6_trailing..].to_vec(),
+        }
+    }
+
+    /// Returns the `peers` field.
+    ///
+    /// Any trailing bytes which were read are appended after the addresses.
+    pub fn write_peers(&self) -> Vec<u8> {
+        let mut field = Vec::with_capacity(self.v4.len() * V4_LEN + self.v4_trailing.len());
+        for addr in &self.v4 {
+            field.extend_from_slice(&addr.to_compact_address());
+        }
+        field.extend_from_slice(&self.v4_trailing);
+        field
+    }
+
+    /// Returns the `peers6` field.
+    ///
+    /// Any trailing bytes which were read are appended after the addresses.
+    pub fn write_peers6(&self) -> Vec<u8> {
+        let mut field = Vec::with_capacity(self.v6.len() * V6_LEN + self.v6_trailing.len());
+        for addr in &self.v6 {
+            field.extend_from_slice(&addr.to_compact_address());
+        }
+        field.extend_from_slice(&self.v6_trailing);
+        field
+    }
+
+    /// Returns the IPv4 peers.
+    pub fn v4(&self) -> &[SocketAddrV4] {
+        &self.v4
+    }
+
+    /// Returns the IPv6 peers.
+    pub fn v6(&self) -> &[SocketAddrV6] {
+        &self.v6
+    }
+
+    /// Returns the IPv4 peers for modification.
+    pub fn v4_mut(&mut self) -> &mut Vec<SocketAddrV4> {
+        &mut self.v4
+    }
+
+    /// Returns the IPv6 peers for modification.
+    pub fn v6_mut(&mut self) -> &mut Vec<SocketAddrV6> {
+        &mut self.v6
+    }
+
+    /// Returns the trailing bytes of the `peers` field which do not form a
+    /// whole address.
+    pub fn v4_trailing(&self) -> &[u8] {
+        &self.v4_trailing
+    }
+
+    /// Returns the trailing bytes of the `peers6` field which do not form a
+    /// whole address.
+    pub fn v6_trailing(&self) -> &[u8] {
+        &self.v6_trailing
+    }
+
+    /// Returns true if either field had trailing bytes.
+    pub fn has_trailing(&self) -> bool {
+        !self.v4_trailing.is_empty() || !self.v6_trailing.is_empty()
+    }
+
+    /// Removes the trailing bytes so the written fields only contain whole
+    /// addresses.
+    pub fn clear_trailing(&mut self) {
+        self.v4_trailing.clear();
+        self.v6_trailing.clear();
+    }
+
+    /// Returns the number of peers.
+    pub fn len(&self) -> usize {
+        self.v4.len() + self.v6.len()
+    }
+
+    /// Returns true if there are no peers.
+    pub fn is_empty(&self) -> bool {
+        self.v4.is_empty() && self.v6.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn read_write_round_trip() {
+        let peers = [1, 2, 3, 4, 0, 80, 5, 6, 7, 8, 0, 81];
+        let mut peers6 = vec![0; V6_LEN];
+        peers6[15] = 1;
+        peers6[17] = 80;
+        let resume = ResumePeers::read(&peers, &peers6);
+        assert_eq!(
+            resume.v4(),
+            [
+                SocketAddrV4::new(Ipv4Addr::new(1, 2, 3, 4), 80),
+                SocketAddrV4::new(Ipv4Addr::new(5, 6, 7, 8), 81)
+            ]
+        );
+        assert_eq!(
+            resume.v6(),
+            [SocketAddrV6::new(Ipv6Addr::LOCALHOST, 80, 0, 0)]
+        );
+        assert_eq!(resume.len(), 3);
+        assert!(!resume.has_trailing());
+        assert_eq!(resume.write_peers(), peers);
+        assert_eq!(resume.write_peers6(), peers6);
+    }
+
+    #[test]
+    fn trailing_bytes_are_kept() {
+        let peers = [1, 2, 3, 4, 0, 80, 9, 9];
+        let peers6 = [7; 5];
+        let mut resume = ResumePeers::read(&peers, &peers6);
+        assert_eq!(resume.v4().len(), 1);
+        assert!(resume.v6().is_empty());
+        assert_eq!(resume.v4_trailing(), [9, 9]);
+        assert_eq!(resume.v6_trailing(), [7; 5]);
+        assert!(resume.has_trailing());
+        assert_eq!(resume.write_peers(), peers);
+        assert_eq!(resume.write_peers6(), peers6);
+
+        resume.clear_trailing();
+        assert!(!resume.has_trailing());
+        assert_eq!(resume.write_peers(), peers[..V4_LEN]);
+        assert!(resume.write_peers6().is_empty());
+    }
+
+    #[test]
+    fn modify_peers() {
+        let mut resume = ResumePeers::new();
+        assert!(resume.is_empty());
+        resume
+            .v4_mut()
+            .push(SocketAddrV4::new(Ipv4Addr::new(1, 2, 3, 4), 80));
+        resume
+            .v6_mut()
+            .push(SocketAddrV6::new(Ipv6Addr::LOCALHOST, 80, 0, 0));
+        assert_eq!(resume.write_peers(), [1, 2, 3, 4, 0, 80]);
+        assert_eq!(resume.write_peers6().len(), V6_LEN);
+        assert_eq!(
+            ResumePeers::read(&resume.write_peers(), &resume.write_peers6()),
+            resume
+        );
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! SipHash-2-4.
+//!
+//! The standard library's hashers do not guarantee a stable output across
+//! releases, so values which are persisted or compared across processes use
+//! this implementation.
+
+/// Returns the SipHash-2-4 of the data with the 128-bit key.
+pub(crate) fn hash(key: &[u8; 16], data: &[u8]) -> u64 {
+    let mut hasher = Hasher::new(key);
+    hasher.write(data);
+    hasher.finish()
+}
+
+/// An incremental SipHash-2-4 hasher.
+#[derive(Clone, Debug)]
+pub(crate) struct Hasher {
+    v: [u64; 4],
+    tail: [u8; 8],
+    tail_len: usize,
+    len: usize,
+}
+
+impl Hasher {
+    pub(crate) fn new(key: &[u8; 16]) -> Self {
+        let mut k0: [u8; 8] = [0; 8];
+        k0.copy_from_slice(&key[0..8]);
+        let mut k1: [u8; 8] = [0; 8];
+        k1.copy_from_slice(&key[8..16]);
+        let (k0, k1) = (u64::from_le_bytes(k0), u64::from_le_bytes(k1));
+
+        Self {
+            v: [
+                k0 ^ 0x736f_6d65_7073_6575,
+                k1 ^ 0x646f_7261_6e64_6f6d,
+                k0 ^ 0x6c79_6765_6e65_7261,
+                k1 ^ 0x7465_6462_7974_6573,
+            ],
+            tail: [0; 8],
+            tail_len: 0,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn write(&mut self, mut data: &[u8]) {
+        self.len = self.len.wrapping_add(data.len());
+
+        if self.tail_len > 0 {
+            let n = (8 - self.tail_len).min(data.len());
+            self.tail[self.tail_len..self.tail_len + n].copy_from_slice(&data[..n]);
+            self.tail_len += n;
+            data = &data[n..];
+            if self.tail_len < 8 {
+                return;
+            }
+            compress(&mut self.v, u64::from_le_bytes(self.tail));
+            self.tail_len = 0;
+        }
+
+        let mut chunks = data.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut m: [u8; 8] = [0; 8];
+            m.copy_from_slice(chunk);
+            compress(&mut self.v, u64::from_le_bytes(m));
+        }
+
+        let remainder = chunks.remainder();
+        self.tail[..remainder.len()].copy_from_slice(remainder);
+        self.tail_len = remainder.len();
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        let mut v = self.v;
+
+        let mut last: [u8; 8] = [0; 8];
+        last[..self.tail_len].copy_from_slice(&self.tail[..self.tail_len]);
+        last[7] = self.len as u8;
+        compress(&mut v, u64::from_le_bytes(last));
+
+        v[2] ^= 0xff;
+        for _ in 0..4 {
+            round(&mut v);
+        }
+        v[0] ^ v[1] ^ v[2] ^ v[3]
+    }
+}
+
+fn compress(v: &mut [u64; 4], m: u64) {
+    v[3] ^= m;
+    round(v);
+    round(v);
+    v[0] ^= m;
+}
+
+fn round(v: &mut [u64; 4]) {
+    v[0] = v[0].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(13);
+    v[1] ^= v[0];
+    v[0] = v[0].rotate_left(32);
+    v[2] = v[2].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(16);
+    v[3] ^= v[2];
+    v[0] = v[0].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(21);
+    v[3] ^= v[0];
+    v[2] = v[2].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(17);
+    v[1] ^= v[2];
+    v[2] = v[2].rotate_left(32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> [u8; 16] {
+        let mut key: [u8; 16] = [0; 16];
+        for (i, b) in key.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        key
+    }
+
+    #[test]
+    fn reference_vectors() {
+        let data = (0..64).collect::<Vec<u8>>();
+        assert_eq!(hash(&key(), &data[..0]), 0x726f_db47_dd0e_0e31);
+        assert_eq!(hash(&key(), &data[..15]), 0xa129_ca61_49be_45e5);
+    }
+
+    #[test]
+    fn incremental_writes() {
+        let data = (0..64).collect::<Vec<u8>>();
+        for split in 0..data.len() {
+            let mut hasher = Hasher::new(&key());
+            hasher.write(&data[..split]);
+            hasher.write(&data[split..]);
+            assert_eq!(hasher.finish(), hash(&key(), &data));
+        }
+    }
+}
@@ -0,0 +1,242 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Lists of compact addresses with inline storage.
+//!
+//! DHT node responses and PEX deltas usually have only a few addresses. A
+//! [`SmallCompactAddrList`] stores up to [`INLINE_LEN`] addresses without
+//! allocating.
+
+use crate::{
+    blob::{self, DecodeError, V4_LEN, V6_LEN},
+    list::CompactAddrList,
+    CompactAddr, CompactAddrV4, CompactAddrV6,
+};
+use smallvec::SmallVec;
+use std::{convert::TryFrom, iter::FromIterator, ops::Index, slice};
+
+/// The number of addresses stored inline.
+pub const INLINE_LEN: usize = 8;
+
+/// An ordered list of IPv4 and IPv6 compact addresses with inline storage.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SmallCompactAddrList {
+    addrs: SmallVec<[CompactAddr; INLINE_LEN]>,
+}
+
+impl SmallCompactAddrList {
+    /// Instantiates an empty list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Instantiates from blobs of compact IPv4 and IPv6 addresses.
+    ///
+    /// The IPv4 addresses are followed by the IPv6 addresses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either blob length is not a multiple of its compact
+    /// address length.
+    pub fn from_blobs(v4: &[u8], v6: &[u8]) -> Result<Self, DecodeError> {
+        if !v4.len().is_multiple_of(V4_LEN) {
+            return Err(DecodeError::invalid_length(v4.len(), V4_LEN));
+        }
+        if !v6.len().is_multiple_of(V6_LEN) {
+            return Err(DecodeError::invalid_length(v6.len(), V6_LEN));
+        }
+
+        let mut addrs = SmallVec::with_capacity(v4.len() / V4_LEN + v6.len() / V6_LEN);
+        addrs.extend(v4.chunks_exact(V4_LEN).map(|chunk| {
+            CompactAddr::V4(CompactAddrV4::try_from(chunk).expect("chunk is a compact address"))
+        }));
+        addrs.extend(v6.chunks_exact(V6_LEN).map(|chunk| {
+            CompactAddr::V6(CompactAddrV6::try_from(chunk).expect("chunk is a compact address"))
+        }));
+        Ok(Self { addrs })
+    }
+
+    /// Returns blobs of the compact IPv4 and IPv6 addresses.
+    ///
+    /// The order of the addresses within each family is preserved.
+    pub fn to_blobs(&self) -> (Vec<u8>, Vec<u8>) {
+        blob::partition_encode(self.addrs.iter().copied())
+    }
+
+    /// Returns the addresses.
+    pub fn as_slice(&self) -> &[CompactAddr] {
+        &self.addrs
+    }
+
+    /// Returns the number of addresses.
+    pub fn len(&self) -> usize {
+        self.addrs.len()
+    }
+
+    /// Returns true if there are no addresses.
+    pub fn is_empty(&self) -> bool {
+        self.addrs.is_empty()
+    }
+
+    /// Returns true if the addresses are stored on the heap.
+    pub fn spilled(&self) -> bool {
+        self.addrs.spilled()
+    }
+
+    /// Returns the address at the index.
+    pub fn get(&self, idx: usize) -> Option<CompactAddr> {
+        self.addrs.get(idx).copied()
+    }
+
+    /// Returns an iterator over the addresses.
+    pub fn iter(&self) -> slice::Iter<'_, CompactAddr> {
+        self.addrs.iter()
+    }
+
+    /// Appends an address.
+    pub fn push<A: Into<CompactAddr>>(&mut self, addr: A) {
+        self.addrs.push(addr.into());
+    }
+
+    /// Removes all addresses.
+    pub fn clear(&mut self) {
+        self.addrs.clear();
+    }
+}
+
+impl From<CompactAddrList> for SmallCompactAddrList {
+    fn from(list: CompactAddrList) -> Self {
+        Self {
+            addrs: SmallVec::from_vec(list.into()),
+        }
+    }
+}
+
+impl From<SmallCompactAddrList> for CompactAddrList {
+    fn from(list: SmallCompactAddrList) -> Self {
+        CompactAddrList::from(list.addrs.into_vec())
+    }
+}
+
+impl AsRef<[CompactAddr]> for SmallCompactAddrList {
+    fn as_ref(&self) -> &[CompactAddr] {
+        &self.addrs
+    }
+}
+
+impl Index<usize> for SmallCompactAddrList {
+    type Output = CompactAddr;
+
+    fn index(&self, idx: usize) -> &Self::Output {
+        &self.addrs[idx]
+    }
+}
+
+impl<'a> IntoIterator for &'a SmallCompactAddrList {
+    type Item = &'a CompactAddr;
+    type IntoIter = slice::Iter<'a, CompactAddr>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.addrs.iter()
+    }
+}
+
+impl IntoIterator for SmallCompactAddrList {
+    type Item = CompactAddr;
+    type IntoIter = smallvec::IntoIter<[CompactAddr; INLINE_LEN]>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.addrs.into_iter()
+    }
+}
+
+impl<A: Into<CompactAddr>> FromIterator<A> for SmallCompactAddrList {
+    fn from_iter<I: IntoIterator<Item = A>>(iter: I) -> Self {
+        Self {
+            addrs: iter.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl<A: Into<CompactAddr>> Extend<A> for SmallCompactAddrList {
+    fn extend<I: IntoIterator<Item = A>>(&mut self, iter: I) {
+        self.addrs.extend(iter.into_iter().map(Into::into));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::compact_v4;
+
+    fn v6(n: u8) -> CompactAddr {
+        CompactAddr::V6(CompactAddrV6::from([n; V6_LEN]))
+    }
+
+    #[test]
+    fn inline_until_full() {
+        let mut list = SmallCompactAddrList::new();
+        for n in 0..INLINE_LEN as u8 {
+            list.push(compact_v4(n));
+        }
+        assert_eq!(list.len(), INLINE_LEN);
+        assert!(!list.spilled());
+        list.push(v6(1));
+        assert!(list.spilled());
+        assert_eq!(list.get(INLINE_LEN), Some(v6(1)));
+        assert_eq!(list.get(INLINE_LEN + 1), None);
+        list.clear();
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn blobs_round_trip() {
+        let (b4, b6) = CompactAddrList::from(vec![v6(1), compact_v4(1)]).to_blobs();
+        let list = SmallCompactAddrList::from_blobs(&b4, &b6).unwrap();
+        assert_eq!(list.as_slice(), [compact_v4(1), v6(1)]);
+        assert_eq!(list.to_blobs(), (b4, b6));
+        assert!(!list.spilled());
+
+        assert_eq!(
+            SmallCompactAddrList::from_blobs(&[0; 5], &[]),
+            Err(DecodeError::invalid_length(5, V4_LEN))
+        );
+        assert_eq!(
+            SmallCompactAddrList::from_blobs(&[], &[0; 19]),
+            Err(DecodeError::invalid_length(19, V6_LEN))
+        );
+    }
+
+    #[test]
+    fn list_conversions() {
+        let list = CompactAddrList::from(vec![compact_v4(1), v6(2)]);
+        let small = SmallCompactAddrList::from(list.clone());
+        assert_eq!(small.as_slice(), list.as_slice());
+        assert_eq!(CompactAddrList::from(small), list);
+    }
+
+    #[test]
+    fn from_iter_and_extend() {
+        let mut list: SmallCompactAddrList = (0..INLINE_LEN as u8).map(compact_v4).collect();
+        assert!(!list.spilled());
+        list.extend(vec![CompactAddrV6::from([1; V6_LEN])]);
+        assert!(list.spilled());
+        assert_eq!(list.get(INLINE_LEN), Some(v6(1)));
+    }
+
+    #[test]
+    fn index_and_into_iter() {
+        let list: SmallCompactAddrList = vec![compact_v4(1), v6(1)].into_iter().collect();
+        assert_eq!(list[0], compact_v4(1));
+        assert_eq!(
+            (&list).into_iter().copied().collect::<Vec<_>>(),
+            [compact_v4(1), v6(1)]
+        );
+        assert_eq!(list.into_iter().collect::<Vec<_>>(), [compact_v4(1), v6(1)]);
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversions between compact addresses and raw `libc` socket addresses.
+//!
+//! Compact addresses and socket address structs both store the IP address and
+//! port in network byte order, so the bytes are copied without conversion.
+//! The IPv6 flow info and scope ID are not part of a compact address and are
+//! set to zero.
+
+use crate::{CompactAddr, CompactAddrV4, CompactAddrV6};
+use libc::{
+    sa_family_t, sockaddr_in, sockaddr_in6, sockaddr_storage, socklen_t, AF_INET, AF_INET6,
+};
+use std::{ffi::CString, mem, ptr};
+
+impl From<CompactAddrV4> for sockaddr_in {
+    fn from(addr: CompactAddrV4) -> Self {
+        let bytes = addr.as_bytes();
+        // SAFETY: sockaddr_in is a plain C struct for which all zeros is valid.
+        let mut sin: sockaddr_in = unsafe { mem::zeroed() };
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        ))]
+        {
+            sin.sin_len = mem::size_of::<sockaddr_in>() as u8;
+        }
+        sin.sin_family = AF_INET as sa_family_t;
+        sin.sin_port = u16::from_ne_bytes([bytes[4], bytes[5]]);
+        sin.sin_addr.s_addr = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        sin
+    }
+}
+
+impl From<&sockaddr_in> for CompactAddrV4 {
+    fn from(sin: &sockaddr_in) -> Self {
+        let mut bytes: [u8; 6] = [0; 6];
+        bytes[..4].copy_from_slice(&sin.sin_addr.s_addr.to_ne_bytes());
+        bytes[4..].copy_from_slice(&sin.sin_port.to_ne_bytes());
+        CompactAddrV4::new(bytes)
+    }
+}
+
+impl From<CompactAddrV6> for sockaddr_in6 {
+    fn from(addr: CompactAddrV6) -> Self {
+        let bytes = addr.as_bytes();
+        // SAFETY: sockaddr_in6 is a plain C struct for which all zeros is valid.
+        let mut sin6: sockaddr_in6 = unsafe { mem::zeroed() };
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        ))]
+        {
+            sin6.sin6_len = mem::size_of::<sockaddr_in6>() as u8;
+        }
+        sin6.sin6_family = AF_INET6 as sa_family_t;
+        sin6.sin6_port = u16::from_ne_bytes([bytes[16], bytes[17]]);
+        sin6.sin6_addr.s6_addr.copy_from_slice(&bytes[..16]);
+        sin6
+    }
+}
+
+impl From<&sockaddr_in6> for CompactAddrV6 {
+    fn from(sin6: &sockaddr_in6) -> Self {
+        let mut bytes: [u8; 18] = [0; 18];
+        bytes[..16].copy_from_slice(&sin6.sin6_addr.s6_addr);
+        bytes[16..].copy_from_slice(&sin6.sin6_port.to_ne_bytes());
+        CompactAddrV6::new(bytes)
+    }
+}
+
+impl CompactAddr {
+    /// Returns the socket address as a `sockaddr_storage` and its length.
+    pub fn to_sockaddr_storage(&self) -> (sockaddr_storage, socklen_t) {
+        // SAFETY: sockaddr_storage is a plain C struct for which all zeros is
+        // valid.
+        let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+        let len = match self {
+            CompactAddr::V4(addr) => {
+                // SAFETY: sockaddr_storage is large enough and suitably
+                // aligned for any socket address.
+                unsafe {
+                    ptr::write(
+                        (&mut storage as *mut sockaddr_storage).cast::<sockaddr_in>(),
+                        sockaddr_in::from(*addr),
+                    );
+                }
+                mem::size_of::<sockaddr_in>()
+            }
+            CompactAddr::V6(addr) => {
+                // SAFETY: sockaddr_storage is large enough and suitably
+                // aligned for any socket address.
+                unsafe {
+                    ptr::write(
+                        (&mut storage as *mut sockaddr_storage).cast::<sockaddr_in6>(),
+                        sockaddr_in6::from(*addr),
+                    );
+                }
+                mem::size_of::<sockaddr_in6>()
+            }
+        };
+        (storage, len as socklen_t)
+    }
+
+    /// Instantiates a compact address from a `sockaddr_storage`.
+    ///
+    /// Returns `None` if the address family is not `AF_INET` or `AF_INET6`.
+    pub fn from_sockaddr_storage(storage: &sockaddr_storage) -> Option<Self> {
+        match i32::from(storage.ss_family) {
+            AF_INET => {
+                // SAFETY: the family is AF_INET so the storage holds a
+                // sockaddr_in.
+                let sin = unsafe { &*(storage as *const sockaddr_storage).cast::<sockaddr_in>() };
+                Some(CompactAddr::V4(CompactAddrV4::from(sin)))
+            }
+            AF_INET6 => {
+                // SAFETY: the family is AF_INET6 so the storage holds a
+                // sockaddr_in6.
+                let sin6 = unsafe { &*(storage as *const sockaddr_storage).cast::<sockaddr_in6>() };
+                Some(CompactAddr::V6(CompactAddrV6::from(sin6)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns the index of the network interface with the name.
+pub(crate) fn interface_index(name: &str) -> Option<u32> {
+    let name = CString::new(name).ok()?;
+    // SAFETY: The name is a valid NUL-terminated string.
+    let idx = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if idx == 0 {
+        None
+    } else {
+        Some(idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libc::AF_UNIX;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn sockaddr_in_round_trip() {
+        let addr = CompactAddrV4::from([192, 0, 2, 1, 0x1a, 0xe1]);
+        let sin = sockaddr_in::from(addr);
+        assert_eq!(i32::from(sin.sin_family), AF_INET);
+        assert_eq!(sin.sin_port, 6881u16.to_be());
+        assert_eq!(
+            sin.sin_addr.s_addr,
+            u32::from(Ipv4Addr::new(192, 0, 2, 1)).to_be()
+        );
+        assert_eq!(CompactAddrV4::from(&sin), addr);
+    }
+
+    #[test]
+    fn sockaddr_in6_round_trip() {
+        let mut bytes = [0; 18];
+        bytes[0] = 0x20;
+        bytes[1] = 0x01;
+        bytes[15] = 1;
+        bytes[17] = 80;
+        let addr = CompactAddrV6::from(bytes);
+        let sin6 = sockaddr_in6::from(addr);
+        assert_eq!(i32::from(sin6.sin6_family), AF_INET6);
+        assert_eq!(sin6.sin6_port, 80u16.to_be());
+        assert_eq!(sin6.sin6_addr.s6_addr, bytes[..16]);
+        assert_eq!(sin6.sin6_flowinfo, 0);
+        assert_eq!(sin6.sin6_scope_id, 0);
+        assert_eq!(CompactAddrV6::from(&sin6), addr);
+    }
+
+    #[test]
+    fn sockaddr_storage_round_trip() {
+        for addr in ["1.2.3.4:80", "[2001:db8::1]:443"] {
+            let addr: CompactAddr = addr.parse().unwrap();
+            let (storage, len) = addr.to_sockaddr_storage();
+            let expected = match addr {
+                CompactAddr::V4(_) => mem::size_of::<sockaddr_in>(),
+                CompactAddr::V6(_) => mem::size_of::<sockaddr_in6>(),
+            };
+            assert_eq!(len as usize, expected);
+            assert_eq!(CompactAddr::from_sockaddr_storage(&storage), Some(addr));
+        }
+    }
+
+    #[test]
+    fn sockaddr_storage_unknown_family() {
+        // SAFETY: sockaddr_storage is a plain C struct for which all zeros is
+        // valid.
+        let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+        assert_eq!(CompactAddr::from_sockaddr_storage(&storage), None);
+        storage.ss_family = AF_UNIX as sa_family_t;
+        assert_eq!(CompactAddr::from_sockaddr_storage(&storage), None);
+    }
+
+    #[test]
+    fn interface_index_invalid() {
+        assert_eq!(interface_index("no-such-interface0"), None);
+        assert_eq!(interface_index("lo\0"), None);
+    }
+}
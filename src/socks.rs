@@ -0,0 +1,334 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! SOCKS5 addresses.
+//!
+//! A SOCKS5 address (RFC 1928) is an address type byte followed by the
+//! address and a big-endian port:
+//!
+//! | `ATYP` | Address                              |
+//! |--------|--------------------------------------|
+//! | `0x01` | 4 byte IPv4 address                  |
+//! | `0x03` | 1 byte length followed by the domain |
+//! | `0x04` | 16 byte IPv6 address                 |
+//!
+//! The IPv4 and IPv6 forms are the `ATYP` byte followed by the compact
+//! address.
+
+use crate::{
+    blob::{V4_LEN, V6_LEN},
+    CompactAddr, CompactAddrV4, CompactAddrV6,
+};
+use std::{
+    convert::TryFrom,
+    error, fmt,
+    net::{SocketAddr, SocketAddrV4, SocketAddrV6},
+    str,
+};
+
+/// The `ATYP` for an IPv4 address.
+pub const ATYP_V4: u8 = 0x01;
+
+/// The `ATYP` for a domain name.
+pub const ATYP_DOMAIN: u8 = 0x03;
+
+/// The `ATYP` for an IPv6 address.
+pub const ATYP_V6: u8 = 0x04;
+
+/// A SOCKS5 address.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SocksAddr {
+    /// An IP address and port.
+    Ip(CompactAddr),
+    /// A domain name and port.
+    Domain(String, u16),
+}
+
+impl SocksAddr {
+    /// Returns the `ATYP` byte.
+    pub fn atyp(&self) -> u8 {
+        match self {
+            SocksAddr::Ip(CompactAddr::V4(_)) => ATYP_V4,
+            SocksAddr::Ip(CompactAddr::V6(_)) => ATYP_V6,
+            SocksAddr::Domain(..) => ATYP_DOMAIN,
+        }
+    }
+
+    /// Returns the port.
+    pub fn port(&self) -> u16 {
+        match self {
+            SocksAddr::Ip(addr) => addr.port(),
+            SocksAddr::Domain(_, port) => *port,
+        }
+    }
+
+    /// Returns the length of the encoding.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            SocksAddr::Ip(addr) => 1 + addr.as_bytes().len(),
+            SocksAddr::Domain(name, _) => 1 + 1 + name.len() + 2,
+        }
+    }
+
+    /// Appends the encoding to the buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the domain name is empty or longer than 255 bytes.
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<(), SocksError> {
+        match self {
+            SocksAddr::Ip(addr) => {
+                buf.push(self.atyp());
+                buf.extend_from_slice(addr.as_bytes());
+            }
+            SocksAddr::Domain(name, port) => {
+                let len = u8::try_from(name.len())
+                    .ok()
+                    .filter(|len| *len > 0)
+                    .ok_or(SocksError::InvalidDomainLength(name.len()))?;
+                buf.push(ATYP_DOMAIN);
+                buf.push(len);
+                buf.extend_from_slice(name.as_bytes());
+                buf.extend_from_slice(&port.to_be_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the domain name is empty or longer than 255 bytes.
+    pub fn to_vec(&self) -> Result<Vec<u8>, SocksError> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.encode(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Decodes an address from the start of the bytes.
+    ///
+    /// Returns the address and the remaining bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `ATYP` is unknown, there are not enough bytes
+    /// for the address, or the domain name is not valid UTF-8.
+    pub fn decode(bytes: &[u8]) -> Result<(Self, &[u8]), SocksError> {
+        let (&atyp, rest) = bytes.split_first().ok_or(SocksError::UnexpectedEnd {
+            expected: 1,
+            actual: 0,
+        })?;
+        let len = match atyp {
+            ATYP_V4 => 1 + V4_LEN,
+            ATYP_V6 => 1 + V6_LEN,
+            ATYP_DOMAIN => 2 + usize::from(rest.first().copied().unwrap_or(0)) + 2,
+            _ => return Err(SocksError::UnknownAtyp(atyp)),
+        };
+        if bytes.len() < len {
+            return Err(SocksError::UnexpectedEnd {
+                expected: len,
+                actual: bytes.len(),
+            });
+        }
+
+        let (entry, rest) = bytes.split_at(len);
+        let addr = match atyp {
+            ATYP_V4 => SocksAddr::Ip(CompactAddr::V4(
+                CompactAddrV4::try_from(&entry[1..]).expect("entry length is checked"),
+            )),
+            ATYP_V6 => SocksAddr::Ip(CompactAddr::V6(
+                CompactAddrV6::try_from(&entry[1..]).expect("entry length is checked"),
+            )),
+            _ => {
+                let (name, port) = entry[2..].split_at(len - 4);
+                if name.is_empty() {
+                    return Err(SocksError::InvalidDomainLength(0));
+                }
+                let name = str::from_utf8(name).map_err(|_| SocksError::InvalidDomain)?;
+                SocksAddr::Domain(name.to_string(), u16::from_be_bytes([port[0], port[1]]))
+            }
+        };
+        Ok((addr, rest))
+    }
+}
+
+impl fmt::Display for SocksAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SocksAddr::Ip(addr) => fmt::Display::fmt(addr, f),
+            SocksAddr::Domain(name, port) => write!(f, "{}:{}", name, port),
+        }
+    }
+}
+
+impl From<CompactAddr> for SocksAddr {
+    fn from(addr: CompactAddr) -> Self {
+        SocksAddr::Ip(addr)
+    }
+}
+
+impl From<CompactAddrV4> for SocksAddr {
+    fn from(addr: CompactAddrV4) -> Self {
+        SocksAddr::Ip(CompactAddr::V4(addr))
+    }
+}
+
+impl From<CompactAddrV6> for SocksAddr {
+    fn from(addr: CompactAddrV6) -> Self {
+        SocksAddr::Ip(CompactAddr::V6(addr))
+    }
+}
+
+impl From<SocketAddrV4> for SocksAddr {
+    fn from(addr: SocketAddrV4) -> Self {
+        SocksAddr::Ip(CompactAddr::from(addr))
+    }
+}
+
+impl From<SocketAddrV6> for SocksAddr {
+    fn from(addr: SocketAddrV6) -> Self {
+        SocksAddr::Ip(CompactAddr::from(addr))
+    }
+}
+
+impl From<SocketAddr> for SocksAddr {
+    fn from(addr: SocketAddr) -> Self {
+        SocksAddr::Ip(CompactAddr::from(addr))
+    }
+}
+
+/// An error when encoding or decoding a SOCKS5 address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SocksError {
+    /// The `ATYP` byte is not a known address type.
+    UnknownAtyp(u8),
+    /// The bytes ended before the address was complete.
+    UnexpectedEnd {
+        /// The number of bytes required.
+        expected: usize,
+        /// The number of bytes available.
+        actual: usize,
+    },
+    /// The domain name is empty or longer than 255 bytes.
+    InvalidDomainLength(usize),
+    /// The domain name is not valid UTF-8.
+    InvalidDomain,
+}
+
+impl fmt::Display for SocksError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SocksError::UnknownAtyp(atyp) => {
+                write!(f, "unknown SOCKS5 address type: {:#04x}", atyp)
+            }
+            SocksError::UnexpectedEnd { expected, actual } => write!(
+                f,
+                "unexpected end of SOCKS5 address: expected {} bytes, found {}",
+                expected, actual
+            ),
+            SocksError::InvalidDomainLength(len) => {
+                write!(f, "invalid SOCKS5 domain name length: {}", len)
+            }
+            SocksError::InvalidDomain => write!(f, "SOCKS5 domain name is not valid UTF-8"),
+        }
+    }
+}
+
+impl error::Error for SocksError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ip_round_trip() {
+        let addr = SocksAddr::from("1.2.3.4:1080".parse::<SocketAddr>().unwrap());
+        let bytes = addr.to_vec().unwrap();
+        assert_eq!(bytes, [ATYP_V4, 1, 2, 3, 4, 0x04, 0x38]);
+        assert_eq!(bytes.len(), addr.encoded_len());
+        assert_eq!(SocksAddr::decode(&bytes), Ok((addr, &[][..])));
+
+        let addr = SocksAddr::from("[::1]:80".parse::<SocketAddrV6>().unwrap());
+        let mut bytes = addr.to_vec().unwrap();
+        assert_eq!(bytes.len(), 19);
+        assert_eq!(bytes[0], ATYP_V6);
+        bytes.push(0xee);
+        assert_eq!(SocksAddr::decode(&bytes), Ok((addr, &[0xee][..])));
+    }
+
+    #[test]
+    fn domain_round_trip() {
+        let addr = SocksAddr::Domain("example.com".to_string(), 443);
+        let bytes = addr.to_vec().unwrap();
+        assert_eq!(bytes[..2], [ATYP_DOMAIN, 11]);
+        assert_eq!(&bytes[2..13], b"example.com");
+        assert_eq!(bytes[13..], [0x01, 0xbb]);
+        assert_eq!(bytes.len(), addr.encoded_len());
+        assert_eq!(addr.port(), 443);
+        assert_eq!(addr.to_string(), "example.com:443");
+        assert_eq!(SocksAddr::decode(&bytes), Ok((addr, &[][..])));
+    }
+
+    #[test]
+    fn encode_invalid_domain() {
+        assert_eq!(
+            SocksAddr::Domain(String::new(), 80).to_vec(),
+            Err(SocksError::InvalidDomainLength(0))
+        );
+        assert_eq!(
+            SocksAddr::Domain("a".repeat(256), 80).to_vec(),
+            Err(SocksError::InvalidDomainLength(256))
+        );
+        assert!(SocksAddr::Domain("a".repeat(255), 80).to_vec().is_ok());
+    }
+
+    #[test]
+    fn decode_malformed() {
+        assert_eq!(
+            SocksAddr::decode(&[]),
+            Err(SocksError::UnexpectedEnd {
+                expected: 1,
+                actual: 0
+            })
+        );
+        assert_eq!(
+            SocksAddr::decode(&[0x02]),
+            Err(SocksError::UnknownAtyp(0x02))
+        );
+        assert_eq!(
+            SocksAddr::decode(&[ATYP_V4, 1, 2, 3]),
+            Err(SocksError::UnexpectedEnd {
+                expected: 7,
+                actual: 4
+            })
+        );
+        assert_eq!(
+            SocksAddr::decode(&[ATYP_DOMAIN]),
+            Err(SocksError::UnexpectedEnd {
+                expected: 4,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            SocksAddr::decode(&[ATYP_DOMAIN, 3, b'a', b'b']),
+            Err(SocksError::UnexpectedEnd {
+                expected: 7,
+                actual: 4
+            })
+        );
+        assert_eq!(
+            SocksAddr::decode(&[ATYP_DOMAIN, 0, 0, 80]),
+            Err(SocksError::InvalidDomainLength(0))
+        );
+        assert_eq!(
+            SocksAddr::decode(&[ATYP_DOMAIN, 1, 0xff, 0, 80]),
+            Err(SocksError::InvalidDomain)
+        );
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! [`sqlx`] support for storing compact addresses in binary columns.
+//!
+//! The compact types are encoded as their compact bytes with the same SQL
+//! type as `Vec<u8>` (e.g. `BLOB` in SQLite and `BYTEA` in PostgreSQL).
+
+use crate::{CompactAddr, CompactAddrV4, CompactAddrV6};
+use sqlx::{encode::IsNull, error::BoxDynError, Database, Decode, Encode, Type};
+use std::convert::TryFrom;
+
+macro_rules! impl_sqlx {
+    ($ty:ty) => {
+        impl<DB: Database> Type<DB> for $ty
+        where
+            Vec<u8>: Type<DB>,
+        {
+            fn type_info() -> DB::TypeInfo {
+                <Vec<u8> as Type<DB>>::type_info()
+            }
+
+            fn compatible(ty: &DB::TypeInfo) -> bool {
+                <Vec<u8> as Type<DB>>::compatible(ty)
+            }
+        }
+
+        impl<'q, DB: Database> Encode<'q, DB> for $ty
+        where
+            Vec<u8>: Encode<'q, DB>,
+        {
+            fn encode_by_ref(
+                &self,
+                buf: &mut <DB as Database>::ArgumentBuffer<'q>,
+            ) -> Result<IsNull, BoxDynError> {
+                <Vec<u8> as Encode<'q, DB>>::encode(self.as_bytes().to_vec(), buf)
+            }
+
+            fn size_hint(&self) -> usize {
+                self.as_bytes().len()
+            }
+        }
+
+        impl<'r, DB: Database> Decode<'r, DB> for $ty
+        where
+            &'r [u8]: Decode<'r, DB>,
+        {
+            fn decode(value: <DB as Database>::ValueRef<'r>) -> Result<Self, BoxDynError> {
+                let bytes = <&'r [u8] as Decode<'r, DB>>::decode(value)?;
+                Ok(Self::try_from(bytes)?)
+            }
+        }
+    };
+}
+
+impl_sqlx!(CompactAddrV4);
+impl_sqlx!(CompactAddrV6);
+impl_sqlx!(CompactAddr);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::{Connection, SqliteConnection};
+
+    #[tokio::test]
+    async fn round_trip() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE peers (v4 BLOB, v6 BLOB, addr BLOB)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let v4 = CompactAddrV4::from([1, 2, 3, 4, 0x1a, 0xe1]);
+        let v6 = CompactAddrV6::from([7; 18]);
+        let addr = CompactAddr::from(v6);
+        sqlx::query("INSERT INTO peers (v4, v6, addr) VALUES (?, ?, ?)")
+            .bind(v4)
+            .bind(v6)
+            .bind(addr)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let row: (CompactAddrV4, CompactAddrV6, CompactAddr) =
+            sqlx::query_as("SELECT v4, v6, addr FROM peers")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(row, (v4, v6, addr));
+
+        let bytes: Vec<u8> = sqlx::query_scalar("SELECT v4 FROM peers")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(bytes, v4.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn decode_invalid_length() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        let result: Result<CompactAddrV4, _> = sqlx::query_scalar("SELECT x'0102030405'")
+            .fetch_one(&mut conn)
+            .await;
+        assert!(result.is_err());
+        let result: Result<CompactAddr, _> = sqlx::query_scalar("SELECT x'0102030405'")
+            .fetch_one(&mut conn)
+            .await;
+        assert!(result.is_err());
+    }
+}
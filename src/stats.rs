@@ -0,0 +1,241 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Summary statistics of compact addresses.
+//!
+//! Statistics are computed in a single pass over the compact bytes without
+//! decoding the addresses.
+
+use crate::{
+    blob::{self, CompactBlobV4, CompactBlobV6},
+    list::CompactAddrList,
+    peers::CompactPeers,
+    CompactAddr,
+};
+use std::collections::HashSet;
+
+/// The number of ports in each range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PortBuckets {
+    /// Port 0.
+    pub zero: usize,
+    /// Well-known ports (1 to 1023).
+    pub well_known: usize,
+    /// Registered ports (1024 to 49151).
+    pub registered: usize,
+    /// Dynamic ports (49152 to 65535).
+    pub dynamic: usize,
+}
+
+impl PortBuckets {
+    fn add(&mut self, port: u16) {
+        match port {
+            0 => self.zero += 1,
+            1..=1023 => self.well_known += 1,
+            1024..=49151 => self.registered += 1,
+            _ => self.dynamic += 1,
+        }
+    }
+}
+
+/// Summary statistics of compact addresses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlobStats {
+    /// The number of IPv4 addresses.
+    pub v4_entries: usize,
+    /// The number of IPv6 addresses.
+    pub v6_entries: usize,
+    /// The number of distinct IPv4 /24 network prefixes.
+    pub v4_prefixes: usize,
+    /// The number of distinct IPv6 /48 network prefixes.
+    pub v6_prefixes: usize,
+    /// The distribution of ports.
+    pub ports: PortBuckets,
+}
+
+impl BlobStats {
+    /// Returns the number of addresses.
+    pub fn entries(&self) -> usize {
+        self.v4_entries + self.v6_entries
+    }
+}
+
+/// Returns the statistics of a blob of compact IPv4 addresses.
+///
+/// Trailing bytes which do not form a whole address are ignored.
+pub fn stats_v4(blob: &[u8]) -> BlobStats {
+    let mut stats = Accumulator::default();
+    stats.add_v4_blob(blob);
+    stats.finish()
+}
+
+/// Returns the statistics of a blob of compact IPv6 addresses.
+///
+/// Trailing bytes which do not form a whole address are ignored.
+pub fn stats_v6(blob: &[u8]) -> BlobStats {
+    let mut stats = Accumulator::default();
+    stats.add_v6_blob(blob);
+    stats.finish()
+}
+
+impl CompactBlobV4 {
+    /// Returns the statistics of the addresses.
+    pub fn stats(&self) -> BlobStats {
+        stats_v4(self.as_bytes())
+    }
+}
+
+impl CompactBlobV6 {
+    /// Returns the statistics of the addresses.
+    pub fn stats(&self) -> BlobStats {
+        stats_v6(self.as_bytes())
+    }
+}
+
+impl CompactAddrList {
+    /// Returns the statistics of the addresses.
+    pub fn stats(&self) -> BlobStats {
+        let mut stats = Accumulator::default();
+        for addr in self {
+            match addr {
+                CompactAddr::V4(addr) => stats.add_v4(addr.as_bytes()),
+                CompactAddr::V6(addr) => stats.add_v6(addr.as_bytes()),
+            }
+        }
+        stats.finish()
+    }
+}
+
+impl CompactPeers<'_> {
+    /// Returns the statistics of the addresses.
+    pub fn stats(&self) -> BlobStats {
+        let mut stats = Accumulator::default();
+        stats.add_v4_blob(self.v4());
+        stats.add_v6_blob(self.v6());
+        stats.finish()
+    }
+}
+
+#[derive(Default)]
+struct Accumulator {
+    stats: BlobStats,
+    v4_prefixes: HashSet<[u8; 3]>,
+    v6_prefixes: HashSet<[u8; 6]>,
+}
+
+impl Accumulator {
+    fn add_v4_blob(&mut self, blob: &[u8]) {
+        let (entries, _) = blob::as_chunks_v4(blob);
+        self.v4_prefixes.reserve(entries.len());
+        for entry in entries {
+            self.add_v4(entry);
+        }
+    }
+
+    fn add_v6_blob(&mut self, blob: &[u8]) {
+        let (entries, _) = blob::as_chunks_v6(blob);
+        self.v6_prefixes.reserve(entries.len());
+        for entry in entries {
+            self.add_v6(entry);
+        }
+    }
+
+    fn add_v4(&mut self, entry: &[u8; 6]) {
+        self.stats.v4_entries += 1;
+        self.v4_prefixes.insert([entry[0], entry[1], entry[2]]);
+        self.stats
+            .ports
+            .add(u16::from_be_bytes([entry[4], entry[5]]));
+    }
+
+    fn add_v6(&mut self, entry: &[u8; 18]) {
+        self.stats.v6_entries += 1;
+        let mut prefix: [u8; 6] = [0; 6];
+        prefix.copy_from_slice(&entry[0..6]);
+        self.v6_prefixes.insert(prefix);
+        self.stats
+            .ports
+            .add(u16::from_be_bytes([entry[16], entry[17]]));
+    }
+
+    fn finish(self) -> BlobStats {
+        BlobStats {
+            v4_prefixes: self.v4_prefixes.len(),
+            v6_prefixes: self.v6_prefixes.len(),
+            ..self.stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v4_stats() {
+        let mut blob = vec![
+            10, 0, 0, 1, 0, 0, //
+            10, 0, 0, 2, 0, 80, //
+            10, 0, 1, 1, 0x04, 0x00, //
+            192, 0, 2, 1, 0xc0, 0x00,
+        ];
+        blob.push(0xee);
+        let stats = stats_v4(&blob);
+        assert_eq!(
+            stats,
+            BlobStats {
+                v4_entries: 4,
+                v6_entries: 0,
+                v4_prefixes: 3,
+                v6_prefixes: 0,
+                ports: PortBuckets {
+                    zero: 1,
+                    well_known: 1,
+                    registered: 1,
+                    dynamic: 1,
+                },
+            }
+        );
+        assert_eq!(stats.entries(), 4);
+        assert_eq!(stats_v4(&[]), BlobStats::default());
+    }
+
+    #[test]
+    fn v6_stats() {
+        let mut a = [0; 18];
+        a[0] = 0x20;
+        a[17] = 1;
+        let mut b = a;
+        b[15] = 1;
+        b[16] = 0xff;
+        let mut c = a;
+        c[5] = 1;
+        let blob = [a, b, c].concat();
+        let stats = stats_v6(&blob);
+        assert_eq!(stats.v6_entries, 3);
+        assert_eq!(stats.v6_prefixes, 2);
+        assert_eq!(stats.ports.well_known, 2);
+        assert_eq!(stats.ports.dynamic, 1);
+        assert_eq!(CompactBlobV6::from_bytes(blob).unwrap().stats(), stats);
+    }
+
+    #[test]
+    fn mixed_stats() {
+        let v4 = [1, 2, 3, 4, 0, 80];
+        let v6 = [6; 18];
+        let peers = CompactPeers::from_borrowed(&v4, &v6).unwrap();
+        let stats = peers.stats();
+        assert_eq!(stats.entries(), 2);
+        assert_eq!(stats.v4_prefixes, 1);
+        assert_eq!(stats.v6_prefixes, 1);
+
+        let (b4, b6) = peers.into_blobs();
+        let list = CompactAddrList::from_blobs(&b4, &b6).unwrap();
+        assert_eq!(list.stats(), stats);
+    }
+}
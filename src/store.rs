@@ -0,0 +1,1063 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Capacity-bounded storage of peers keyed by compact address.
+//!
+//! [`PeerStore`] is a single-threaded store. [`ConcurrentPeerStore`] holds a
+//! [`PeerStore`] per swarm (e.g. per info hash) in sharded locks so multiple
+//! threads can insert and export concurrently.
+//!
+//! Tracker and DHT peer records are only valid for a limited time. A store
+//! with a time-to-live treats entries announced longer ago than the TTL as
+//! absent and removes them when pruned or when room is needed for a new
+//! entry. A store without a TTL never reads the clock.
+//!
+//! `wasm32-unknown-unknown` has no clock, so stores on that target have no TTL
+//! and never expire entries: `PeerStore::with_ttl`, `PeerStore::set_ttl`, and
+//! `ConcurrentPeerStore::with_ttl` are not available there.
+
+#[cfg(feature = "zstd")]
+use crate::compress::{self, Compression};
+use crate::{
+    blob::{DecodeError, V4_LEN, V6_LEN},
+    CompactAddr, CompactAddrV4, CompactAddrV6,
+};
+use std::{
+    collections::{hash_map::RandomState, BTreeMap, BTreeSet, HashMap},
+    hash::{BuildHasher, Hash},
+    mem,
+    sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::{Duration, Instant},
+};
+#[cfg(feature = "zstd")]
+use std::{io, path::Path};
+
+#[derive(Clone, Debug)]
+struct Entry<V> {
+    value: V,
+    seq: u64,
+    announced: Option<Instant>,
+}
+
+impl<V> Entry<V> {
+    fn is_live(&self, ttl: Option<Duration>) -> bool {
+        match (ttl, self.announced) {
+            (Some(ttl), Some(announced)) => announced.elapsed() <= ttl,
+            _ => true,
+        }
+    }
+}
+
+/// A map from compact addresses to values with a maximum number of entries.
+///
+/// When the store is full, inserting a new address removes the expired
+/// entries and, if the store is still full, evicts the least recently seen
+/// entry. An entry is seen when it is inserted or touched.
+///
+/// An entry is announced when it is inserted. If the store has a TTL, entries
+/// announced longer ago than the TTL are expired: they are not counted or
+/// returned by reads and are removed by
+/// [`prune_expired`](Self::prune_expired).
+#[derive(Clone, Debug)]
+pub struct PeerStore<V> {
+    capacity: usize,
+    ttl: Option<Duration>,
+    entries: HashMap<CompactAddr, Entry<V>>,
+    order: BTreeMap<u64, CompactAddr>,
+    expiry: BTreeSet<(Instant, CompactAddr)>,
+    next_seq: u64,
+}
+
+impl<V> PeerStore<V> {
+    /// Instantiates an empty store which holds at most `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ttl: None,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            expiry: BTreeSet::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Instantiates an empty store which holds at most `capacity` entries
+    /// and expires entries announced longer than `ttl` ago.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn with_ttl(capacity: usize, ttl: Duration) -> Self {
+        let mut store = Self::new(capacity);
+        store.ttl = Some(ttl);
+        store
+    }
+
+    /// Returns the maximum number of entries.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the time-to-live of entries.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    /// Sets the time-to-live of entries.
+    ///
+    /// Existing entries are expired according to the new TTL. Entries inserted
+    /// with [`insert`](Self::insert) while the store had no TTL have no
+    /// announce time and never expire.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn set_ttl(&mut self, ttl: Option<Duration>) {
+        self.ttl = ttl;
+    }
+
+    /// Returns the number of entries.
+    ///
+    /// Expired entries are not counted.
+    pub fn len(&self) -> usize {
+        let expired = self.expired_before().map_or(0, |older_than| {
+            self.expiry
+                .iter()
+                .take_while(|(announced, _)| *announced < older_than)
+                .count()
+        });
+        self.entries.len() - expired
+    }
+
+    /// Returns true if there are no entries.
+    ///
+    /// Expired entries are not counted.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if the address is in the store.
+    pub fn contains<A: Into<CompactAddr>>(&self, addr: A) -> bool {
+        self.entries
+            .get(&addr.into())
+            .is_some_and(|entry| entry.is_live(self.ttl))
+    }
+
+    /// Returns a reference to the value for the address.
+    ///
+    /// The entry is not marked as seen.
+    pub fn get<A: Into<CompactAddr>>(&self, addr: A) -> Option<&V> {
+        self.entries
+            .get(&addr.into())
+            .filter(|entry| entry.is_live(self.ttl))
+            .map(|entry| &entry.value)
+    }
+
+    /// Returns a mutable reference to the value for the address.
+    ///
+    /// The entry is not marked as seen.
+    pub fn get_mut<A: Into<CompactAddr>>(&mut self, addr: A) -> Option<&mut V> {
+        let ttl = self.ttl;
+        self.entries
+            .get_mut(&addr.into())
+            .filter(|entry| entry.is_live(ttl))
+            .map(|entry| &mut entry.value)
+    }
+
+    /// Returns when the address was last announced.
+    ///
+    /// Returns `None` if the address is not in the store, has expired, or was
+    /// inserted without an announce time.
+    pub fn announced<A: Into<CompactAddr>>(&self, addr: A) -> Option<Instant> {
+        self.entries
+            .get(&addr.into())
+            .filter(|entry| entry.is_live(self.ttl))
+            .and_then(|entry| entry.announced)
+    }
+
+    /// Inserts or replaces the value for the address and marks it as seen and
+    /// announced now.
+    ///
+    /// The clock is only read if the store has a TTL. Otherwise the entry has
+    /// no announce time.
+    ///
+    /// Returns the entry which was replaced or evicted, if any. Expired
+    /// entries removed to make room are not returned.
+    pub fn insert<A: Into<CompactAddr>>(&mut self, addr: A, value: V) -> Option<(CompactAddr, V)> {
+        let announced = self.ttl.map(|_| Instant::now());
+        self.insert_entry(addr.into(), value, announced)
+    }
+
+    /// Inserts or replaces the value for the address and marks it as seen and
+    /// announced at the instant.
+    ///
+    /// Returns the entry which was replaced or evicted, if any. Expired
+    /// entries removed to make room are not returned.
+    pub fn insert_announced<A: Into<CompactAddr>>(
+        &mut self,
+        addr: A,
+        value: V,
+        announced: Instant,
+    ) -> Option<(CompactAddr, V)> {
+        self.insert_entry(addr.into(), value, Some(announced))
+    }
+
+    fn insert_entry(
+        &mut self,
+        addr: CompactAddr,
+        value: V,
+        announced: Option<Instant>,
+    ) -> Option<(CompactAddr, V)> {
+        if self.capacity == 0 {
+            return Some((addr, value));
+        }
+
+        let seq = self.next_seq();
+        if let Some(entry) = self.entries.get_mut(&addr) {
+            self.order.remove(&entry.seq);
+            self.order.insert(seq, addr);
+            if let Some(old) = entry.announced {
+                self.expiry.remove(&(old, addr));
+            }
+            if let Some(announced) = announced {
+                self.expiry.insert((announced, addr));
+            }
+            entry.seq = seq;
+            entry.announced = announced;
+            let old = mem::replace(&mut entry.value, value);
+            return Some((addr, old));
+        }
+
+        let evicted = if self.entries.len() >= self.capacity {
+            if let Some(older_than) = self.expired_before() {
+                self.remove_announced_before(older_than);
+            }
+            if self.entries.len() >= self.capacity {
+                self.pop_least_recent()
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        if let Some(announced) = announced {
+            self.expiry.insert((announced, addr));
+        }
+
+        self.order.insert(seq, addr);
+        self.entries.insert(
+            addr,
+            Entry {
+                value,
+                seq,
+                announced,
+            },
+        );
+        evicted
+    }
+
+    /// Marks the address as seen.
+    ///
+    /// Returns true if the address is in the store. An expired entry is
+    /// removed instead.
+    pub fn touch<A: Into<CompactAddr>>(&mut self, addr: A) -> bool {
+        let addr = addr.into();
+        let seq = self.next_seq();
+        match self.entries.get_mut(&addr) {
+            Some(entry) if !entry.is_live(self.ttl) => {
+                self.remove(addr);
+                false
+            }
+            Some(entry) => {
+                self.order.remove(&entry.seq);
+                self.order.insert(seq, addr);
+                entry.seq = seq;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the address from the store.
+    pub fn remove<A: Into<CompactAddr>>(&mut self, addr: A) -> Option<V> {
+        let addr = addr.into();
+        let entry = self.entries.remove(&addr)?;
+        self.order.remove(&entry.seq);
+        if let Some(announced) = entry.announced {
+            self.expiry.remove(&(announced, addr));
+        }
+        Some(entry.value)
+    }
+
+    /// Removes and returns the least recently seen entry.
+    ///
+    /// Expired entries which were seen less recently are removed and skipped.
+    pub fn pop_least_recent(&mut self) -> Option<(CompactAddr, V)> {
+        while let Some((_, addr)) = self.order.pop_first() {
+            let entry = self.entries.remove(&addr)?;
+            if let Some(announced) = entry.announced {
+                self.expiry.remove(&(announced, addr));
+            }
+            if entry.is_live(self.ttl) {
+                return Some((addr, entry.value));
+            }
+        }
+        None
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.expiry.clear();
+    }
+
+    /// Removes the entries announced before the instant.
+    ///
+    /// Entries without an announce time are kept. Returns the number of
+    /// removed entries. If at least half of the entries were removed, the
+    /// store is [compacted](Self::compact).
+    pub fn prune(&mut self, older_than: Instant) -> usize {
+        let removed = self.remove_announced_before(older_than);
+        if removed != 0 && removed >= self.entries.len() {
+            self.compact();
+        }
+        removed
+    }
+
+    /// Removes the expired entries.
+    ///
+    /// Returns the number of removed entries. If at least half of the entries
+    /// were removed, the store is [compacted](Self::compact).
+    pub fn prune_expired(&mut self) -> usize {
+        match self.expired_before() {
+            Some(older_than) => self.prune(older_than),
+            None => 0,
+        }
+    }
+
+    /// Rewrites the backing storage to fit the current entries.
+    ///
+    /// Memory held for removed entries is released.
+    pub fn compact(&mut self) {
+        let addrs: Vec<CompactAddr> = self.order.values().copied().collect();
+        self.order = BTreeMap::new();
+        self.entries.shrink_to_fit();
+        for (seq, addr) in (0..).zip(addrs) {
+            if let Some(entry) = self.entries.get_mut(&addr) {
+                entry.seq = seq;
+            }
+            self.order.insert(seq, addr);
+        }
+        self.next_seq = self.order.len() as u64;
+    }
+
+    /// Returns an iterator over the entries from most to least recently seen.
+    ///
+    /// Expired entries are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = (CompactAddr, &V)> + '_ {
+        self.live_addrs()
+            .map(move |addr| (*addr, &self.entries[addr].value))
+    }
+
+    /// Inserts every address in a blob of compact IPv4 addresses.
+    ///
+    /// Returns the number of addresses in the blob.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blob length is not a multiple of the compact
+    /// address length. No addresses are inserted.
+    pub fn import_v4(&mut self, blob: &[u8], value: V) -> Result<usize, DecodeError>
+    where
+        V: Clone,
+    {
+        if !blob.len().is_multiple_of(V4_LEN) {
+            return Err(DecodeError::invalid_length(blob.len(), V4_LEN));
+        }
+
+        for chunk in blob.chunks_exact(V4_LEN) {
+            let mut a: [u8; V4_LEN] = [0; V4_LEN];
+            a.copy_from_slice(chunk);
+            self.insert(CompactAddrV4::new(a), value.clone());
+        }
+        Ok(blob.len() / V4_LEN)
+    }
+
+    /// Inserts every address in a blob of compact IPv6 addresses.
+    ///
+    /// Returns the number of addresses in the blob.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blob length is not a multiple of the compact
+    /// address length. No addresses are inserted.
+    pub fn import_v6(&mut self, blob: &[u8], value: V) -> Result<usize, DecodeError>
+    where
+        V: Clone,
+    {
+        if !blob.len().is_multiple_of(V6_LEN) {
+            return Err(DecodeError::invalid_length(blob.len(), V6_LEN));
+        }
+
+        for chunk in blob.chunks_exact(V6_LEN) {
+            let mut a: [u8; V6_LEN] = [0; V6_LEN];
+            a.copy_from_slice(chunk);
+            self.insert(CompactAddrV6::new(a), value.clone());
+        }
+        Ok(blob.len() / V6_LEN)
+    }
+
+    /// Inserts every address in a cache file of compact IPv4 addresses,
+    /// decompressed if the file is compressed.
+    ///
+    /// Returns the number of addresses in the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or the compressed data is
+    /// invalid. An invalid blob is returned as an
+    /// [`InvalidData`](io::ErrorKind::InvalidData) error with the
+    /// [`DecodeError`]. No addresses are inserted.
+    #[cfg(feature = "zstd")]
+    pub fn import_v4_file<P: AsRef<Path>>(&mut self, path: P, value: V) -> io::Result<usize>
+    where
+        V: Clone,
+    {
+        let blob = compress::read_file(path)?;
+        self.import_v4(&blob, value).map_err(invalid_data)
+    }
+
+    /// Inserts every address in a cache file of compact IPv6 addresses,
+    /// decompressed if the file is compressed.
+    ///
+    /// Returns the number of addresses in the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or the compressed data is
+    /// invalid. An invalid blob is returned as an
+    /// [`InvalidData`](io::ErrorKind::InvalidData) error with the
+    /// [`DecodeError`]. No addresses are inserted.
+    #[cfg(feature = "zstd")]
+    pub fn import_v6_file<P: AsRef<Path>>(&mut self, path: P, value: V) -> io::Result<usize>
+    where
+        V: Clone,
+    {
+        let blob = compress::read_file(path)?;
+        self.import_v6(&blob, value).map_err(invalid_data)
+    }
+
+    /// Returns a blob of the IPv4 addresses from most to least recently seen.
+    ///
+    /// Expired entries are skipped.
+    pub fn export_v4(&self) -> Vec<u8> {
+        let mut blob = Vec::new();
+        for addr in self.live_addrs() {
+            if let CompactAddr::V4(addr) = addr {
+                blob.extend_from_slice(addr.as_bytes());
+            }
+        }
+        blob
+    }
+
+    /// Returns a blob of the IPv6 addresses from most to least recently seen.
+    ///
+    /// Expired entries are skipped.
+    pub fn export_v6(&self) -> Vec<u8> {
+        let mut blob = Vec::new();
+        for addr in self.live_addrs() {
+            if let CompactAddr::V6(addr) = addr {
+                blob.extend_from_slice(addr.as_bytes());
+            }
+        }
+        blob
+    }
+
+    /// Writes the [IPv4 blob](Self::export_v4) to a cache file with the
+    /// compression.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written or the compression level
+    /// is invalid.
+    #[cfg(feature = "zstd")]
+    pub fn export_v4_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        compression: Compression,
+    ) -> io::Result<()> {
+        compress::write_file(path, &self.export_v4(), compression)
+    }
+
+    /// Writes the [IPv6 blob](Self::export_v6) to a cache file with the
+    /// compression.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written or the compression level
+    /// is invalid.
+    #[cfg(feature = "zstd")]
+    pub fn export_v6_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        compression: Compression,
+    ) -> io::Result<()> {
+        compress::write_file(path, &self.export_v6(), compression)
+    }
+
+    fn live_addrs(&self) -> impl Iterator<Item = &CompactAddr> + '_ {
+        self.order
+            .values()
+            .rev()
+            .filter(move |addr| self.entries[*addr].is_live(self.ttl))
+    }
+
+    fn expired_before(&self) -> Option<Instant> {
+        self.ttl.and_then(|ttl| Instant::now().checked_sub(ttl))
+    }
+
+    fn remove_announced_before(&mut self, older_than: Instant) -> usize {
+        let mut removed = 0;
+        while let Some(&(announced, addr)) = self.expiry.first() {
+            if announced >= older_than {
+                break;
+            }
+            self.expiry.pop_first();
+            if let Some(entry) = self.entries.remove(&addr) {
+                self.order.remove(&entry.seq);
+            }
+            removed += 1;
+        }
+        removed
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
+}
+
+type Shard<K, V> = RwLock<HashMap<K, PeerStore<V>>>;
+
+/// A thread-safe map from swarm keys to [`PeerStore`]s.
+///
+/// Swarms are distributed across independently locked shards by the hash of
+/// their key, so operations on different swarms rarely contend. Exports take a
+/// shared lock and may run concurrently with each other.
+#[derive(Debug)]
+pub struct ConcurrentPeerStore<K, V> {
+    shards: Box<[Shard<K, V>]>,
+    swarm_capacity: usize,
+    swarm_ttl: Option<Duration>,
+    hasher: RandomState,
+}
+
+impl<K, V> ConcurrentPeerStore<K, V>
+where
+    K: Eq + Hash,
+{
+    /// Instantiates an empty store.
+    ///
+    /// Each swarm holds at most `swarm_capacity` peers. At least one shard is
+    /// always used.
+    pub fn new(swarm_capacity: usize, shard_count: usize) -> Self {
+        let shards = (0..shard_count.max(1))
+            .map(|_| RwLock::new(HashMap::new()))
+            .collect();
+        Self {
+            shards,
+            swarm_capacity,
+            swarm_ttl: None,
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Instantiates an empty store whose swarms expire peers announced longer
+    /// than `swarm_ttl` ago.
+    ///
+    /// Each swarm holds at most `swarm_capacity` peers. At least one shard is
+    /// always used.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn with_ttl(swarm_capacity: usize, swarm_ttl: Duration, shard_count: usize) -> Self {
+        let mut store = Self::new(swarm_capacity, shard_count);
+        store.swarm_ttl = Some(swarm_ttl);
+        store
+    }
+
+    /// Returns the maximum number of peers in each swarm.
+    pub fn swarm_capacity(&self) -> usize {
+        self.swarm_capacity
+    }
+
+    /// Returns the time-to-live of peers in each swarm.
+    pub fn swarm_ttl(&self) -> Option<Duration> {
+        self.swarm_ttl
+    }
+
+    /// Returns the number of swarms.
+    pub fn swarm_count(&self) -> usize {
+        (0..self.shards.len())
+            .map(|idx| self.read_shard(idx).len())
+            .sum()
+    }
+
+    /// Returns the number of peers in the swarm.
+    ///
+    /// Expired peers are not counted.
+    pub fn swarm_len(&self, key: &K) -> usize {
+        self.read(key).get(key).map_or(0, PeerStore::len)
+    }
+
+    /// Inserts or replaces the peer in the swarm and marks it as seen.
+    ///
+    /// Returns the entry which was replaced or evicted, if any. If the swarm
+    /// capacity is zero, the peer is returned and no swarm is created.
+    pub fn insert<A: Into<CompactAddr>>(
+        &self,
+        key: K,
+        addr: A,
+        value: V,
+    ) -> Option<(CompactAddr, V)> {
+        if self.swarm_capacity == 0 {
+            return Some((addr.into(), value));
+        }
+        let swarm_capacity = self.swarm_capacity;
+        let swarm_ttl = self.swarm_ttl;
+        self.write(&key)
+            .entry(key)
+            .or_insert_with(|| {
+                let mut swarm = PeerStore::new(swarm_capacity);
+                swarm.ttl = swarm_ttl;
+                swarm
+            })
+            .insert(addr, value)
+    }
+
+    /// Returns a clone of the value for the peer in the swarm.
+    pub fn get<A: Into<CompactAddr>>(&self, key: &K, addr: A) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.read(key)
+            .get(key)
+            .and_then(|swarm| swarm.get(addr).cloned())
+    }
+
+    /// Marks the peer in the swarm as seen.
+    ///
+    /// Returns true if the peer is in the swarm.
+    pub fn touch<A: Into<CompactAddr>>(&self, key: &K, addr: A) -> bool {
+        self.write(key)
+            .get_mut(key)
+            .is_some_and(|swarm| swarm.touch(addr))
+    }
+
+    /// Removes the peer from the swarm.
+    ///
+    /// The swarm is removed when its last peer is removed.
+    pub fn remove<A: Into<CompactAddr>>(&self, key: &K, addr: A) -> Option<V> {
+        let mut shard = self.write(key);
+        let swarm = shard.get_mut(key)?;
+        let value = swarm.remove(addr);
+        if swarm.is_empty() {
+            shard.remove(key);
+        }
+        value
+    }
+
+    /// Removes the swarm and returns its peers.
+    pub fn remove_swarm(&self, key: &K) -> Option<PeerStore<V>> {
+        self.write(key).remove(key)
+    }
+
+    /// Removes the expired peers from every swarm.
+    ///
+    /// Swarms without any remaining peers are removed. Returns the number of
+    /// removed peers.
+    pub fn prune_expired(&self) -> usize {
+        let mut removed = 0;
+        for idx in 0..self.shards.len() {
+            let mut shard = self.shards[idx]
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            shard.retain(|_, swarm| {
+                removed += swarm.prune_expired();
+                !swarm.is_empty()
+            });
+        }
+        removed
+    }
+
+    /// Returns a blob of the swarm's IPv4 peers from most to least recently
+    /// seen.
+    pub fn export_v4(&self, key: &K) -> Vec<u8> {
+        self.read(key)
+            .get(key)
+            .map(PeerStore::export_v4)
+            .unwrap_or_default()
+    }
+
+    /// Returns a blob of the swarm's IPv6 peers from most to least recently
+    /// seen.
+    pub fn export_v6(&self, key: &K) -> Vec<u8> {
+        self.read(key)
+            .get(key)
+            .map(PeerStore::export_v6)
+            .unwrap_or_default()
+    }
+
+    /// Returns blobs of the swarm's IPv4 and IPv6 peers.
+    ///
+    /// Both blobs are exported from the same snapshot of the swarm.
+    pub fn export(&self, key: &K) -> (Vec<u8>, Vec<u8>) {
+        self.read(key)
+            .get(key)
+            .map(|swarm| (swarm.export_v4(), swarm.export_v6()))
+            .unwrap_or_default()
+    }
+
+    /// Calls the function with exclusive access to the swarm's store.
+    ///
+    /// Returns `None` if the swarm does not exist.
+    pub fn with_swarm<F, T>(&self, key: &K, f: F) -> Option<T>
+    where
+        F: FnOnce(&mut PeerStore<V>) -> T,
+    {
+        self.write(key).get_mut(key).map(f)
+    }
+
+    fn shard_idx(&self, key: &K) -> usize {
+        let hash = self.hasher.hash_one(key);
+        (hash % self.shards.len() as u64) as usize
+    }
+
+    fn read(&self, key: &K) -> RwLockReadGuard<'_, HashMap<K, PeerStore<V>>> {
+        self.read_shard(self.shard_idx(key))
+    }
+
+    fn read_shard(&self, idx: usize) -> RwLockReadGuard<'_, HashMap<K, PeerStore<V>>> {
+        self.shards[idx]
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self, key: &K) -> RwLockWriteGuard<'_, HashMap<K, PeerStore<V>>> {
+        self.shards[self.shard_idx(key)]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn invalid_data(e: DecodeError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::compact_v4;
+    #[cfg(feature = "zstd")]
+    use crate::test_util::{compact_v6, temp_path};
+    #[cfg(feature = "zstd")]
+    use std::fs;
+
+    #[test]
+    fn evicts_least_recently_seen() {
+        let mut store = PeerStore::new(2);
+        assert_eq!(store.insert(compact_v4(1), 1), None);
+        assert_eq!(store.insert(compact_v4(2), 2), None);
+        assert!(store.touch(compact_v4(1)));
+        assert_eq!(store.insert(compact_v4(3), 3), Some((compact_v4(2), 2)));
+        assert_eq!(store.len(), 2);
+        assert_eq!(
+            store.iter().map(|(addr, _)| addr).collect::<Vec<_>>(),
+            vec![compact_v4(3), compact_v4(1)]
+        );
+    }
+
+    #[test]
+    fn replace_marks_seen() {
+        let mut store = PeerStore::new(2);
+        store.insert(compact_v4(1), 1);
+        store.insert(compact_v4(2), 2);
+        assert_eq!(store.insert(compact_v4(1), 10), Some((compact_v4(1), 1)));
+        assert_eq!(store.pop_least_recent(), Some((compact_v4(2), 2)));
+        assert_eq!(store.get(compact_v4(1)), Some(&10));
+    }
+
+    #[test]
+    fn touch_and_remove() {
+        let mut store = PeerStore::new(2);
+        assert!(!store.touch(compact_v4(1)));
+        store.insert(compact_v4(1), 1);
+        *store.get_mut(compact_v4(1)).unwrap() += 1;
+        assert_eq!(store.remove(compact_v4(1)), Some(2));
+        assert_eq!(store.remove(compact_v4(1)), None);
+        assert!(store.is_empty());
+        assert_eq!(store.pop_least_recent(), None);
+    }
+
+    #[test]
+    fn zero_capacity_store() {
+        let mut store = PeerStore::new(0);
+        assert_eq!(store.insert(compact_v4(1), 1), Some((compact_v4(1), 1)));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn import_and_export() {
+        let mut blob = Vec::new();
+        for n in 1..=3 {
+            if let CompactAddr::V4(a) = compact_v4(n) {
+                blob.extend_from_slice(a.as_bytes());
+            }
+        }
+        let mut store = PeerStore::new(8);
+        assert_eq!(store.import_v4(&blob, ()), Ok(3));
+        assert!(store.export_v6().is_empty());
+
+        let exported = store.export_v4();
+        assert_eq!(exported.len(), blob.len());
+        assert_eq!(exported[..V4_LEN], blob[2 * V4_LEN..]);
+
+        let v6 = [0; V6_LEN];
+        assert_eq!(store.import_v6(&v6, ()), Ok(1));
+        assert_eq!(store.export_v6(), v6.to_vec());
+        assert_eq!(store.len(), 4);
+    }
+
+    #[test]
+    fn import_ragged_blob() {
+        let mut store = PeerStore::new(8);
+        assert_eq!(
+            store.import_v4(&[0; 7], ()),
+            Err(DecodeError::invalid_length(7, V4_LEN))
+        );
+        assert!(store.import_v6(&[0; 17], ()).is_err());
+        assert!(store.is_empty());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn cache_files() {
+        let mut store = PeerStore::new(8);
+        for n in 1..=3 {
+            store.insert(compact_v4(n), ());
+        }
+        store.insert(compact_v6(1), ());
+
+        let v4_path = temp_path("store-v4");
+        let v6_path = temp_path("store-v6");
+        store.export_v4_file(&v4_path, Compression::zstd()).unwrap();
+        store.export_v6_file(&v6_path, Compression::None).unwrap();
+        assert!(compress::is_compressed(&fs::read(&v4_path).unwrap()));
+        assert_eq!(fs::read(&v6_path).unwrap(), store.export_v6());
+
+        let mut imported = PeerStore::new(8);
+        assert_eq!(imported.import_v4_file(&v4_path, ()).unwrap(), 3);
+        assert_eq!(imported.import_v6_file(&v6_path, ()).unwrap(), 1);
+        assert_eq!(compress::read_file(&v4_path).unwrap(), store.export_v4());
+        assert!(store.iter().all(|(addr, _)| imported.contains(addr)));
+
+        fs::write(&v4_path, [0; 7]).unwrap();
+        let e = imported.import_v4_file(&v4_path, ()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(imported
+            .import_v4_file(temp_path("store-missing"), ())
+            .is_err());
+
+        fs::remove_file(&v4_path).unwrap();
+        fs::remove_file(&v6_path).unwrap();
+    }
+
+    #[test]
+    fn zero_capacity_creates_no_swarm() {
+        let store = ConcurrentPeerStore::new(0, 4);
+        for n in 0..8 {
+            assert_eq!(store.insert(n, compact_v4(1), n), Some((compact_v4(1), n)));
+        }
+        assert_eq!(store.swarm_count(), 0);
+        assert_eq!(store.swarm_len(&0), 0);
+    }
+
+    #[test]
+    fn concurrent_store_swarms() {
+        let store = ConcurrentPeerStore::new(2, 4);
+        assert_eq!(store.insert("a", compact_v4(1), 1), None);
+        assert_eq!(store.insert("a", compact_v4(2), 2), None);
+        assert_eq!(store.insert("b", compact_v4(1), 10), None);
+        assert_eq!(store.swarm_count(), 2);
+        assert_eq!(store.swarm_len(&"a"), 2);
+        assert_eq!(store.get(&"b", compact_v4(1)), Some(10));
+        assert_eq!(store.get(&"c", compact_v4(1)), None);
+
+        assert!(store.touch(&"a", compact_v4(1)));
+        assert!(!store.touch(&"c", compact_v4(1)));
+        assert_eq!(
+            store.insert("a", compact_v4(3), 3),
+            Some((compact_v4(2), 2))
+        );
+        assert_eq!(store.with_swarm(&"a", |swarm| swarm.len()), Some(2));
+
+        let (v4, v6) = store.export(&"a");
+        assert_eq!(v4.len(), 2 * V4_LEN);
+        assert!(v6.is_empty());
+        assert_eq!(store.export_v4(&"a"), v4);
+        assert!(store.export_v6(&"c").is_empty());
+    }
+
+    #[test]
+    fn concurrent_store_removes_empty_swarms() {
+        let store = ConcurrentPeerStore::new(2, 1);
+        store.insert(1, compact_v4(1), ());
+        store.insert(2, compact_v4(1), ());
+        assert_eq!(store.remove(&1, compact_v4(1)), Some(()));
+        assert_eq!(store.remove(&1, compact_v4(1)), None);
+        assert_eq!(store.swarm_count(), 1);
+        assert_eq!(store.remove_swarm(&2).map(|swarm| swarm.len()), Some(1));
+        assert_eq!(store.swarm_count(), 0);
+    }
+
+    #[test]
+    fn concurrent_store_from_threads() {
+        let store = std::sync::Arc::new(ConcurrentPeerStore::new(64, 4));
+        let handles = (0..4u8)
+            .map(|thread| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    for n in 0..32 {
+                        store.insert(n % 4, compact_v4(thread * 32 + n), ());
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(store.swarm_count(), 4);
+        assert!((0..4).all(|key| store.swarm_len(&key) == 32));
+    }
+
+    fn ago(secs: u64) -> Instant {
+        Instant::now() - Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn insert_without_ttl_has_no_announce_time() {
+        let mut store = PeerStore::new(2);
+        store.insert(compact_v4(1), ());
+        assert!(store.contains(compact_v4(1)));
+        assert_eq!(store.announced(compact_v4(1)), None);
+    }
+
+    #[test]
+    fn insert_with_ttl_is_announced_now() {
+        let before = Instant::now();
+        let mut store = PeerStore::with_ttl(2, Duration::from_secs(60));
+        store.insert(compact_v4(1), ());
+        assert!(store
+            .announced(compact_v4(1))
+            .is_some_and(|at| at >= before));
+    }
+
+    #[test]
+    fn full_store_removes_expired_before_evicting_live() {
+        let mut store = PeerStore::with_ttl(3, Duration::from_secs(60));
+        store.insert_announced(compact_v4(1), 1, ago(10));
+        store.insert_announced(compact_v4(2), 2, ago(120));
+        store.insert_announced(compact_v4(3), 3, ago(10));
+        assert_eq!(store.len(), 2);
+
+        assert_eq!(store.insert_announced(compact_v4(4), 4, ago(0)), None);
+        assert_eq!(store.len(), 3);
+        assert!(store.contains(compact_v4(1)));
+        assert!(!store.contains(compact_v4(2)));
+        assert!(store.contains(compact_v4(3)));
+        assert!(store.contains(compact_v4(4)));
+
+        assert_eq!(
+            store.insert_announced(compact_v4(5), 5, ago(0)),
+            Some((compact_v4(1), 1))
+        );
+    }
+
+    #[test]
+    fn pop_least_recent_skips_expired() {
+        let mut store = PeerStore::with_ttl(3, Duration::from_secs(60));
+        store.insert_announced(compact_v4(1), 1, ago(120));
+        store.insert_announced(compact_v4(2), 2, ago(10));
+        assert_eq!(store.pop_least_recent(), Some((compact_v4(2), 2)));
+        assert_eq!(store.pop_least_recent(), None);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn len_excludes_expired() {
+        let mut store = PeerStore::with_ttl(4, Duration::from_secs(60));
+        store.insert_announced(compact_v4(1), (), ago(120));
+        store.insert_announced(compact_v4(2), (), ago(10));
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.iter().count(), 1);
+
+        store.set_ttl(None);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn replace_updates_announce_time() {
+        let mut store = PeerStore::with_ttl(2, Duration::from_secs(60));
+        store.insert_announced(compact_v4(1), 1, ago(120));
+        assert!(!store.contains(compact_v4(1)));
+        assert_eq!(
+            store.insert_announced(compact_v4(1), 2, ago(0)),
+            Some((compact_v4(1), 1))
+        );
+        assert_eq!(store.get(compact_v4(1)), Some(&2));
+        assert_eq!(store.prune_expired(), 0);
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn prune_keeps_entries_without_announce_time() {
+        let mut store = PeerStore::new(4);
+        store.insert(compact_v4(1), ());
+        store.insert_announced(compact_v4(2), (), ago(120));
+        store.insert_announced(compact_v4(3), (), ago(10));
+        assert_eq!(store.prune(ago(60)), 1);
+        assert!(store.contains(compact_v4(1)));
+        assert!(!store.contains(compact_v4(2)));
+        assert!(store.contains(compact_v4(3)));
+    }
+
+    #[test]
+    fn prune_expired_removes_swarm_peers() {
+        let store = ConcurrentPeerStore::with_ttl(4, Duration::from_secs(60), 2);
+        store.insert("a", compact_v4(1), ());
+        store.with_swarm(&"a", |swarm| {
+            swarm.insert_announced(compact_v4(2), (), ago(120))
+        });
+        assert_eq!(store.swarm_len(&"a"), 1);
+        assert_eq!(store.prune_expired(), 1);
+        assert_eq!(store.swarm_len(&"a"), 1);
+    }
+
+    #[test]
+    fn compact_keeps_order() {
+        let mut store = PeerStore::new(8);
+        for n in 1..=4 {
+            store.insert(compact_v4(n), n);
+        }
+        store.remove(compact_v4(2));
+        store.compact();
+        assert_eq!(store.next_seq, 3);
+        assert_eq!(
+            store.iter().map(|(_, value)| *value).collect::<Vec<_>>(),
+            vec![4, 3, 1]
+        );
+        store.insert(compact_v4(5), 5);
+        assert_eq!(store.pop_least_recent(), Some((compact_v4(1), 1)));
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! STUN `MAPPED-ADDRESS` and `XOR-MAPPED-ADDRESS` attribute bodies.
+//!
+//! The attribute body (RFC 8489) is a reserved zero byte, a family byte
+//! (`0x01` for IPv4 and `0x02` for IPv6), a big-endian port, and the IP
+//! address.
+//!
+//! In `XOR-MAPPED-ADDRESS`, the port is XORed with the most significant 16
+//! bits of the magic cookie, and the IP address is XORed with the magic cookie
+//! (IPv4) or the magic cookie followed by the transaction ID (IPv6).
+
+use crate::{CompactAddr, CompactAddrV4, CompactAddrV6};
+use std::{error, fmt};
+
+/// The STUN magic cookie.
+pub const MAGIC_COOKIE: u32 = 0x2112_a442;
+
+/// The family byte for an IPv4 address.
+pub const FAMILY_V4: u8 = 0x01;
+
+/// The family byte for an IPv6 address.
+pub const FAMILY_V6: u8 = 0x02;
+
+/// Appends a `MAPPED-ADDRESS` attribute body to the buffer.
+pub fn encode_mapped(addr: &CompactAddr, buf: &mut Vec<u8>) {
+    encode(addr, &[0; 16], buf);
+}
+
+/// Decodes a `MAPPED-ADDRESS` attribute body.
+///
+/// # Errors
+///
+/// Returns an error if the family is unknown or the body length does not
+/// match the family.
+pub fn decode_mapped(body: &[u8]) -> Result<CompactAddr, StunError> {
+    decode(body, &[0; 16])
+}
+
+/// Appends an `XOR-MAPPED-ADDRESS` attribute body to the buffer.
+pub fn encode_xor_mapped(addr: &CompactAddr, transaction_id: &[u8; 12], buf: &mut Vec<u8>) {
+    encode(addr, &xor_key(transaction_id), buf);
+}
+
+/// Decodes an `XOR-MAPPED-ADDRESS` attribute body.
+///
+/// # Errors
+///
+/// Returns an error if the family is unknown or the body length does not
+/// match the family.
+pub fn decode_xor_mapped(body: &[u8], transaction_id: &[u8; 12]) -> Result<CompactAddr, StunError> {
+    decode(body, &xor_key(transaction_id))
+}
+
+/// Returns the magic cookie followed by the transaction ID.
+fn xor_key(transaction_id: &[u8; 12]) -> [u8; 16] {
+    let mut key: [u8; 16] = [0; 16];
+    key[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    key[4..].copy_from_slice(transaction_id);
+    key
+}
+
+fn encode(addr: &CompactAddr, key: &[u8; 16], buf: &mut Vec<u8>) {
+    let bytes = addr.as_bytes();
+    let (ip, port) = bytes.split_at(bytes.len() - 2);
+    let family = match addr {
+        CompactAddr::V4(_) => FAMILY_V4,
+        CompactAddr::V6(_) => FAMILY_V6,
+    };
+
+    buf.push(0);
+    buf.push(family);
+    buf.push(port[0] ^ key[0]);
+    buf.push(port[1] ^ key[1]);
+    buf.extend(ip.iter().zip(key.iter()).map(|(b, k)| b ^ k));
+}
+
+fn decode(body: &[u8], key: &[u8; 16]) -> Result<CompactAddr, StunError> {
+    if body.len() < 2 {
+        return Err(StunError::InvalidLength(body.len()));
+    }
+    let ip_len = match body[1] {
+        FAMILY_V4 => 4,
+        FAMILY_V6 => 16,
+        family => return Err(StunError::UnknownFamily(family)),
+    };
+    if body.len() != 4 + ip_len {
+        return Err(StunError::InvalidLength(body.len()));
+    }
+
+    let port = [body[2] ^ key[0], body[3] ^ key[1]];
+    let ip = body[4..].iter().zip(key.iter()).map(|(b, k)| b ^ k);
+    if ip_len == 4 {
+        let mut bytes: [u8; 6] = [0; 6];
+        for (dst, src) in bytes.iter_mut().zip(ip) {
+            *dst = src;
+        }
+        bytes[4..].copy_from_slice(&port);
+        Ok(CompactAddr::V4(CompactAddrV4::new(bytes)))
+    } else {
+        let mut bytes: [u8; 18] = [0; 18];
+        for (dst, src) in bytes.iter_mut().zip(ip) {
+            *dst = src;
+        }
+        bytes[16..].copy_from_slice(&port);
+        Ok(CompactAddr::V6(CompactAddrV6::new(bytes)))
+    }
+}
+
+/// An error when decoding a STUN address attribute body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StunError {
+    /// The family byte is not a known address family.
+    UnknownFamily(u8),
+    /// The body length does not match the address family.
+    InvalidLength(usize),
+}
+
+impl fmt::Display for StunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StunError::UnknownFamily(family) => {
+                write!(f, "unknown STUN address family: {:#04x}", family)
+            }
+            StunError::InvalidLength(len) => {
+                write!(f, "invalid STUN address attribute length: {}", len)
+            }
+        }
+    }
+}
+
+impl error::Error for StunError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::addr;
+
+    // RFC 5769 section 2.2 and 2.3
+    const TRANSACTION_ID_V4: [u8; 12] = [
+        0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6, 0x86, 0xfa, 0x87, 0xdf, 0xae,
+    ];
+
+    #[test]
+    fn xor_mapped_v4_vector() {
+        let addr = addr("192.0.2.1:32853");
+        let mut body = Vec::new();
+        encode_xor_mapped(&addr, &TRANSACTION_ID_V4, &mut body);
+        assert_eq!(body, [0x00, 0x01, 0xa1, 0x47, 0xe1, 0x12, 0xa6, 0x43]);
+        assert_eq!(decode_xor_mapped(&body, &TRANSACTION_ID_V4), Ok(addr));
+    }
+
+    #[test]
+    fn xor_mapped_v6_vector() {
+        let addr = addr("[2001:db8:1234:5678:11:2233:4455:6677]:32853");
+        let mut body = Vec::new();
+        encode_xor_mapped(&addr, &TRANSACTION_ID_V4, &mut body);
+        assert_eq!(
+            body,
+            [
+                0x00, 0x02, 0xa1, 0x47, 0x01, 0x13, 0xa9, 0xfa, 0xa5, 0xd3, 0xf1, 0x79, 0xbc, 0x25,
+                0xf4, 0xb5, 0xbe, 0xd2, 0xb9, 0xd9
+            ]
+        );
+        assert_eq!(decode_xor_mapped(&body, &TRANSACTION_ID_V4), Ok(addr));
+    }
+
+    #[test]
+    fn mapped_round_trip() {
+        let addr = addr("1.2.3.4:3478");
+        let mut body = Vec::new();
+        encode_mapped(&addr, &mut body);
+        assert_eq!(body, [0x00, FAMILY_V4, 0x0d, 0x96, 1, 2, 3, 4]);
+        assert_eq!(decode_mapped(&body), Ok(addr));
+    }
+
+    #[test]
+    fn decode_malformed() {
+        assert_eq!(decode_mapped(&[0]), Err(StunError::InvalidLength(1)));
+        assert_eq!(
+            decode_mapped(&[0, 0x03, 0, 0]),
+            Err(StunError::UnknownFamily(0x03))
+        );
+        assert_eq!(
+            decode_mapped(&[0, FAMILY_V4, 0, 80, 1, 2, 3]),
+            Err(StunError::InvalidLength(7))
+        );
+        assert_eq!(
+            decode_mapped(&[0, FAMILY_V6, 0, 80, 1, 2, 3, 4]),
+            Err(StunError::InvalidLength(8))
+        );
+    }
+}
@@ -0,0 +1,292 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Self-describing compact addresses with an address family tag.
+//!
+//! A tagged address is a tag byte followed by the compact address:
+//!
+//! * `[0x04, ..6 bytes]` for an IPv4 compact address
+//! * `[0x06, ..18 bytes]` for an IPv6 compact address
+//!
+//! A tagged list is the concatenation of tagged addresses. The tagged
+//! encoding is intended for storage where the address family is not implied
+//! by the context (such as the `peers` and `peers6` keys).
+
+use crate::{
+    blob::{V4_LEN, V6_LEN},
+    list::CompactAddrList,
+    CompactAddr, CompactAddrV4, CompactAddrV6,
+};
+use std::{convert::TryFrom, error, fmt};
+
+/// Tag for an IPv4 compact address.
+pub const TAG_V4: u8 = 0x04;
+
+/// Tag for an IPv6 compact address.
+pub const TAG_V6: u8 = 0x06;
+
+impl CompactAddr {
+    /// Returns the tag byte for the address family.
+    pub fn tag(&self) -> u8 {
+        match self {
+            CompactAddr::V4(_) => TAG_V4,
+            CompactAddr::V6(_) => TAG_V6,
+        }
+    }
+
+    /// Returns the length of the tagged encoding.
+    pub fn tagged_len(&self) -> usize {
+        1 + self.as_bytes().len()
+    }
+
+    /// Appends the tagged encoding to the buffer.
+    pub fn encode_tagged(&self, buf: &mut Vec<u8>) {
+        buf.push(self.tag());
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    /// Returns the tagged encoding.
+    pub fn to_tagged(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.tagged_len());
+        self.encode_tagged(&mut buf);
+        buf
+    }
+
+    /// Decodes a tagged address from the start of the bytes.
+    ///
+    /// Returns the address and the remaining bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tag is unknown or there are not enough bytes
+    /// for the address.
+    pub fn decode_tagged(bytes: &[u8]) -> Result<(Self, &[u8]), DecodeError> {
+        let (tag, entry, rest) = split_tagged(bytes, |tag| match tag {
+            TAG_V4 => Some(V4_LEN),
+            TAG_V6 => Some(V6_LEN),
+            _ => None,
+        })?;
+        let addr = if tag == TAG_V4 {
+            CompactAddr::V4(CompactAddrV4::try_from(entry).expect("entry length is checked"))
+        } else {
+            CompactAddr::V6(CompactAddrV6::try_from(entry).expect("entry length is checked"))
+        };
+        Ok((addr, rest))
+    }
+
+    /// Decodes exactly one tagged address.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tag is unknown or the length does not match the
+    /// tagged address.
+    pub fn from_tagged(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let (addr, rest) = Self::decode_tagged(bytes)?;
+        if !rest.is_empty() {
+            return Err(DecodeError::TrailingBytes(rest.len()));
+        }
+        Ok(addr)
+    }
+}
+
+impl CompactAddrList {
+    /// Returns the tagged list encoding.
+    pub fn to_tagged(&self) -> Vec<u8> {
+        encode_list(self.iter())
+    }
+
+    /// Decodes a tagged list.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any tag is unknown or the bytes end in the middle of
+    /// an address.
+    pub fn from_tagged(bytes: &[u8]) -> Result<Self, DecodeError> {
+        decode_list(bytes).map(CompactAddrList::from)
+    }
+}
+
+/// Returns the concatenated tagged encodings of the addresses.
+pub fn encode_list<'a, I>(addrs: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = &'a CompactAddr>,
+{
+    encode_tagged_list(addrs, CompactAddr::encode_tagged)
+}
+
+/// Decodes concatenated tagged addresses.
+///
+/// # Errors
+///
+/// Returns an error if any tag is unknown or the bytes end in the middle of
+/// an address.
+pub fn decode_list(bytes: &[u8]) -> Result<Vec<CompactAddr>, DecodeError> {
+    decode_tagged_list(bytes, CompactAddr::decode_tagged)
+}
+
+/// Splits the tag byte and the payload from the start of the bytes.
+///
+/// `payload_len` returns the payload length for a known tag. Returns the tag,
+/// the payload, and the remaining bytes.
+pub(crate) fn split_tagged<F>(
+    bytes: &[u8],
+    payload_len: F,
+) -> Result<(u8, &[u8], &[u8]), DecodeError>
+where
+    F: FnOnce(u8) -> Option<usize>,
+{
+    let (&tag, rest) = bytes.split_first().ok_or(DecodeError::UnexpectedEnd {
+        expected: 1,
+        actual: 0,
+    })?;
+    let len = payload_len(tag).ok_or(DecodeError::UnknownTag(tag))?;
+    if rest.len() < len {
+        return Err(DecodeError::UnexpectedEnd {
+            expected: len + 1,
+            actual: bytes.len(),
+        });
+    }
+    let (payload, rest) = rest.split_at(len);
+    Ok((tag, payload, rest))
+}
+
+/// Concatenates the tagged encodings of the addresses.
+pub(crate) fn encode_tagged_list<I, F>(addrs: I, mut encode: F) -> Vec<u8>
+where
+    I: IntoIterator,
+    F: FnMut(I::Item, &mut Vec<u8>),
+{
+    let mut buf = Vec::new();
+    for addr in addrs {
+        encode(addr, &mut buf);
+    }
+    buf
+}
+
+/// Decodes tagged addresses until the bytes are consumed.
+pub(crate) fn decode_tagged_list<T, F>(
+    mut bytes: &[u8],
+    mut decode: F,
+) -> Result<Vec<T>, DecodeError>
+where
+    F: FnMut(&[u8]) -> Result<(T, &[u8]), DecodeError>,
+{
+    let mut addrs = Vec::new();
+    while !bytes.is_empty() {
+        let (addr, rest) = decode(bytes)?;
+        addrs.push(addr);
+        bytes = rest;
+    }
+    Ok(addrs)
+}
+
+/// An error when decoding tagged compact addresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The tag byte is not a known address family.
+    UnknownTag(u8),
+    /// The bytes ended before the address was complete.
+    UnexpectedEnd {
+        /// The number of bytes required.
+        expected: usize,
+        /// The number of bytes available.
+        actual: usize,
+    },
+    /// There are bytes after the tagged address.
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnknownTag(tag) => write!(f, "unknown address tag: {:#04x}", tag),
+            DecodeError::UnexpectedEnd { expected, actual } => write!(
+                f,
+                "unexpected end of address: expected {} bytes, found {}",
+                expected, actual
+            ),
+            DecodeError::TrailingBytes(len) => {
+                write!(f, "{} trailing bytes after tagged address", len)
+            }
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v4() -> CompactAddr {
+        CompactAddr::V4(CompactAddrV4::from([1, 2, 3, 4, 0x1a, 0xe1]))
+    }
+
+    fn v6() -> CompactAddr {
+        CompactAddr::V6(CompactAddrV6::from([6; V6_LEN]))
+    }
+
+    #[test]
+    fn tagged_round_trip() {
+        assert_eq!(v4().to_tagged(), [TAG_V4, 1, 2, 3, 4, 0x1a, 0xe1]);
+        assert_eq!(v4().tagged_len(), 7);
+        assert_eq!(v6().tagged_len(), 19);
+        for addr in [v4(), v6()] {
+            assert_eq!(CompactAddr::from_tagged(&addr.to_tagged()), Ok(addr));
+        }
+
+        let mut bytes = v6().to_tagged();
+        bytes.push(0xee);
+        assert_eq!(CompactAddr::decode_tagged(&bytes), Ok((v6(), &[0xee][..])));
+        assert_eq!(
+            CompactAddr::from_tagged(&bytes),
+            Err(DecodeError::TrailingBytes(1))
+        );
+    }
+
+    #[test]
+    fn tagged_malformed() {
+        assert_eq!(
+            CompactAddr::decode_tagged(&[]),
+            Err(DecodeError::UnexpectedEnd {
+                expected: 1,
+                actual: 0
+            })
+        );
+        assert_eq!(
+            CompactAddr::decode_tagged(&[0x05, 0, 0, 0, 0, 0, 0]),
+            Err(DecodeError::UnknownTag(0x05))
+        );
+        assert_eq!(
+            CompactAddr::decode_tagged(&[TAG_V6, 0, 0, 0]),
+            Err(DecodeError::UnexpectedEnd {
+                expected: 19,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn tagged_list() {
+        let list: CompactAddrList = vec![v6(), v4(), v4()].into();
+        let bytes = list.to_tagged();
+        assert_eq!(bytes.len(), 19 + 7 + 7);
+        assert_eq!(bytes, encode_list(list.iter()));
+        assert_eq!(CompactAddrList::from_tagged(&bytes), Ok(list));
+        assert_eq!(decode_list(&[]), Ok(Vec::new()));
+        assert!(decode_list(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn error_display() {
+        assert_eq!(
+            DecodeError::UnknownTag(5).to_string(),
+            "unknown address tag: 0x05"
+        );
+    }
+}
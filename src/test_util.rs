@@ -0,0 +1,56 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Fixtures shared by the unit tests.
+
+use crate::CompactAddr;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+/// Returns the compact address parsed from the text.
+pub(crate) fn addr(s: &str) -> CompactAddr {
+    s.parse().unwrap()
+}
+
+/// Returns the IPv4 socket address `10.0.0.n:port`.
+pub(crate) fn socket_v4(n: u8, port: u16) -> SocketAddrV4 {
+    SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, n), port)
+}
+
+/// Returns the IPv6 socket address `[2001:db8::n]:port`.
+pub(crate) fn socket_v6(n: u16, port: u16) -> SocketAddrV6 {
+    SocketAddrV6::new(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, n), port, 0, 0)
+}
+
+/// Returns the compact address `10.0.0.n:6881`.
+pub(crate) fn compact_v4(n: u8) -> CompactAddr {
+    CompactAddr::from(socket_v4(n, 6881))
+}
+
+/// Returns the compact address `[2001:db8::n]:6881`.
+pub(crate) fn compact_v6(n: u8) -> CompactAddr {
+    CompactAddr::from(socket_v6(u16::from(n), 6881))
+}
+
+/// Returns the compact bytes of the IPv4 address with port 6881.
+pub(crate) fn v4_bytes(ip: [u8; 4]) -> [u8; 6] {
+    [ip[0], ip[1], ip[2], ip[3], 0x1a, 0xe1]
+}
+
+/// Returns the compact bytes of the IPv6 address with port 0.
+pub(crate) fn v6_bytes(ip: &str) -> [u8; 18] {
+    let mut addr: [u8; 18] = [0; 18];
+    addr[..16].copy_from_slice(&ip.parse::<Ipv6Addr>().unwrap().octets());
+    addr
+}
+
+/// Returns a path in the temporary directory which is unique to the test
+/// process.
+#[cfg(any(feature = "mmap", feature = "zstd"))]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("compact_addr-{}-{}", std::process::id(), name))
+}
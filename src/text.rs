@@ -0,0 +1,594 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Text dump and import formats.
+//!
+//! The text format has one `ip:port` address per line (IPv6 addresses are
+//! written as `[ip]:port`). When importing, blank lines and lines starting
+//! with `#` are ignored.
+//!
+//! The CSV format has `ip` and `port` columns followed by optional extra
+//! columns. Fields containing commas, quotes, or line breaks are quoted.
+
+#[cfg(feature = "zstd")]
+use crate::compress::{self, Compression};
+use crate::{
+    blob::{self, DecodeError},
+    CompactAddr, CompactAddrV4Info, CompactAddrV6, ParseAddrError, ParseAddrErrorKind,
+};
+use std::{
+    error, fmt,
+    fmt::Write,
+    mem,
+    net::{IpAddr, SocketAddr, SocketAddrV4},
+};
+#[cfg(feature = "zstd")]
+use std::{io, path::Path};
+
+/// Returns the text dump of a blob of compact IPv4 addresses.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length.
+pub fn to_text_v4(blob: &[u8]) -> Result<String, DecodeError> {
+    check_len(blob, blob::V4_LEN)?;
+    Ok(write_lines(blob::iter_v4(blob)))
+}
+
+/// Returns the text dump of a blob of compact IPv6 addresses.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length.
+pub fn to_text_v6(blob: &[u8]) -> Result<String, DecodeError> {
+    check_len(blob, blob::V6_LEN)?;
+    Ok(write_lines(blob::iter_v6(blob)))
+}
+
+/// Returns a blob of compact IPv4 addresses from a text dump.
+///
+/// # Errors
+///
+/// Returns an error if a line is not an IPv4 socket address.
+pub fn from_text_v4(text: &str) -> Result<Vec<u8>, ParseError> {
+    let mut blob = Vec::new();
+    for (line, value) in lines(text) {
+        let addr = value
+            .parse::<SocketAddrV4>()
+            .map_err(|_| ParseError::new(line, ParseErrorKind::InvalidAddr))?;
+        blob.extend_from_slice(&addr.to_compact_address());
+    }
+    Ok(blob)
+}
+
+/// Returns a blob of compact IPv6 addresses from a text dump.
+///
+/// # Errors
+///
+/// Returns an error if a line is not an IPv6 socket address or has a non-zero
+/// zone identifier.
+pub fn from_text_v6(text: &str) -> Result<Vec<u8>, ParseError> {
+    let mut blob = Vec::new();
+    for (line, value) in lines(text) {
+        let addr = value
+            .parse::<CompactAddrV6>()
+            .map_err(|e| ParseError::from_addr(line, e))?;
+        blob.extend_from_slice(addr.as_bytes());
+    }
+    Ok(blob)
+}
+
+/// Returns the text dump of a list of compact addresses.
+pub fn to_text<'a, I>(addrs: I) -> String
+where
+    I: IntoIterator<Item = &'a CompactAddr>,
+{
+    write_lines(addrs)
+}
+
+/// Returns a list of compact addresses from a text dump.
+///
+/// # Errors
+///
+/// Returns an error if a line is not a socket address or an IPv6 address has a
+/// non-zero zone identifier.
+pub fn from_text(text: &str) -> Result<Vec<CompactAddr>, ParseError> {
+    lines(text)
+        .map(|(line, value)| {
+            value
+                .parse::<CompactAddr>()
+                .map_err(|e| ParseError::from_addr(line, e))
+        })
+        .collect()
+}
+
+/// A CSV record of a compact address with extra columns.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CsvRecord {
+    /// The address.
+    pub addr: CompactAddr,
+    /// The values of the extra columns.
+    pub extra: Vec<String>,
+}
+
+impl From<CompactAddr> for CsvRecord {
+    fn from(addr: CompactAddr) -> Self {
+        Self {
+            addr,
+            extra: Vec::new(),
+        }
+    }
+}
+
+/// Returns the CSV dump of the records.
+///
+/// The header row is `ip,port` followed by the extra column names.
+pub fn to_csv<'a, I>(records: I, extra_columns: &[&str]) -> String
+where
+    I: IntoIterator<Item = &'a CsvRecord>,
+{
+    let mut text = String::from("ip,port");
+    for column in extra_columns {
+        text.push(',');
+        write_field(&mut text, column);
+    }
+    text.push('\n');
+
+    for record in records {
+        let _ = write!(text, "{},{}", record.addr.ip(), record.addr.port());
+        for value in &record.extra {
+            text.push(',');
+            write_field(&mut text, value);
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Returns the records from a CSV dump.
+///
+/// A leading header row starting with `ip,port` is skipped.
+///
+/// # Errors
+///
+/// Returns an error if a row has an invalid IP address or port, is missing
+/// a column, or has an unterminated quoted field.
+pub fn from_csv(text: &str) -> Result<Vec<CsvRecord>, ParseError> {
+    let mut records = Vec::new();
+    let mut rest = text;
+    let mut next_line = 1;
+    while !rest.is_empty() {
+        let line = next_line;
+        let row = read_row(rest)
+            .ok_or_else(|| ParseError::new(line, ParseErrorKind::UnterminatedQuote))?;
+        rest = row.rest;
+        next_line += row.line_count;
+
+        let fields = row.fields;
+        if fields.len() == 1 && fields[0].trim().is_empty() {
+            continue;
+        }
+        if line == 1 && fields.len() >= 2 && fields[0] == "ip" && fields[1] == "port" {
+            continue;
+        }
+
+        let mut fields = fields.into_iter();
+        let ip = fields
+            .next()
+            .ok_or_else(|| ParseError::new(line, ParseErrorKind::MissingField))?
+            .trim()
+            .parse::<IpAddr>()
+            .map_err(|_| ParseError::new(line, ParseErrorKind::InvalidAddr))?;
+        let port = fields
+            .next()
+            .ok_or_else(|| ParseError::new(line, ParseErrorKind::MissingField))?
+            .trim()
+            .parse::<u16>()
+            .map_err(|_| ParseError::new(line, ParseErrorKind::InvalidPort))?;
+
+        records.push(CsvRecord {
+            addr: CompactAddr::from(SocketAddr::new(ip, port)),
+            extra: fields.collect(),
+        });
+    }
+    Ok(records)
+}
+
+/// Writes the text dump of a list of compact addresses to the file with the
+/// compression.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written or the compression level
+/// is invalid.
+#[cfg(feature = "zstd")]
+pub fn write_text_file<'a, P, I>(path: P, addrs: I, compression: Compression) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = &'a CompactAddr>,
+{
+    compress::write_file(path, to_text(addrs).as_bytes(), compression)
+}
+
+/// Returns a list of compact addresses from a text dump file, decompressed if
+/// the file is compressed.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or the compressed data is
+/// invalid. An invalid text dump is returned as an
+/// [`InvalidData`](io::ErrorKind::InvalidData) error with the [`ParseError`].
+#[cfg(feature = "zstd")]
+pub fn read_text_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<CompactAddr>> {
+    from_text(&compress::read_to_string(path)?).map_err(invalid_data)
+}
+
+/// Writes the CSV dump of the records to the file with the compression.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written or the compression level
+/// is invalid.
+#[cfg(feature = "zstd")]
+pub fn write_csv_file<'a, P, I>(
+    path: P,
+    records: I,
+    extra_columns: &[&str],
+    compression: Compression,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = &'a CsvRecord>,
+{
+    compress::write_file(path, to_csv(records, extra_columns).as_bytes(), compression)
+}
+
+/// Returns the records from a CSV dump file, decompressed if the file is
+/// compressed.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or the compressed data is
+/// invalid. An invalid CSV dump is returned as an
+/// [`InvalidData`](io::ErrorKind::InvalidData) error with the [`ParseError`].
+#[cfg(feature = "zstd")]
+pub fn read_csv_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<CsvRecord>> {
+    from_csv(&compress::read_to_string(path)?).map_err(invalid_data)
+}
+
+/// The kind of error when parsing a text or CSV dump.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The address is invalid or of the wrong address family.
+    InvalidAddr,
+    /// The port is invalid.
+    InvalidPort,
+    /// A required column is missing.
+    MissingField,
+    /// A quoted field is not terminated.
+    UnterminatedQuote,
+    /// The IPv6 address has a non-zero zone identifier.
+    Scoped,
+}
+
+/// An error when parsing a text or CSV dump.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    line: usize,
+    kind: ParseErrorKind,
+}
+
+impl ParseError {
+    fn new(line: usize, kind: ParseErrorKind) -> Self {
+        Self { line, kind }
+    }
+
+    fn from_addr(line: usize, e: ParseAddrError) -> Self {
+        let kind = match e.kind() {
+            ParseAddrErrorKind::Scoped => ParseErrorKind::Scoped,
+            _ => ParseErrorKind::InvalidAddr,
+        };
+        Self::new(line, kind)
+    }
+
+    /// Returns the 1-based line number where the error occurred.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the kind of error.
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.kind {
+            ParseErrorKind::InvalidAddr => "invalid address",
+            ParseErrorKind::InvalidPort => "invalid port",
+            ParseErrorKind::MissingField => "missing field",
+            ParseErrorKind::UnterminatedQuote => "unterminated quoted field",
+            ParseErrorKind::Scoped => "scoped address",
+        };
+        write!(f, "{} on line {}", reason, self.line)
+    }
+}
+
+impl error::Error for ParseError {}
+
+fn check_len(blob: &[u8], entry_len: usize) -> Result<(), DecodeError> {
+    if blob.len().is_multiple_of(entry_len) {
+        Ok(())
+    } else {
+        Err(DecodeError::invalid_length(blob.len(), entry_len))
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn invalid_data(e: ParseError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn write_lines<I>(addrs: I) -> String
+where
+    I: IntoIterator,
+    I::Item: fmt::Display,
+{
+    let mut text = String::new();
+    for addr in addrs {
+        let _ = writeln!(text, "{}", addr);
+    }
+    text
+}
+
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(idx, value)| (idx + 1, value.trim()))
+        .filter(|(_, value)| !value.is_empty() && !value.starts_with('#'))
+}
+
+fn write_field(text: &mut String, value: &str) {
+    if value.contains([',', '"', '\n', '\r']) {
+        text.push('"');
+        text.push_str(&value.replace('"', "\"\""));
+        text.push('"');
+    } else {
+        text.push_str(value);
+    }
+}
+
+struct Row<'a> {
+    fields: Vec<String>,
+    rest: &'a str,
+    line_count: usize,
+}
+
+/// Reads the fields of the first row.
+///
+/// Returns `None` if a quoted field is not terminated.
+fn read_row(text: &str) -> Option<Row<'_>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.char_indices().peekable();
+    let mut quoted = false;
+    let mut line_count = 1;
+
+    while let Some((idx, c)) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek().map(|(_, c)| *c) == Some('"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                c => {
+                    if c == '\n' {
+                        line_count += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            ',' => fields.push(mem::take(&mut field)),
+            '\n' => {
+                if field.ends_with('\r') {
+                    field.pop();
+                }
+                fields.push(field);
+                return Some(Row {
+                    fields,
+                    rest: &text[idx + 1..],
+                    line_count,
+                });
+            }
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        return None;
+    }
+    fields.push(field);
+    Some(Row {
+        fields,
+        rest: "",
+        line_count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::addr;
+    #[cfg(feature = "zstd")]
+    use crate::test_util::temp_path;
+    #[cfg(feature = "zstd")]
+    use std::fs;
+
+    #[test]
+    fn text_round_trip() {
+        let addrs = vec![addr("1.2.3.4:80"), addr("[2001:db8::1]:6881")];
+        let text = to_text(&addrs);
+        assert_eq!(text, "1.2.3.4:80\n[2001:db8::1]:6881\n");
+        assert_eq!(from_text(&text), Ok(addrs));
+    }
+
+    #[test]
+    fn text_blob_round_trip() {
+        let v4 = [1, 2, 3, 4, 0, 80, 5, 6, 7, 8, 0x1a, 0xe1];
+        let text = to_text_v4(&v4).unwrap();
+        assert_eq!(text, "1.2.3.4:80\n5.6.7.8:6881\n");
+        assert_eq!(from_text_v4(&text), Ok(v4.to_vec()));
+
+        let v6 = [0; 18];
+        let text = to_text_v6(&v6).unwrap();
+        assert_eq!(text, "[::]:0\n");
+        assert_eq!(from_text_v6(&text), Ok(v6.to_vec()));
+
+        assert!(to_text_v4(&v4[..5]).is_err());
+        assert!(to_text_v6(&v4).is_err());
+    }
+
+    #[test]
+    fn text_skips_comments_and_blank_lines() {
+        let text = "# peers\n\n  1.2.3.4:80  \n#[::1]:80\n";
+        assert_eq!(from_text(text), Ok(vec![addr("1.2.3.4:80")]));
+    }
+
+    #[test]
+    fn text_reports_invalid_line() {
+        let e = from_text_v4("1.2.3.4:80\n\n[::1]:80\n").unwrap_err();
+        assert_eq!(e.kind(), ParseErrorKind::InvalidAddr);
+        assert_eq!(e.line(), 3);
+        assert_eq!(e.to_string(), "invalid address on line 3");
+        assert!(from_text("1.2.3.4").is_err());
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let records = vec![
+            CsvRecord {
+                addr: addr("1.2.3.4:80"),
+                extra: vec!["a,b".to_string(), "say \"hi\"".to_string()],
+            },
+            CsvRecord {
+                addr: addr("[2001:db8::1]:6881"),
+                extra: vec!["line\nbreak".to_string(), String::new()],
+            },
+        ];
+        let text = to_csv(&records, &["name", "note"]);
+        assert!(text.starts_with("ip,port,name,note\n1.2.3.4,80,\"a,b\",\"say \"\"hi\"\"\"\n"));
+        assert_eq!(from_csv(&text), Ok(records));
+    }
+
+    #[test]
+    fn csv_without_header() {
+        let records = from_csv("1.2.3.4,80\r\n\n::1,443,x\n").unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], CsvRecord::from(addr("1.2.3.4:80")));
+        assert_eq!(records[1].extra, vec!["x".to_string()]);
+    }
+
+    #[test]
+    fn csv_errors() {
+        let kind = |text| from_csv(text).unwrap_err().kind();
+        assert_eq!(kind("ip,port\nnope,80\n"), ParseErrorKind::InvalidAddr);
+        assert_eq!(kind("1.2.3.4,70000\n"), ParseErrorKind::InvalidPort);
+        assert_eq!(kind("1.2.3.4\n"), ParseErrorKind::MissingField);
+        assert_eq!(
+            kind("1.2.3.4,80,\"open\n"),
+            ParseErrorKind::UnterminatedQuote
+        );
+
+        let e = from_csv("ip,port\n1.2.3.4,80,\"a\nb\"\nx,1\n").unwrap_err();
+        assert_eq!(e.line(), 4);
+    }
+
+    #[test]
+    fn from_text_rejects_scoped_v6() {
+        let e = from_text("1.2.3.4:80\n[fe80::1%3]:6881\n").unwrap_err();
+        assert_eq!(e.kind(), ParseErrorKind::Scoped);
+        assert_eq!(e.line(), 2);
+
+        let e = from_text_v6("[fe80::1%3]:6881").unwrap_err();
+        assert_eq!(e.kind(), ParseErrorKind::Scoped);
+        assert_eq!(e.line(), 1);
+    }
+
+    #[test]
+    fn from_text_accepts_zero_scope() {
+        let addrs = from_text("[fe80::1%0]:6881").unwrap();
+        assert_eq!(
+            addrs,
+            vec!["[fe80::1]:6881".parse::<CompactAddr>().unwrap()]
+        );
+        assert_eq!(
+            from_text_v6("[fe80::1%0]:6881").unwrap().len(),
+            blob::V6_LEN
+        );
+    }
+
+    #[test]
+    fn from_text_rejects_invalid_zone() {
+        let e = from_text_v6("[fe80::1%]:6881").unwrap_err();
+        assert_eq!(e.kind(), ParseErrorKind::InvalidAddr);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn text_file_round_trip() {
+        let addrs = vec![addr("1.2.3.4:80"), addr("[2001:db8::1]:6881")];
+        for (name, compression) in [
+            ("text-plain", Compression::None),
+            ("text-zstd", Compression::zstd()),
+        ] {
+            let path = temp_path(name);
+            write_text_file(&path, &addrs, compression).unwrap();
+            assert_eq!(
+                compress::is_compressed(&fs::read(&path).unwrap()),
+                compression != Compression::None
+            );
+            assert_eq!(read_text_file(&path).unwrap(), addrs);
+            fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn csv_file_round_trip() {
+        let records = vec![CsvRecord {
+            addr: addr("1.2.3.4:80"),
+            extra: vec!["a,b".to_string()],
+        }];
+        let path = temp_path("csv-zstd");
+        write_csv_file(&path, &records, &["name"], Compression::zstd()).unwrap();
+        assert_eq!(read_csv_file(&path).unwrap(), records);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn read_invalid_text_file() {
+        let path = temp_path("text-invalid");
+        compress::write_file(&path, b"1.2.3.4:80\nnope\n", Compression::zstd()).unwrap();
+        let e = read_text_file(&path).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        let e = e.into_inner().unwrap().downcast::<ParseError>().unwrap();
+        assert_eq!(e.line(), 2);
+        assert!(read_csv_file(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Tracker response fields.
+//!
+//! The `external ip` key (BEP 24) is the requesting client's IP address as
+//! seen by the tracker, encoded as a 4 byte IPv4 or 16 byte IPv6 address in
+//! network byte order. Some trackers incorrectly send the address as text, such
+//! as a dotted-quad string.
+
+use crate::ltep;
+use std::{error, fmt, net::IpAddr, str};
+
+/// Returns the `external ip` value for the IP address.
+pub fn encode_external_ip(ip: IpAddr) -> Vec<u8> {
+    ltep::encode_yourip(ip)
+}
+
+/// Decodes an `external ip` value.
+///
+/// A value which only contains the characters of an IP address's text form
+/// and at least one `.` or `:` is parsed as text, so a text value is never
+/// mistaken for a compact IP address of the same length. Other 4 and 16 byte
+/// values are decoded as compact IP addresses, as are 4 and 16 byte values
+/// which look like text but fail to parse (such as `"1:80"`).
+///
+/// A compact address whose bytes are all such characters (such as
+/// `49.58.58.49`, which is `"1::1"` as text) is decoded as text. Use
+/// [`ltep::decode_yourip`] if the tracker is known to send compact addresses.
+///
+/// # Errors
+///
+/// Returns an error if the value is neither a compact IP address nor the
+/// text form of an IP address.
+pub fn decode_external_ip(value: &[u8]) -> Result<IpAddr, ExternalIpError> {
+    let err = ExternalIpError { len: value.len() };
+    if is_text(value) {
+        if let Some(ip) = str::from_utf8(value)
+            .ok()
+            .and_then(|text| text.trim().parse::<IpAddr>().ok())
+        {
+            return Ok(ip);
+        }
+    }
+    ltep::decode_yourip(value).map_err(|_| err)
+}
+
+fn is_text(value: &[u8]) -> bool {
+    let trimmed = value.trim_ascii();
+    !trimmed.is_empty()
+        && trimmed.iter().any(|b| *b == b'.' || *b == b':')
+        && trimmed
+            .iter()
+            .all(|b| b.is_ascii_hexdigit() || *b == b'.' || *b == b':')
+}
+
+/// An error when decoding an `external ip` value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExternalIpError {
+    len: usize,
+}
+
+impl ExternalIpError {
+    /// Returns the length of the value.
+    pub fn actual_len(&self) -> usize {
+        self.len
+    }
+}
+
+impl fmt::Display for ExternalIpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid external ip of length {}", self.len)
+    }
+}
+
+impl error::Error for ExternalIpError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn round_trip() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+        let v6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        assert_eq!(decode_external_ip(&encode_external_ip(v4)), Ok(v4));
+        assert_eq!(decode_external_ip(&encode_external_ip(v6)), Ok(v6));
+    }
+
+    #[test]
+    fn text_values() {
+        assert_eq!(
+            decode_external_ip(b"203.0.113.7"),
+            Ok(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)))
+        );
+        assert_eq!(
+            decode_external_ip(b" 2001:db8::1\n"),
+            Ok(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)))
+        );
+        assert_eq!(
+            decode_external_ip(b"1::1"),
+            Ok(IpAddr::V6(Ipv6Addr::new(1, 0, 0, 0, 0, 0, 0, 1)))
+        );
+        assert_eq!(
+            decode_external_ip(b"2001:db8::1:2:34"),
+            Ok(IpAddr::V6(Ipv6Addr::new(
+                0x2001, 0xdb8, 0, 0, 0, 1, 2, 0x34
+            )))
+        );
+    }
+
+    #[test]
+    fn invalid_text_of_compact_length_is_binary() {
+        assert_eq!(
+            decode_external_ip(b"1:80"),
+            Ok(IpAddr::V4(Ipv4Addr::new(49, 58, 56, 48)))
+        );
+        assert_eq!(
+            decode_external_ip(b"1.23"),
+            Ok(IpAddr::V4(Ipv4Addr::new(49, 46, 50, 51)))
+        );
+        assert_eq!(
+            decode_external_ip(b"1.2.3.4.5.6.7.89"),
+            Ok(IpAddr::V6(Ipv6Addr::from(*b"1.2.3.4.5.6.7.89")))
+        );
+        assert!(decode_external_ip(b"1.2.3.4.5").is_err());
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_eq!(decode_external_ip(&[]).unwrap_err().actual_len(), 0);
+        assert_eq!(decode_external_ip(&[1, 2, 3]).unwrap_err().actual_len(), 3);
+        assert!(decode_external_ip(b"localhost").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! IPv4 addresses embedded in Teredo, 6to4, and NAT64 IPv6 addresses.
+
+use crate::{CompactAddrV4, CompactAddrV6};
+use std::net::Ipv4Addr;
+
+/// The NAT64 well-known prefix (`64:ff9b::/96`).
+const NAT64_PREFIX: [u8; 12] = [0x00, 0x64, 0xff, 0x9b, 0, 0, 0, 0, 0, 0, 0, 0];
+
+impl CompactAddrV6 {
+    /// Returns true if the IP address is a Teredo address (`2001::/32`).
+    pub fn is_teredo(&self) -> bool {
+        self.0[0..4] == [0x20, 0x01, 0x00, 0x00]
+    }
+
+    /// Returns the Teredo server IPv4 address if the IP address is a Teredo
+    /// address.
+    pub fn teredo_server(&self) -> Option<Ipv4Addr> {
+        if !self.is_teredo() {
+            return None;
+        }
+        Some(Ipv4Addr::new(self.0[4], self.0[5], self.0[6], self.0[7]))
+    }
+
+    /// Returns the Teredo client's public IPv4 address and port if the IP
+    /// address is a Teredo address.
+    ///
+    /// The obfuscated client address and port embedded in the IP address are
+    /// returned. The compact address's own port is ignored.
+    pub fn teredo_client(&self) -> Option<CompactAddrV4> {
+        if !self.is_teredo() {
+            return None;
+        }
+        let mut a: [u8; 6] = [0; 6];
+        a[0..4].copy_from_slice(&self.0[12..16]);
+        a[4..6].copy_from_slice(&self.0[10..12]);
+        for b in &mut a {
+            *b ^= 0xff;
+        }
+        Some(CompactAddrV4(a))
+    }
+
+    /// Returns true if the IP address is a 6to4 address (`2002::/16`).
+    pub fn is_6to4(&self) -> bool {
+        self.0[0..2] == [0x20, 0x02]
+    }
+
+    /// Returns the embedded IPv4 address and the port if the IP address is a
+    /// 6to4 address.
+    pub fn sixto4_v4(&self) -> Option<CompactAddrV4> {
+        if !self.is_6to4() {
+            return None;
+        }
+        let mut a: [u8; 6] = [0; 6];
+        a[0..4].copy_from_slice(&self.0[2..6]);
+        a[4..6].copy_from_slice(&self.0[16..18]);
+        Some(CompactAddrV4(a))
+    }
+
+    /// Returns the embedded IPv4 address and port if the IP address is a
+    /// Teredo or 6to4 address.
+    pub fn embedded_v4(&self) -> Option<CompactAddrV4> {
+        self.teredo_client().or_else(|| self.sixto4_v4())
+    }
+
+    /// Returns true if the IP address has the NAT64 well-known prefix
+    /// (`64:ff9b::/96`).
+    pub fn is_nat64(&self) -> bool {
+        self.0[0..12] == NAT64_PREFIX
+    }
+
+    /// Returns the embedded IPv4 address and the port if the IP address has
+    /// the NAT64 well-known prefix.
+    pub fn nat64_v4(&self) -> Option<CompactAddrV4> {
+        self.nat64_v4_with_prefix(&NAT64_PREFIX)
+    }
+
+    /// Returns the embedded IPv4 address and the port if the IP address has
+    /// the network-specific /96 NAT64 prefix.
+    pub fn nat64_v4_with_prefix(&self, prefix: &[u8; 12]) -> Option<CompactAddrV4> {
+        if self.0[0..12] != prefix[..] {
+            return None;
+        }
+        let mut a: [u8; 6] = [0; 6];
+        a.copy_from_slice(&self.0[12..18]);
+        Some(CompactAddrV4(a))
+    }
+}
+
+impl CompactAddrV4 {
+    /// Returns the NAT64 address with the well-known prefix (`64:ff9b::/96`)
+    /// and the same port.
+    pub fn to_nat64(&self) -> CompactAddrV6 {
+        self.to_nat64_with_prefix(&NAT64_PREFIX)
+    }
+
+    /// Returns the NAT64 address with the network-specific /96 prefix and the
+    /// same port.
+    pub fn to_nat64_with_prefix(&self, prefix: &[u8; 12]) -> CompactAddrV6 {
+        let mut a: [u8; 18] = [0; 18];
+        a[0..12].copy_from_slice(prefix);
+        a[12..18].copy_from_slice(&self.0);
+        CompactAddrV6(a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddrV6;
+
+    fn v6(s: &str) -> CompactAddrV6 {
+        CompactAddrV6::from(s.parse::<SocketAddrV6>().unwrap())
+    }
+
+    fn v4(s: &str) -> CompactAddrV4 {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn teredo() {
+        // RFC 4380 section 4
+        let addr = v6("[2001:0:4136:e378:8000:63bf:3fff:fdd2]:6881");
+        assert!(addr.is_teredo());
+        assert!(!addr.is_6to4());
+        assert_eq!(addr.teredo_server(), Some(Ipv4Addr::new(65, 54, 227, 120)));
+        assert_eq!(addr.teredo_client(), Some(v4("192.0.2.45:40000")));
+        assert_eq!(addr.embedded_v4(), Some(v4("192.0.2.45:40000")));
+    }
+
+    #[test]
+    fn sixto4() {
+        let addr = v6("[2002:c000:0201::1]:6881");
+        assert!(addr.is_6to4());
+        assert!(!addr.is_teredo());
+        assert_eq!(addr.teredo_server(), None);
+        assert_eq!(addr.sixto4_v4(), Some(v4("192.0.2.1:6881")));
+        assert_eq!(addr.embedded_v4(), Some(v4("192.0.2.1:6881")));
+    }
+
+    #[test]
+    fn not_embedded() {
+        let addr = v6("[2001:db8::1]:80");
+        assert!(!addr.is_teredo());
+        assert!(!addr.is_6to4());
+        assert_eq!(addr.teredo_client(), None);
+        assert_eq!(addr.sixto4_v4(), None);
+        assert_eq!(addr.embedded_v4(), None);
+    }
+
+    #[test]
+    fn nat64_round_trip() {
+        let addr = v4("192.0.2.33:6881");
+        let nat64 = addr.to_nat64();
+        assert_eq!(nat64, v6("[64:ff9b::c000:221]:6881"));
+        assert!(nat64.is_nat64());
+        assert_eq!(nat64.nat64_v4(), Some(addr));
+        assert_eq!(v6("[64:ff9b:1::c000:221]:6881").nat64_v4(), None);
+        assert!(!v6("[::ffff:192.0.2.33]:6881").is_nat64());
+    }
+
+    #[test]
+    fn nat64_network_specific_prefix() {
+        let prefix = [0x20, 0x01, 0x0d, 0xb8, 0, 0x64, 0, 0, 0, 0, 0, 0];
+        let addr = v4("192.0.2.33:80");
+        let nat64 = addr.to_nat64_with_prefix(&prefix);
+        assert_eq!(nat64, v6("[2001:db8:64::c000:221]:80"));
+        assert!(!nat64.is_nat64());
+        assert_eq!(nat64.nat64_v4(), None);
+        assert_eq!(nat64.nat64_v4_with_prefix(&prefix), Some(addr));
+    }
+}
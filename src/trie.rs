@@ -0,0 +1,813 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A radix trie map keyed by compact address bytes.
+//!
+//! Keys are [`CompactPrefix`]es: the leading bits of a compact address. A
+//! prefix over the whole compact address is a single socket address, and a
+//! prefix over the IP address bits is a network such as `10.0.0.0/8`.
+//!
+//! Entries are ordered in the byte-lexicographic order of the compact
+//! addresses with IPv4 addresses before IPv6 addresses. A prefix is ordered
+//! before the longer prefixes it contains.
+
+use crate::{CompactAddr, CompactAddrV4, CompactAddrV6};
+use std::{
+    cmp::Ordering,
+    fmt,
+    iter::FromIterator,
+    net::{IpAddr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    ops::{Bound, RangeBounds},
+};
+
+const V4_BITS: u8 = 48;
+const V6_BITS: u8 = 144;
+
+/// The leading bits of a compact address.
+///
+/// The bits after the prefix length are zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CompactPrefix {
+    addr: CompactAddr,
+    len: u8,
+}
+
+impl CompactPrefix {
+    /// Instantiates a prefix of the leading bits of the compact address.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length is greater than the number of bits in the compact
+    /// address (48 for IPv4 and 144 for IPv6).
+    pub fn new(addr: CompactAddr, len: u8) -> Self {
+        let max_len = max_len(&addr);
+        assert!(
+            len <= max_len,
+            "prefix length {} exceeds {} bits",
+            len,
+            max_len
+        );
+        let mut bytes = Key::from(addr).bytes;
+        mask(&mut bytes, len);
+        Self::from_key(Key {
+            v6: addr.is_ipv6(),
+            bytes,
+            len,
+        })
+    }
+
+    /// Instantiates a prefix of the leading bits of the IP address.
+    ///
+    /// The prefix matches addresses in the network with any port.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the prefix length is greater than the number of bits in the
+    /// IP address.
+    pub fn ip(ip: IpAddr, prefix_len: u8) -> Self {
+        let ip_bits = match ip {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        assert!(
+            prefix_len <= ip_bits,
+            "prefix length {} exceeds {} bits",
+            prefix_len,
+            ip_bits
+        );
+        Self::new(CompactAddr::from(SocketAddr::new(ip, 0)), prefix_len)
+    }
+
+    /// Returns the compact address with the bits after the prefix set to
+    /// zero.
+    pub fn addr(&self) -> CompactAddr {
+        self.addr
+    }
+
+    /// Returns the number of leading bits in the prefix.
+    pub fn len(&self) -> u8 {
+        self.len
+    }
+
+    /// Returns true if the prefix has no bits and matches every address of
+    /// the family.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if the prefix is a whole compact address.
+    pub fn is_addr(&self) -> bool {
+        self.len == max_len(&self.addr)
+    }
+
+    /// Returns true if the address starts with the prefix.
+    pub fn contains<A: Into<CompactAddr>>(&self, addr: A) -> bool {
+        let addr = Key::from(addr.into());
+        let key = Key::from(*self);
+        key.v6 == addr.v6 && common_len(&key.bytes, &addr.bytes, key.len) == key.len
+    }
+
+    fn from_key(key: Key) -> Self {
+        let addr = if key.v6 {
+            let mut a: [u8; 18] = [0; 18];
+            a.copy_from_slice(&key.bytes);
+            CompactAddr::V6(CompactAddrV6::new(a))
+        } else {
+            let mut a: [u8; 6] = [0; 6];
+            a.copy_from_slice(&key.bytes[0..6]);
+            CompactAddr::V4(CompactAddrV4::new(a))
+        };
+        Self { addr, len: key.len }
+    }
+}
+
+impl fmt::Display for CompactPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ip_bits = max_len(&self.addr) - 16;
+        if self.is_addr() {
+            fmt::Display::fmt(&self.addr, f)
+        } else if self.len <= ip_bits {
+            write!(f, "{}/{}", self.addr.ip(), self.len)
+        } else {
+            write!(f, "{}/{}", self.addr, self.len)
+        }
+    }
+}
+
+impl From<CompactAddr> for CompactPrefix {
+    fn from(addr: CompactAddr) -> Self {
+        Self {
+            addr,
+            len: max_len(&addr),
+        }
+    }
+}
+
+impl From<CompactAddrV4> for CompactPrefix {
+    fn from(addr: CompactAddrV4) -> Self {
+        Self::from(CompactAddr::V4(addr))
+    }
+}
+
+impl From<CompactAddrV6> for CompactPrefix {
+    fn from(addr: CompactAddrV6) -> Self {
+        Self::from(CompactAddr::V6(addr))
+    }
+}
+
+impl From<SocketAddr> for CompactPrefix {
+    fn from(addr: SocketAddr) -> Self {
+        Self::from(CompactAddr::from(addr))
+    }
+}
+
+impl From<SocketAddrV4> for CompactPrefix {
+    fn from(addr: SocketAddrV4) -> Self {
+        Self::from(CompactAddr::from(addr))
+    }
+}
+
+impl From<SocketAddrV6> for CompactPrefix {
+    fn from(addr: SocketAddrV6) -> Self {
+        Self::from(CompactAddr::from(addr))
+    }
+}
+
+#[cfg(feature = "ipnet")]
+impl From<ipnet::IpNet> for CompactPrefix {
+    fn from(net: ipnet::IpNet) -> Self {
+        Self::ip(net.network(), net.prefix_len())
+    }
+}
+
+#[cfg(feature = "ipnet")]
+impl From<ipnet::Ipv4Net> for CompactPrefix {
+    fn from(net: ipnet::Ipv4Net) -> Self {
+        Self::ip(IpAddr::V4(net.network()), net.prefix_len())
+    }
+}
+
+#[cfg(feature = "ipnet")]
+impl From<ipnet::Ipv6Net> for CompactPrefix {
+    fn from(net: ipnet::Ipv6Net) -> Self {
+        Self::ip(IpAddr::V6(net.network()), net.prefix_len())
+    }
+}
+
+/// A map from compact address prefixes to values.
+///
+/// The map is a binary radix trie with one node per branch point, so
+/// addresses which share leading bits share nodes.
+#[derive(Clone)]
+pub struct CompactIpMap<V> {
+    v4: Option<Box<Node<V>>>,
+    v6: Option<Box<Node<V>>>,
+    len: usize,
+}
+
+impl<V> Default for CompactIpMap<V> {
+    fn default() -> Self {
+        Self {
+            v4: None,
+            v6: None,
+            len: 0,
+        }
+    }
+}
+
+impl<V> CompactIpMap<V> {
+    /// Instantiates an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.v4 = None;
+        self.v6 = None;
+        self.len = 0;
+    }
+
+    /// Inserts the value for the prefix.
+    ///
+    /// Returns the previous value for the prefix.
+    pub fn insert<K: Into<CompactPrefix>>(&mut self, key: K, value: V) -> Option<V> {
+        let key = Key::from(key.into());
+        let previous = insert(self.root_mut(key.v6), &key, value);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    /// Returns the value for the prefix.
+    pub fn get<K: Into<CompactPrefix>>(&self, key: K) -> Option<&V> {
+        let key = Key::from(key.into());
+        let mut node = self.root(key.v6).as_deref()?;
+        loop {
+            if node.len > key.len || common_len(&node.prefix, &key.bytes, node.len) < node.len {
+                return None;
+            }
+            if node.len == key.len {
+                return node.value.as_ref();
+            }
+            node = node.children[bit(&key.bytes, node.len)].as_deref()?;
+        }
+    }
+
+    /// Returns the mutable value for the prefix.
+    pub fn get_mut<K: Into<CompactPrefix>>(&mut self, key: K) -> Option<&mut V> {
+        let key = Key::from(key.into());
+        let mut node = self.root_mut(key.v6).as_deref_mut()?;
+        loop {
+            if node.len > key.len || common_len(&node.prefix, &key.bytes, node.len) < node.len {
+                return None;
+            }
+            if node.len == key.len {
+                return node.value.as_mut();
+            }
+            node = node.children[bit(&key.bytes, node.len)].as_deref_mut()?;
+        }
+    }
+
+    /// Returns true if there is a value for the prefix.
+    pub fn contains_key<K: Into<CompactPrefix>>(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes the value for the prefix.
+    pub fn remove<K: Into<CompactPrefix>>(&mut self, key: K) -> Option<V> {
+        let key = Key::from(key.into());
+        let value = remove(self.root_mut(key.v6), &key)?;
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Returns the longest prefix which contains the address and its value.
+    ///
+    /// An entry for the whole address is the longest possible match.
+    pub fn longest_match<A: Into<CompactAddr>>(&self, addr: A) -> Option<(CompactPrefix, &V)> {
+        let key = Key::from(addr.into());
+        let mut best = None;
+        let mut next = self.root(key.v6).as_deref();
+        while let Some(node) = next {
+            if common_len(&node.prefix, &key.bytes, node.len) < node.len {
+                break;
+            }
+            if let Some(value) = &node.value {
+                best = Some((node, value));
+            }
+            if node.len == key.len {
+                break;
+            }
+            next = node.children[bit(&key.bytes, node.len)].as_deref();
+        }
+        best.map(|(node, value)| (node.prefix(key.v6), value))
+    }
+
+    /// Returns an iterator over the entries in order.
+    pub fn iter(&self) -> Iter<'_, V> {
+        self.range(..)
+    }
+
+    /// Returns an iterator over the entries whose prefix address is in the
+    /// range, in order.
+    ///
+    /// The prefix address is the compact address with the bits after the
+    /// prefix set to zero. See [`CompactPrefix::addr`].
+    pub fn range<R: RangeBounds<CompactAddr>>(&self, range: R) -> Iter<'_, V> {
+        let mut stack = Vec::new();
+        if let Some(node) = &self.v6 {
+            stack.push((true, &**node));
+        }
+        if let Some(node) = &self.v4 {
+            stack.push((false, &**node));
+        }
+        Iter {
+            stack,
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+        }
+    }
+
+    fn root(&self, v6: bool) -> &Option<Box<Node<V>>> {
+        if v6 {
+            &self.v6
+        } else {
+            &self.v4
+        }
+    }
+
+    fn root_mut(&mut self, v6: bool) -> &mut Option<Box<Node<V>>> {
+        if v6 {
+            &mut self.v6
+        } else {
+            &mut self.v4
+        }
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for CompactIpMap<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Into<CompactPrefix>, V> Extend<(K, V)> for CompactIpMap<V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K: Into<CompactPrefix>, V> FromIterator<(K, V)> for CompactIpMap<V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<'a, V> IntoIterator for &'a CompactIpMap<V> {
+    type Item = (CompactPrefix, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a [`CompactIpMap`].
+pub struct Iter<'a, V> {
+    stack: Vec<(bool, &'a Node<V>)>,
+    start: Bound<CompactAddr>,
+    end: Bound<CompactAddr>,
+}
+
+impl<'a, V> Iter<'a, V> {
+    fn is_before_start(&self, addr: &CompactAddr) -> bool {
+        match &self.start {
+            Bound::Included(start) => addr < start,
+            Bound::Excluded(start) => addr <= start,
+            Bound::Unbounded => false,
+        }
+    }
+
+    fn is_after_end(&self, addr: &CompactAddr) -> bool {
+        match &self.end {
+            Bound::Included(end) => addr > end,
+            Bound::Excluded(end) => addr >= end,
+            Bound::Unbounded => false,
+        }
+    }
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (CompactPrefix, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((v6, node)) = self.stack.pop() {
+            let prefix = node.prefix(v6);
+            // Entries are visited in order, so no later entry is in range.
+            if self.is_after_end(&prefix.addr) {
+                self.stack.clear();
+                return None;
+            }
+            // Skip the subtree if its greatest address is before the start.
+            if self.is_before_start(&node.last_addr(v6)) {
+                continue;
+            }
+
+            for child in node.children.iter().rev().flatten() {
+                self.stack.push((v6, &**child));
+            }
+            if let Some(value) = &node.value {
+                if !self.is_before_start(&prefix.addr) {
+                    return Some((prefix, value));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<'a, V> fmt::Debug for Iter<'a, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Iter")
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .finish()
+    }
+}
+
+#[derive(Clone)]
+struct Node<V> {
+    prefix: [u8; 18],
+    len: u8,
+    value: Option<V>,
+    children: [Option<Box<Node<V>>>; 2],
+}
+
+impl<V> Node<V> {
+    fn leaf(key: &Key, value: V) -> Box<Self> {
+        Box::new(Self {
+            prefix: key.bytes,
+            len: key.len,
+            value: Some(value),
+            children: [None, None],
+        })
+    }
+
+    fn prefix(&self, v6: bool) -> CompactPrefix {
+        CompactPrefix::from_key(Key {
+            v6,
+            bytes: self.prefix,
+            len: self.len,
+        })
+    }
+
+    /// Returns the greatest address with the node's prefix.
+    fn last_addr(&self, v6: bool) -> CompactAddr {
+        let mut bytes = self.prefix;
+        let max_len = if v6 { V6_BITS } else { V4_BITS };
+        for idx in self.len..max_len {
+            bytes[usize::from(idx / 8)] |= 0x80 >> (idx % 8);
+        }
+        CompactPrefix::from_key(Key {
+            v6,
+            bytes,
+            len: max_len,
+        })
+        .addr
+    }
+}
+
+struct Key {
+    v6: bool,
+    bytes: [u8; 18],
+    len: u8,
+}
+
+impl From<CompactAddr> for Key {
+    fn from(addr: CompactAddr) -> Self {
+        let mut bytes: [u8; 18] = [0; 18];
+        bytes[..addr.as_bytes().len()].copy_from_slice(addr.as_bytes());
+        Self {
+            v6: addr.is_ipv6(),
+            bytes,
+            len: max_len(&addr),
+        }
+    }
+}
+
+impl From<CompactPrefix> for Key {
+    fn from(prefix: CompactPrefix) -> Self {
+        Self {
+            len: prefix.len,
+            ..Self::from(prefix.addr)
+        }
+    }
+}
+
+fn max_len(addr: &CompactAddr) -> u8 {
+    match addr {
+        CompactAddr::V4(_) => V4_BITS,
+        CompactAddr::V6(_) => V6_BITS,
+    }
+}
+
+fn bit(bytes: &[u8; 18], idx: u8) -> usize {
+    usize::from((bytes[usize::from(idx / 8)] >> (7 - idx % 8)) & 1)
+}
+
+fn mask(bytes: &mut [u8; 18], len: u8) {
+    let full = usize::from(len / 8);
+    if full < bytes.len() {
+        let rem = len % 8;
+        bytes[full] &= !(0xff >> rem);
+        for b in &mut bytes[full + 1..] {
+            *b = 0;
+        }
+    }
+}
+
+/// Returns the number of equal leading bits, up to the maximum.
+fn common_len(a: &[u8; 18], b: &[u8; 18], max: u8) -> u8 {
+    let mut len = 0;
+    for (x, y) in a.iter().zip(b.iter()) {
+        let diff = x ^ y;
+        if diff != 0 {
+            len += diff.leading_zeros() as u8;
+            break;
+        }
+        len += 8;
+        if len >= max {
+            break;
+        }
+    }
+    len.min(max)
+}
+
+fn insert<V>(slot: &mut Option<Box<Node<V>>>, key: &Key, value: V) -> Option<V> {
+    let node = match slot {
+        Some(node) => node,
+        None => {
+            *slot = Some(Node::leaf(key, value));
+            return None;
+        }
+    };
+
+    let common = common_len(&node.prefix, &key.bytes, node.len.min(key.len));
+    match (common.cmp(&node.len), common.cmp(&key.len)) {
+        (Ordering::Equal, Ordering::Equal) => node.value.replace(value),
+        (Ordering::Equal, _) => {
+            let b = bit(&key.bytes, node.len);
+            insert(&mut node.children[b], key, value)
+        }
+        (_, Ordering::Equal) => {
+            // The key is a prefix of the node.
+            let old = slot.take().unwrap();
+            let b = bit(&old.prefix, key.len);
+            let mut parent = Node::leaf(key, value);
+            parent.children[b] = Some(old);
+            *slot = Some(parent);
+            None
+        }
+        _ => {
+            // The key and the node diverge after the common bits.
+            let old = slot.take().unwrap();
+            let mut prefix = key.bytes;
+            mask(&mut prefix, common);
+            let mut branch = Box::new(Node {
+                prefix,
+                len: common,
+                value: None,
+                children: [None, None],
+            });
+            let old_bit = bit(&old.prefix, common);
+            branch.children[old_bit] = Some(old);
+            branch.children[bit(&key.bytes, common)] = Some(Node::leaf(key, value));
+            *slot = Some(branch);
+            None
+        }
+    }
+}
+
+fn remove<V>(slot: &mut Option<Box<Node<V>>>, key: &Key) -> Option<V> {
+    let node = slot.as_mut()?;
+    if node.len > key.len || common_len(&node.prefix, &key.bytes, node.len) < node.len {
+        return None;
+    }
+    let value = if node.len == key.len {
+        node.value.take()?
+    } else {
+        let b = bit(&key.bytes, node.len);
+        remove(&mut node.children[b], key)?
+    };
+
+    // Remove nodes which no longer hold a value or branch.
+    if node.value.is_none() {
+        match node.children {
+            [None, None] => *slot = None,
+            [Some(_), None] | [None, Some(_)] => {
+                let child = node.children.iter_mut().find_map(Option::take);
+                *slot = child;
+            }
+            [Some(_), Some(_)] => {}
+        }
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::addr;
+
+    fn net(ip: &str, prefix_len: u8) -> CompactPrefix {
+        CompactPrefix::ip(ip.parse().unwrap(), prefix_len)
+    }
+
+    fn node_count<V>(slot: &Option<Box<Node<V>>>) -> usize {
+        slot.as_ref().map_or(0, |node| {
+            1 + node_count(&node.children[0]) + node_count(&node.children[1])
+        })
+    }
+
+    fn keys<V>(iter: Iter<'_, V>) -> Vec<CompactPrefix> {
+        iter.map(|(prefix, _)| prefix).collect()
+    }
+
+    #[test]
+    fn prefix_before_longer_key() {
+        let mut map = CompactIpMap::new();
+        map.insert(net("10.0.0.0", 8), 8);
+        map.insert(net("10.1.0.0", 16), 16);
+        assert_eq!(node_count(&map.v4), 2);
+        assert_eq!(map.get(net("10.0.0.0", 8)), Some(&8));
+        assert_eq!(map.get(net("10.1.0.0", 16)), Some(&16));
+        assert_eq!(map.get(net("10.0.0.0", 16)), None);
+    }
+
+    #[test]
+    fn prefix_after_longer_key() {
+        let mut map = CompactIpMap::new();
+        map.insert(net("10.1.0.0", 16), 16);
+        map.insert(net("10.0.0.0", 8), 8);
+        assert_eq!(node_count(&map.v4), 2);
+        assert_eq!(map.v4.as_ref().unwrap().len, 8);
+        assert_eq!(map.get(net("10.0.0.0", 8)), Some(&8));
+        assert_eq!(map.get(net("10.1.0.0", 16)), Some(&16));
+        assert_eq!(
+            keys(map.iter()),
+            vec![net("10.0.0.0", 8), net("10.1.0.0", 16)]
+        );
+    }
+
+    #[test]
+    fn diverging_keys_split() {
+        let mut map = CompactIpMap::new();
+        map.insert(net("10.1.0.0", 16), 1);
+        map.insert(net("10.2.0.0", 16), 2);
+
+        let root = map.v4.as_ref().unwrap();
+        assert_eq!(root.len, 14);
+        assert!(root.value.is_none());
+        assert_eq!(node_count(&map.v4), 3);
+        assert_eq!(map.get(net("10.0.0.0", 14)), None);
+        assert_eq!(map.get(net("10.1.0.0", 16)), Some(&1));
+        assert_eq!(map.get(net("10.2.0.0", 16)), Some(&2));
+    }
+
+    #[test]
+    fn remove_collapses_branch() {
+        let mut map = CompactIpMap::new();
+        map.insert(net("10.1.0.0", 16), 1);
+        map.insert(net("10.2.0.0", 16), 2);
+        assert_eq!(map.remove(net("10.2.0.0", 16)), Some(2));
+
+        let root = map.v4.as_ref().unwrap();
+        assert_eq!(root.len, 16);
+        assert_eq!(root.value, Some(1));
+        assert_eq!(node_count(&map.v4), 1);
+
+        assert_eq!(map.remove(net("10.1.0.0", 16)), Some(1));
+        assert!(map.v4.is_none());
+    }
+
+    #[test]
+    fn remove_collapses_valueless_parent() {
+        let mut map = CompactIpMap::new();
+        map.insert(net("10.0.0.0", 8), 8);
+        map.insert(net("10.1.0.0", 16), 16);
+        assert_eq!(map.remove(net("10.0.0.0", 8)), Some(8));
+        assert_eq!(node_count(&map.v4), 1);
+        assert_eq!(map.get(net("10.1.0.0", 16)), Some(&16));
+    }
+
+    #[test]
+    fn longest_match_nested() {
+        let mut map = CompactIpMap::new();
+        map.insert(net("10.0.0.0", 8), 8);
+        map.insert(net("10.1.0.0", 16), 16);
+        map.insert(net("10.1.2.0", 24), 24);
+        map.insert(addr("10.1.2.3:80"), 48);
+
+        let matched = |s| map.longest_match(addr(s)).map(|(_, value)| *value);
+        assert_eq!(matched("10.1.2.3:80"), Some(48));
+        assert_eq!(matched("10.1.2.3:81"), Some(24));
+        assert_eq!(matched("10.1.3.3:80"), Some(16));
+        assert_eq!(matched("10.2.2.3:80"), Some(8));
+        assert_eq!(matched("11.1.2.3:80"), None);
+        assert_eq!(matched("[::ffff:10.1.2.3]:80"), None);
+
+        assert_eq!(
+            map.longest_match(addr("10.1.9.9:1"))
+                .map(|(prefix, _)| prefix),
+            Some(net("10.1.0.0", 16))
+        );
+    }
+
+    #[test]
+    fn range_bounds_across_families() {
+        let entries = ["10.0.0.1:80", "10.0.0.2:80", "[::1]:80", "[2001:db8::1]:80"];
+        let map = entries
+            .iter()
+            .map(|s| (addr(s), ()))
+            .collect::<CompactIpMap<_>>();
+        let all = entries
+            .iter()
+            .map(|s| CompactPrefix::from(addr(s)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(keys(map.iter()), all);
+        assert_eq!(keys(map.range(addr("10.0.0.2:80")..)), all[1..]);
+        assert_eq!(
+            keys(map.range((
+                Bound::Excluded(addr("10.0.0.1:80")),
+                Bound::Included(addr("[::1]:80"))
+            ))),
+            all[1..3]
+        );
+        assert_eq!(
+            keys(map.range(addr("10.0.0.2:80")..addr("[2001:db8::1]:80"))),
+            all[1..3]
+        );
+        assert_eq!(keys(map.range(..=addr("10.0.0.2:80"))), all[..2]);
+        assert_eq!(keys(map.range(addr("[::]:0")..)), all[2..]);
+        assert!(map
+            .range((Bound::Excluded(addr("[2001:db8::1]:80")), Bound::Unbounded))
+            .next()
+            .is_none());
+    }
+
+    #[test]
+    fn len_after_replace_and_remove() {
+        let mut map = CompactIpMap::new();
+        assert_eq!(map.insert(net("10.0.0.0", 8), 1), None);
+        assert_eq!(map.insert(net("10.0.0.0", 8), 2), Some(1));
+        assert_eq!(map.len(), 1);
+
+        map.insert(net("::", 0), 3);
+        assert_eq!(map.len(), 2);
+
+        assert_eq!(map.remove(net("10.0.0.0", 16)), None);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.remove(net("10.0.0.0", 8)), Some(2));
+        assert_eq!(map.remove(net("10.0.0.0", 8)), None);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.remove(net("::", 0)), Some(3));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn prefix_masks_and_contains() {
+        let prefix = CompactPrefix::new(addr("10.1.2.3:80"), 16);
+        assert_eq!(prefix.addr(), addr("10.1.0.0:0"));
+        assert!(prefix.contains(addr("10.1.255.255:1")));
+        assert!(!prefix.contains(addr("10.2.0.0:0")));
+        assert!(CompactPrefix::from(addr("10.1.2.3:80")).is_addr());
+    }
+
+    #[test]
+    #[should_panic]
+    fn prefix_too_long() {
+        CompactPrefix::ip("10.0.0.0".parse().unwrap(), 33);
+    }
+}
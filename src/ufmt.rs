@@ -0,0 +1,284 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! [`ufmt`] formatting of compact addresses.
+//!
+//! `uDisplay` output is the same as `Display`, and `uDebug` output is the same
+//! as `Debug`.
+//!
+//! The crate still requires `std`, so the feature does not make it usable on
+//! `no_std` targets. It only avoids pulling in `core::fmt` machinery for
+//! address formatting on `std` targets where that code size matters, such as
+//! `wasm32-unknown-unknown`.
+
+use crate::{CompactAddr, CompactAddrV4, CompactAddrV4Ref, CompactAddrV6, CompactAddrV6Ref};
+use ::ufmt::{uDebug, uDisplay, uWrite, uwrite, Formatter};
+
+/// Writes an IPv4 socket address as `ip:port`.
+fn write_v4<W>(f: &mut Formatter<'_, W>, bytes: &[u8; 6]) -> Result<(), W::Error>
+where
+    W: uWrite + ?Sized,
+{
+    write_ipv4(f, &[bytes[0], bytes[1], bytes[2], bytes[3]])?;
+    uwrite!(f, ":{}", u16::from_be_bytes([bytes[4], bytes[5]]))
+}
+
+/// Writes an IPv6 socket address as `[ip]:port`.
+fn write_v6<W>(f: &mut Formatter<'_, W>, bytes: &[u8; 18]) -> Result<(), W::Error>
+where
+    W: uWrite + ?Sized,
+{
+    f.write_str("[")?;
+    write_ipv6(f, bytes)?;
+    uwrite!(f, "]:{}", u16::from_be_bytes([bytes[16], bytes[17]]))
+}
+
+fn write_ipv4<W>(f: &mut Formatter<'_, W>, ip: &[u8; 4]) -> Result<(), W::Error>
+where
+    W: uWrite + ?Sized,
+{
+    uwrite!(f, "{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3])
+}
+
+/// Writes the IPv6 address in the first 16 bytes in the same form as the
+/// standard library (RFC 5952).
+fn write_ipv6<W>(f: &mut Formatter<'_, W>, bytes: &[u8; 18]) -> Result<(), W::Error>
+where
+    W: uWrite + ?Sized,
+{
+    let mut segments: [u16; 8] = [0; 8];
+    for (idx, segment) in segments.iter_mut().enumerate() {
+        *segment = u16::from_be_bytes([bytes[2 * idx], bytes[2 * idx + 1]]);
+    }
+
+    if segments[..5].iter().all(|s| *s == 0) && segments[5] == 0xffff {
+        f.write_str("::ffff:")?;
+        return write_ipv4(f, &[bytes[12], bytes[13], bytes[14], bytes[15]]);
+    }
+
+    // The longest run of at least two zero segments is elided.
+    let mut zeros = (0, 0);
+    let mut run = (0, 0);
+    for (idx, segment) in segments.iter().enumerate() {
+        if *segment == 0 {
+            if run.1 == 0 {
+                run.0 = idx;
+            }
+            run.1 += 1;
+            if run.1 > zeros.1 {
+                zeros = run;
+            }
+        } else {
+            run.1 = 0;
+        }
+    }
+
+    if zeros.1 > 1 {
+        write_segments(f, &segments[..zeros.0])?;
+        f.write_str("::")?;
+        write_segments(f, &segments[zeros.0 + zeros.1..])
+    } else {
+        write_segments(f, &segments)
+    }
+}
+
+fn write_segments<W>(f: &mut Formatter<'_, W>, segments: &[u16]) -> Result<(), W::Error>
+where
+    W: uWrite + ?Sized,
+{
+    for (idx, segment) in segments.iter().enumerate() {
+        if idx != 0 {
+            f.write_str(":")?;
+        }
+        uwrite!(f, "{:x}", *segment)?;
+    }
+    Ok(())
+}
+
+impl uDisplay for CompactAddrV4 {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        write_v4(f, self.as_bytes())
+    }
+}
+
+impl uDebug for CompactAddrV4 {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        f.debug_tuple("CompactAddrV4")?
+            .field(self.as_bytes())?
+            .finish()
+    }
+}
+
+impl uDisplay for CompactAddrV6 {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        write_v6(f, self.as_bytes())
+    }
+}
+
+impl uDebug for CompactAddrV6 {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        f.debug_tuple("CompactAddrV6")?
+            .field(self.as_bytes())?
+            .finish()
+    }
+}
+
+impl uDisplay for CompactAddrV4Ref<'_> {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        write_v4(f, self.as_bytes())
+    }
+}
+
+impl uDebug for CompactAddrV4Ref<'_> {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        f.debug_tuple("CompactAddrV4Ref")?
+            .field(self.as_bytes())?
+            .finish()
+    }
+}
+
+impl uDisplay for CompactAddrV6Ref<'_> {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        write_v6(f, self.as_bytes())
+    }
+}
+
+impl uDebug for CompactAddrV6Ref<'_> {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        f.debug_tuple("CompactAddrV6Ref")?
+            .field(self.as_bytes())?
+            .finish()
+    }
+}
+
+impl uDisplay for CompactAddr {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        match self {
+            CompactAddr::V4(addr) => uDisplay::fmt(addr, f),
+            CompactAddr::V6(addr) => uDisplay::fmt(addr, f),
+        }
+    }
+}
+
+impl uDebug for CompactAddr {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        match self {
+            CompactAddr::V4(addr) => f.debug_tuple("V4")?.field(addr)?.finish(),
+            CompactAddr::V6(addr) => f.debug_tuple("V6")?.field(addr)?.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::ufmt::uwrite;
+    use std::{convert::Infallible, net::SocketAddr};
+
+    struct Buf(String);
+
+    impl uWrite for Buf {
+        type Error = Infallible;
+
+        fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+            self.0.push_str(s);
+            Ok(())
+        }
+    }
+
+    fn display<T: uDisplay>(value: &T) -> String {
+        let mut buf = Buf(String::new());
+        uwrite!(buf, "{}", value).unwrap();
+        buf.0
+    }
+
+    fn debug<T: uDebug>(value: &T) -> String {
+        let mut buf = Buf(String::new());
+        uwrite!(buf, "{:?}", value).unwrap();
+        buf.0
+    }
+
+    #[test]
+    fn display_matches_std() {
+        for addr in [
+            "1.2.3.4:6881",
+            "0.0.0.0:0",
+            "255.255.255.255:65535",
+            "[::]:0",
+            "[::1]:80",
+            "[1::]:80",
+            "[2001:db8::1]:443",
+            "[2001:db8:0:1:1:1:1:1]:443",
+            "[2001:0:0:1::1]:443",
+            "[1:0:0:2:0:0:0:3]:1",
+            "[fe80::1:2:3:4]:1",
+            "[::ffff:192.0.2.1]:80",
+            "[::192.0.2.1]:80",
+            "[ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff]:65535",
+        ] {
+            let addr = CompactAddr::from(addr.parse::<SocketAddr>().unwrap());
+            assert_eq!(display(&addr), addr.to_string());
+            match addr {
+                CompactAddr::V4(addr) => {
+                    assert_eq!(display(&addr), addr.to_string());
+                    assert_eq!(display(&CompactAddrV4Ref::from(&addr)), addr.to_string());
+                }
+                CompactAddr::V6(addr) => {
+                    assert_eq!(display(&addr), addr.to_string());
+                    assert_eq!(display(&CompactAddrV6Ref::from(&addr)), addr.to_string());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn debug_matches_std() {
+        let v4 = CompactAddrV4::from([1, 2, 3, 4, 0, 80]);
+        let v6 = CompactAddrV6::from([6; 18]);
+        assert_eq!(debug(&v4), format!("{:?}", v4));
+        assert_eq!(debug(&v6), format!("{:?}", v6));
+        assert_eq!(
+            debug(&CompactAddr::V4(v4)),
+            format!("{:?}", CompactAddr::V4(v4))
+        );
+        assert_eq!(
+            debug(&CompactAddrV4Ref::from(&v4)),
+            format!("{:?}", CompactAddrV4Ref::from(&v4))
+        );
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Azureus/Vuze DHT contacts.
+//!
+//! A contact is serialized as:
+//!
+//! | Field          | Length      |
+//! |----------------|-------------|
+//! | Contact type   | 1           |
+//! | Version        | 1           |
+//! | Address length | 1           |
+//! | IP address     | 4 or 16     |
+//! | Port           | 2           |
+//!
+//! The only contact type is UDP (`0x01`). The address length is 4 for IPv4 and
+//! 16 for IPv6 addresses, and the port is big-endian.
+
+use crate::{
+    blob::{V4_LEN, V6_LEN},
+    CompactAddr, CompactAddrV4, CompactAddrV6,
+};
+use std::{error, fmt, net::IpAddr};
+
+/// The contact type of a UDP contact.
+pub const CONTACT_TYPE_UDP: u8 = 0x01;
+
+/// A Vuze DHT contact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VuzeContact {
+    addr: CompactAddr,
+    version: u8,
+}
+
+impl VuzeContact {
+    /// Instantiates a new contact with the DHT protocol version.
+    pub fn new<A: Into<CompactAddr>>(addr: A, version: u8) -> Self {
+        Self {
+            addr: addr.into(),
+            version,
+        }
+    }
+
+    /// Returns the compact address.
+    pub fn addr(&self) -> CompactAddr {
+        self.addr
+    }
+
+    /// Returns the DHT protocol version.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the IP address.
+    pub fn ip(&self) -> IpAddr {
+        self.addr.ip()
+    }
+
+    /// Returns the port.
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Returns the length of the encoding.
+    pub fn encoded_len(&self) -> usize {
+        3 + self.addr.as_ref().len()
+    }
+
+    /// Appends the encoding to the buffer.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let bytes = self.addr.as_ref();
+        buf.push(CONTACT_TYPE_UDP);
+        buf.push(self.version);
+        buf.push((bytes.len() - 2) as u8);
+        buf.extend_from_slice(bytes);
+    }
+
+    /// Returns the encoding.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.encode(&mut buf);
+        buf
+    }
+
+    /// Decodes a contact from the start of the bytes.
+    ///
+    /// Returns the contact and the remaining bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the contact type or address length is unknown or
+    /// there are not enough bytes for the contact.
+    pub fn decode(bytes: &[u8]) -> Result<(Self, &[u8]), VuzeError> {
+        if bytes.len() < 3 {
+            return Err(VuzeError::UnexpectedEnd {
+                expected: 3,
+                actual: bytes.len(),
+            });
+        }
+        if bytes[0] != CONTACT_TYPE_UDP {
+            return Err(VuzeError::UnknownContactType(bytes[0]));
+        }
+        let version = bytes[1];
+        let len = match usize::from(bytes[2]) {
+            4 => V4_LEN,
+            16 => V6_LEN,
+            _ => return Err(VuzeError::InvalidAddrLength(bytes[2])),
+        };
+
+        let rest = &bytes[3..];
+        if rest.len() < len {
+            return Err(VuzeError::UnexpectedEnd {
+                expected: 3 + len,
+                actual: bytes.len(),
+            });
+        }
+        let (addr, rest) = rest.split_at(len);
+        let addr = if len == V4_LEN {
+            let mut a: [u8; V4_LEN] = [0; V4_LEN];
+            a.copy_from_slice(addr);
+            CompactAddr::V4(CompactAddrV4::new(a))
+        } else {
+            let mut a: [u8; V6_LEN] = [0; V6_LEN];
+            a.copy_from_slice(addr);
+            CompactAddr::V6(CompactAddrV6::new(a))
+        };
+        Ok((Self { addr, version }, rest))
+    }
+}
+
+impl fmt::Display for VuzeContact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.addr, f)
+    }
+}
+
+/// An error when decoding a Vuze DHT contact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VuzeError {
+    /// The contact type is not UDP.
+    UnknownContactType(u8),
+    /// The address length is neither 4 nor 16.
+    InvalidAddrLength(u8),
+    /// The bytes ended before the contact was complete.
+    UnexpectedEnd {
+        /// The expected number of bytes.
+        expected: usize,
+        /// The actual number of bytes.
+        actual: usize,
+    },
+}
+
+impl fmt::Display for VuzeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VuzeError::UnknownContactType(value) => {
+                write!(f, "unknown contact type: {:#04x}", value)
+            }
+            VuzeError::InvalidAddrLength(len) => write!(f, "invalid address length: {}", len),
+            VuzeError::UnexpectedEnd { expected, actual } => write!(
+                f,
+                "unexpected end of contact: expected {} bytes, found {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl error::Error for VuzeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v4_round_trip() {
+        let contact = VuzeContact::new("1.2.3.4:6881".parse::<CompactAddr>().unwrap(), 50);
+        let bytes = contact.to_vec();
+        assert_eq!(bytes, [CONTACT_TYPE_UDP, 50, 4, 1, 2, 3, 4, 0x1a, 0xe1]);
+        assert_eq!(bytes.len(), contact.encoded_len());
+        assert_eq!(VuzeContact::decode(&bytes), Ok((contact, &[][..])));
+        assert_eq!(contact.version(), 50);
+        assert_eq!(contact.port(), 6881);
+        assert_eq!(contact.to_string(), "1.2.3.4:6881");
+    }
+
+    #[test]
+    fn v6_round_trip() {
+        let contact = VuzeContact::new("[2001:db8::1]:80".parse::<CompactAddr>().unwrap(), 1);
+        let mut bytes = contact.to_vec();
+        assert_eq!(bytes[..3], [CONTACT_TYPE_UDP, 1, 16]);
+        assert_eq!(bytes.len(), 3 + V6_LEN);
+        bytes.push(0xee);
+        assert_eq!(VuzeContact::decode(&bytes), Ok((contact, &[0xee][..])));
+    }
+
+    #[test]
+    fn decode_malformed() {
+        assert_eq!(
+            VuzeContact::decode(&[CONTACT_TYPE_UDP, 0]),
+            Err(VuzeError::UnexpectedEnd {
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            VuzeContact::decode(&[0x02, 0, 4]),
+            Err(VuzeError::UnknownContactType(0x02))
+        );
+        assert_eq!(
+            VuzeContact::decode(&[CONTACT_TYPE_UDP, 0, 6]),
+            Err(VuzeError::InvalidAddrLength(6))
+        );
+        assert_eq!(
+            VuzeContact::decode(&[CONTACT_TYPE_UDP, 0, 16, 0, 0]),
+            Err(VuzeError::UnexpectedEnd {
+                expected: 21,
+                actual: 5
+            })
+        );
+    }
+}
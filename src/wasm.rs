@@ -0,0 +1,147 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! JavaScript bindings for blobs with [`wasm_bindgen`](mod@wasm_bindgen).
+//!
+//! Blobs are passed to and returned from JavaScript as `Uint8Array`s.
+//! Addresses are passed as `ip:port` strings (IPv6 addresses are written as
+//! `[ip]:port`).
+
+use crate::{blob, CompactAddrV4, CompactAddrV6};
+use wasm_bindgen::prelude::*;
+
+/// Returns the addresses in a blob of compact IPv4 addresses.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length.
+#[wasm_bindgen(js_name = decodePeers)]
+pub fn decode_peers(blob: &[u8]) -> Result<Vec<String>, JsError> {
+    let addrs = blob::decode_v4(blob)?;
+    Ok(addrs.iter().map(ToString::to_string).collect())
+}
+
+/// Returns the addresses in a blob of compact IPv6 addresses.
+///
+/// # Errors
+///
+/// Returns an error if the blob length is not a multiple of the compact
+/// address length.
+#[wasm_bindgen(js_name = decodePeers6)]
+pub fn decode_peers6(blob: &[u8]) -> Result<Vec<String>, JsError> {
+    let addrs = blob::decode_v6(blob)?;
+    Ok(addrs.iter().map(ToString::to_string).collect())
+}
+
+/// Returns a blob of compact IPv4 addresses.
+///
+/// # Errors
+///
+/// Returns an error if an address is not an IPv4 socket address.
+#[wasm_bindgen(js_name = encodePeers)]
+pub fn encode_peers(addrs: Vec<String>) -> Result<Vec<u8>, JsError> {
+    let mut blob = Vec::with_capacity(addrs.len() * blob::V4_LEN);
+    for addr in &addrs {
+        let compact = addr
+            .parse::<CompactAddrV4>()
+            .map_err(|e| JsError::new(&format!("{}: {}", e, addr)))?;
+        blob.extend_from_slice(compact.as_bytes());
+    }
+    Ok(blob)
+}
+
+/// Returns a blob of compact IPv6 addresses.
+///
+/// # Errors
+///
+/// Returns an error if an address is not an IPv6 socket address or has a
+/// non-zero zone identifier.
+#[wasm_bindgen(js_name = encodePeers6)]
+pub fn encode_peers6(addrs: Vec<String>) -> Result<Vec<u8>, JsError> {
+    let mut blob = Vec::with_capacity(addrs.len() * blob::V6_LEN);
+    for addr in &addrs {
+        let compact = addr
+            .parse::<CompactAddrV6>()
+            .map_err(|e| JsError::new(&format!("{}: {}", e, addr)))?;
+        blob.extend_from_slice(compact.as_bytes());
+    }
+    Ok(blob)
+}
+
+/// Returns the number of whole compact IPv4 addresses in a blob.
+#[wasm_bindgen(js_name = peerCount)]
+pub fn peer_count(blob: &[u8]) -> usize {
+    blob::len_v4(blob)
+}
+
+/// Returns the number of whole compact IPv6 addresses in a blob.
+#[wasm_bindgen(js_name = peer6Count)]
+pub fn peer6_count(blob: &[u8]) -> usize {
+    blob::len_v6(blob)
+}
+
+/// Returns the compact IPv4 address at the index in a blob.
+#[wasm_bindgen(js_name = peerAt)]
+pub fn peer_at(blob: &[u8], idx: usize) -> Option<String> {
+    blob::get_v4(blob, idx).map(|addr| addr.to_string())
+}
+
+/// Returns the compact IPv6 address at the index in a blob.
+#[wasm_bindgen(js_name = peer6At)]
+pub fn peer6_at(blob: &[u8], idx: usize) -> Option<String> {
+    blob::get_v6(blob, idx).map(|addr| addr.to_string())
+}
+
+// `JsError` can only be created on wasm32 targets, so the error paths are not
+// tested natively.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(addrs: &[&str]) -> Vec<String> {
+        addrs.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn peers_round_trip() {
+        let addrs = strings(&["192.0.2.1:6881", "10.0.0.1:80"]);
+        let blob = encode_peers(addrs.clone()).unwrap();
+        assert_eq!(blob, [192, 0, 2, 1, 0x1a, 0xe1, 10, 0, 0, 1, 0, 80]);
+        assert_eq!(decode_peers(&blob).unwrap(), addrs);
+        assert!(encode_peers(Vec::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn peers6_round_trip() {
+        let addrs = strings(&["[2001:db8::1]:6881", "[::1]:80"]);
+        let blob = encode_peers6(addrs.clone()).unwrap();
+        assert_eq!(blob.len(), 2 * blob::V6_LEN);
+        assert_eq!(decode_peers6(&blob).unwrap(), addrs);
+        assert_eq!(
+            encode_peers6(strings(&["[fe80::1%0]:1"])).unwrap().len(),
+            blob::V6_LEN
+        );
+    }
+
+    #[test]
+    fn peer_count_and_at() {
+        let blob = [192, 0, 2, 1, 0x1a, 0xe1, 10, 0, 0, 1, 0, 80, 1, 2];
+        assert_eq!(peer_count(&blob), 2);
+        assert_eq!(peer_at(&blob, 1), Some("10.0.0.1:80".to_string()));
+        assert_eq!(peer_at(&blob, 2), None);
+
+        let mut blob6 = vec![0; 2 * blob::V6_LEN + 1];
+        blob6[15] = 1;
+        blob6[17] = 80;
+        assert_eq!(peer6_count(&blob6), 2);
+        assert_eq!(peer6_at(&blob6, 0), Some("[::1]:80".to_string()));
+        assert_eq!(peer6_at(&blob6, 1), Some("[::]:0".to_string()));
+        assert_eq!(peer6_at(&blob6, 2), None);
+    }
+}
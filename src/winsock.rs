@@ -0,0 +1,185 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversions between compact addresses and raw Winsock socket addresses.
+//!
+//! Compact addresses and socket address structs both store the IP address and
+//! port in network byte order, so the bytes are copied without conversion.
+//! The IPv6 flow info and scope ID are not part of a compact address and are
+//! set to zero.
+
+use crate::{CompactAddr, CompactAddrV4, CompactAddrV6};
+use std::{mem, ptr};
+use windows_sys::Win32::Networking::WinSock::{
+    AF_INET, AF_INET6, IN6_ADDR, IN6_ADDR_0, IN_ADDR, IN_ADDR_0, SOCKADDR_IN, SOCKADDR_IN6,
+    SOCKADDR_STORAGE,
+};
+
+impl From<CompactAddrV4> for SOCKADDR_IN {
+    fn from(addr: CompactAddrV4) -> Self {
+        let bytes = addr.as_bytes();
+        SOCKADDR_IN {
+            sin_family: AF_INET,
+            sin_port: u16::from_ne_bytes([bytes[4], bytes[5]]),
+            sin_addr: IN_ADDR {
+                S_un: IN_ADDR_0 {
+                    S_addr: u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                },
+            },
+            ..SOCKADDR_IN::default()
+        }
+    }
+}
+
+impl From<&SOCKADDR_IN> for CompactAddrV4 {
+    fn from(sin: &SOCKADDR_IN) -> Self {
+        // SAFETY: every variant of the address union is plain bytes.
+        let s_addr = unsafe { sin.sin_addr.S_un.S_addr };
+        let mut bytes: [u8; 6] = [0; 6];
+        bytes[..4].copy_from_slice(&s_addr.to_ne_bytes());
+        bytes[4..].copy_from_slice(&sin.sin_port.to_ne_bytes());
+        CompactAddrV4::new(bytes)
+    }
+}
+
+impl From<CompactAddrV6> for SOCKADDR_IN6 {
+    fn from(addr: CompactAddrV6) -> Self {
+        let bytes = addr.as_bytes();
+        let mut ip: [u8; 16] = [0; 16];
+        ip.copy_from_slice(&bytes[..16]);
+        SOCKADDR_IN6 {
+            sin6_family: AF_INET6,
+            sin6_port: u16::from_ne_bytes([bytes[16], bytes[17]]),
+            sin6_addr: IN6_ADDR {
+                u: IN6_ADDR_0 { Byte: ip },
+            },
+            ..SOCKADDR_IN6::default()
+        }
+    }
+}
+
+impl From<&SOCKADDR_IN6> for CompactAddrV6 {
+    fn from(sin6: &SOCKADDR_IN6) -> Self {
+        // SAFETY: every variant of the address union is plain bytes.
+        let ip = unsafe { sin6.sin6_addr.u.Byte };
+        let mut bytes: [u8; 18] = [0; 18];
+        bytes[..16].copy_from_slice(&ip);
+        bytes[16..].copy_from_slice(&sin6.sin6_port.to_ne_bytes());
+        CompactAddrV6::new(bytes)
+    }
+}
+
+impl CompactAddr {
+    /// Returns the socket address as a `SOCKADDR_STORAGE` and its length.
+    pub fn to_winsock_storage(&self) -> (SOCKADDR_STORAGE, i32) {
+        let mut storage = SOCKADDR_STORAGE::default();
+        let len = match self {
+            CompactAddr::V4(addr) => {
+                // SAFETY: SOCKADDR_STORAGE is large enough and suitably
+                // aligned for any socket address.
+                unsafe {
+                    ptr::write(
+                        (&mut storage as *mut SOCKADDR_STORAGE).cast::<SOCKADDR_IN>(),
+                        SOCKADDR_IN::from(*addr),
+                    );
+                }
+                mem::size_of::<SOCKADDR_IN>()
+            }
+            CompactAddr::V6(addr) => {
+                // SAFETY: SOCKADDR_STORAGE is large enough and suitably
+                // aligned for any socket address.
+                unsafe {
+                    ptr::write(
+                        (&mut storage as *mut SOCKADDR_STORAGE).cast::<SOCKADDR_IN6>(),
+                        SOCKADDR_IN6::from(*addr),
+                    );
+                }
+                mem::size_of::<SOCKADDR_IN6>()
+            }
+        };
+        (storage, len as i32)
+    }
+
+    /// Instantiates a compact address from a `SOCKADDR_STORAGE`.
+    ///
+    /// Returns `None` if the address family is not `AF_INET` or `AF_INET6`.
+    pub fn from_winsock_storage(storage: &SOCKADDR_STORAGE) -> Option<Self> {
+        match storage.ss_family {
+            AF_INET => {
+                // SAFETY: the family is AF_INET so the storage holds a
+                // SOCKADDR_IN.
+                let sin = unsafe { &*(storage as *const SOCKADDR_STORAGE).cast::<SOCKADDR_IN>() };
+                Some(CompactAddr::V4(CompactAddrV4::from(sin)))
+            }
+            AF_INET6 => {
+                // SAFETY: the family is AF_INET6 so the storage holds a
+                // SOCKADDR_IN6.
+                let sin6 = unsafe { &*(storage as *const SOCKADDR_STORAGE).cast::<SOCKADDR_IN6>() };
+                Some(CompactAddr::V6(CompactAddrV6::from(sin6)))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use windows_sys::Win32::Networking::WinSock::AF_UNIX;
+
+    #[test]
+    fn sockaddr_in_round_trip() {
+        let addr = CompactAddrV4::from([192, 0, 2, 1, 0x1a, 0xe1]);
+        let sin = SOCKADDR_IN::from(addr);
+        assert_eq!(sin.sin_family, AF_INET);
+        assert_eq!(sin.sin_port, 6881u16.to_be());
+        // SAFETY: every variant of the address union is plain bytes.
+        assert_eq!(
+            unsafe { sin.sin_addr.S_un.S_addr }.to_ne_bytes(),
+            [192, 0, 2, 1]
+        );
+        assert_eq!(CompactAddrV4::from(&sin), addr);
+    }
+
+    #[test]
+    fn sockaddr_in6_round_trip() {
+        let mut bytes = [0; 18];
+        bytes[0] = 0x20;
+        bytes[1] = 0x01;
+        bytes[15] = 1;
+        bytes[17] = 80;
+        let addr = CompactAddrV6::from(bytes);
+        let sin6 = SOCKADDR_IN6::from(addr);
+        assert_eq!(sin6.sin6_family, AF_INET6);
+        assert_eq!(sin6.sin6_port, 80u16.to_be());
+        assert_eq!(sin6.sin6_flowinfo, 0);
+        assert_eq!(CompactAddrV6::from(&sin6), addr);
+    }
+
+    #[test]
+    fn storage_round_trip() {
+        for addr in ["1.2.3.4:80", "[2001:db8::1]:443"] {
+            let addr: CompactAddr = addr.parse().unwrap();
+            let (storage, len) = addr.to_winsock_storage();
+            let expected = match addr {
+                CompactAddr::V4(_) => mem::size_of::<SOCKADDR_IN>(),
+                CompactAddr::V6(_) => mem::size_of::<SOCKADDR_IN6>(),
+            };
+            assert_eq!(len as usize, expected);
+            assert_eq!(CompactAddr::from_winsock_storage(&storage), Some(addr));
+        }
+    }
+
+    #[test]
+    fn storage_unknown_family() {
+        let mut storage = SOCKADDR_STORAGE::default();
+        assert_eq!(CompactAddr::from_winsock_storage(&storage), None);
+        storage.ss_family = AF_UNIX;
+        assert_eq!(CompactAddr::from_winsock_storage(&storage), None);
+    }
+}
@@ -0,0 +1,518 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Shared traits for wire formats.
+//!
+//! [`Encode`] appends a value's encoding to a buffer. [`Decode`] reads a value
+//! from a [`Reader`], a cursor over borrowed bytes, so values of different
+//! formats can be composed in one message. Each format keeps its own error
+//! type.
+//!
+//! | Type                                  | Encoding                        | Traits             |
+//! |---------------------------------------|---------------------------------|--------------------|
+//! | [`CompactAddrV4`], [`CompactAddrV6`]  | Compact address                 | `Encode`, `Decode` |
+//! | [`CompactAddr`]                       | [Tagged](crate::tagged) address | `Encode`, `Decode` |
+//! | [`CompactEndpoint`]                   | Endpoint                        | `Encode`, `Decode` |
+//! | [`HolepunchMessage`]                  | `ut_holepunch` message          | `Encode`, `Decode` |
+//! | [`VuzeContact`]                       | Vuze DHT contact                | `Encode`, `Decode` |
+//! | [`NodeInfo`]                          | Compact node info               | `Encode`           |
+//! | [`SocksAddr`]                         | SOCKS5 address                  | `Decode`           |
+//!
+//! Compact node info does not identify its address family, and a SOCKS5
+//! domain name may be too long to encode, so those types only implement one
+//! of the traits.
+//!
+//! [`CompactEndpoint`]: crate::endpoint::CompactEndpoint
+//! [`HolepunchMessage`]: crate::holepunch::HolepunchMessage
+//! [`VuzeContact`]: crate::vuze::VuzeContact
+//! [`NodeInfo`]: crate::node::NodeInfo
+//! [`SocksAddr`]: crate::socks::SocksAddr
+
+use crate::{
+    blob::{V4_LEN, V6_LEN},
+    endpoint::{self, CompactEndpoint},
+    holepunch::{HolepunchError, HolepunchMessage},
+    node::NodeInfo,
+    socks::{SocksAddr, SocksError},
+    tagged,
+    vuze::{VuzeContact, VuzeError},
+    CompactAddr, CompactAddrV4, CompactAddrV6,
+};
+use std::{error, fmt};
+
+/// A value with a wire encoding.
+pub trait Encode {
+    /// Returns the length of the encoding.
+    fn encoded_len(&self) -> usize;
+
+    /// Appends the encoding to the buffer.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Returns the encoding.
+    fn to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.encode(&mut buf);
+        buf
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encoded_len(&self) -> usize {
+        self.iter().map(Encode::encoded_len).sum()
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        for value in self {
+            value.encode(buf);
+        }
+    }
+}
+
+/// A value which can be decoded from its wire encoding.
+pub trait Decode: Sized {
+    /// The error when decoding.
+    type Error;
+
+    /// Decodes a value from the reader.
+    ///
+    /// The reader is advanced past the value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes are not a valid encoding. The reader's
+    /// position is unspecified after an error.
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, Self::Error>;
+
+    /// Decodes a value from the start of the bytes.
+    ///
+    /// Returns the value and the remaining bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes do not start with a valid encoding.
+    fn decode_prefix(bytes: &[u8]) -> Result<(Self, &[u8]), Self::Error> {
+        let mut reader = Reader::new(bytes);
+        let value = Self::decode(&mut reader)?;
+        Ok((value, reader.remaining()))
+    }
+}
+
+/// A cursor over borrowed bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Instantiates a reader at the start of the bytes.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Returns the number of bytes read.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the bytes which have not been read.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+
+    /// Returns true if all of the bytes have been read.
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    /// Reads a byte.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are no remaining bytes.
+    pub fn read_u8(&mut self) -> Result<u8, ReadError> {
+        self.read_array::<1>().map(|[b]| b)
+    }
+
+    /// Reads a big-endian `u16`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are fewer than 2 remaining bytes.
+    pub fn read_u16(&mut self) -> Result<u16, ReadError> {
+        self.read_array().map(u16::from_be_bytes)
+    }
+
+    /// Reads a big-endian `u32`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are fewer than 4 remaining bytes.
+    pub fn read_u32(&mut self) -> Result<u32, ReadError> {
+        self.read_array().map(u32::from_be_bytes)
+    }
+
+    /// Reads `N` bytes into an array.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are fewer than `N` remaining bytes.
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], ReadError> {
+        let mut array: [u8; N] = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+
+    /// Reads `len` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are fewer than `len` remaining bytes.
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], ReadError> {
+        let remaining = self.remaining();
+        if remaining.len() < len {
+            return Err(ReadError {
+                expected: len,
+                actual: remaining.len(),
+            });
+        }
+        self.pos += len;
+        Ok(&remaining[..len])
+    }
+
+    /// Reads all of the remaining bytes.
+    pub fn read_remaining(&mut self) -> &'a [u8] {
+        let remaining = self.remaining();
+        self.pos = self.bytes.len();
+        remaining
+    }
+
+    /// Decodes a value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the remaining bytes do not start with a valid
+    /// encoding.
+    pub fn decode<T: Decode>(&mut self) -> Result<T, T::Error> {
+        T::decode(self)
+    }
+
+    /// Decodes with a function which returns the value and the bytes after it.
+    fn decode_with<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&'a [u8]) -> Result<(T, &'a [u8]), E>,
+    {
+        let remaining = self.remaining();
+        let (value, rest) = f(remaining)?;
+        self.pos += remaining.len() - rest.len();
+        Ok(value)
+    }
+}
+
+/// An error when the reader has fewer bytes than required.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadError {
+    expected: usize,
+    actual: usize,
+}
+
+impl ReadError {
+    /// Returns the number of bytes required.
+    pub fn expected(&self) -> usize {
+        self.expected
+    }
+
+    /// Returns the number of remaining bytes.
+    pub fn actual(&self) -> usize {
+        self.actual
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unexpected end of input: expected {} bytes, found {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl error::Error for ReadError {}
+
+impl Encode for CompactAddrV4 {
+    fn encoded_len(&self) -> usize {
+        V4_LEN
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Decode for CompactAddrV4 {
+    type Error = ReadError;
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, Self::Error> {
+        reader.read_array().map(CompactAddrV4::new)
+    }
+}
+
+impl Encode for CompactAddrV6 {
+    fn encoded_len(&self) -> usize {
+        V6_LEN
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Decode for CompactAddrV6 {
+    type Error = ReadError;
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, Self::Error> {
+        reader.read_array().map(CompactAddrV6::new)
+    }
+}
+
+impl Encode for CompactAddr {
+    fn encoded_len(&self) -> usize {
+        self.tagged_len()
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.encode_tagged(buf);
+    }
+}
+
+impl Decode for CompactAddr {
+    type Error = tagged::DecodeError;
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, Self::Error> {
+        reader.decode_with(CompactAddr::decode_tagged)
+    }
+}
+
+impl Encode for CompactEndpoint {
+    fn encoded_len(&self) -> usize {
+        CompactEndpoint::encoded_len(self)
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        CompactEndpoint::encode(self, buf);
+    }
+}
+
+impl Decode for CompactEndpoint {
+    type Error = endpoint::DecodeError;
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, Self::Error> {
+        reader.decode_with(CompactEndpoint::decode)
+    }
+}
+
+impl Encode for HolepunchMessage {
+    fn encoded_len(&self) -> usize {
+        HolepunchMessage::encoded_len(self)
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        HolepunchMessage::encode(self, buf);
+    }
+}
+
+impl Decode for HolepunchMessage {
+    type Error = HolepunchError;
+
+    /// Decodes a message from all of the remaining bytes.
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, HolepunchError> {
+        HolepunchMessage::decode(reader.read_remaining())
+    }
+}
+
+impl Encode for VuzeContact {
+    fn encoded_len(&self) -> usize {
+        VuzeContact::encoded_len(self)
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        VuzeContact::encode(self, buf);
+    }
+}
+
+impl Decode for VuzeContact {
+    type Error = VuzeError;
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, Self::Error> {
+        reader.decode_with(VuzeContact::decode)
+    }
+}
+
+impl Encode for NodeInfo {
+    fn encoded_len(&self) -> usize {
+        NodeInfo::encoded_len(self)
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        NodeInfo::encode(self, buf);
+    }
+}
+
+impl Decode for SocksAddr {
+    type Error = SocksError;
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, Self::Error> {
+        reader.decode_with(SocksAddr::decode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::endpoint::Transport;
+    use crate::test_util::addr;
+
+    #[test]
+    fn reader_reads_integers() {
+        let mut reader = Reader::new(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        assert_eq!(reader.read_u8(), Ok(0x01));
+        assert_eq!(reader.read_u16(), Ok(0x0203));
+        assert_eq!(reader.read_u32(), Ok(0x0405_0607));
+        assert_eq!(reader.position(), 7);
+        assert_eq!(reader.remaining(), &[0x08]);
+        assert!(!reader.is_empty());
+        assert_eq!(reader.read_remaining(), &[0x08]);
+        assert!(reader.is_empty());
+        assert_eq!(reader.read_remaining(), &[] as &[u8]);
+    }
+
+    #[test]
+    fn reader_short_read() {
+        let mut reader = Reader::new(&[0x01, 0x02, 0x03]);
+        let err = reader.read_u32().unwrap_err();
+        assert_eq!(err.expected(), 4);
+        assert_eq!(err.actual(), 3);
+        assert_eq!(
+            err.to_string(),
+            "unexpected end of input: expected 4 bytes, found 3"
+        );
+        assert_eq!(reader.position(), 0);
+        assert_eq!(reader.read_bytes(3), Ok(&[0x01, 0x02, 0x03][..]));
+        assert_eq!(
+            reader.read_u8(),
+            Err(ReadError {
+                expected: 1,
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn compact_addr_round_trip() {
+        let v4 = CompactAddrV4::from([127, 0, 0, 1, 0x1a, 0xe1]);
+        assert_eq!(v4.encoded_len(), V4_LEN);
+        assert_eq!(v4.to_vec(), v4.as_bytes());
+        assert_eq!(
+            CompactAddrV4::decode_prefix(&v4.to_vec()),
+            Ok((v4, &[][..]))
+        );
+
+        let v6 = CompactAddrV6::from([1; 18]);
+        assert_eq!(v6.encoded_len(), V6_LEN);
+        assert_eq!(
+            CompactAddrV6::decode_prefix(&v6.to_vec()),
+            Ok((v6, &[][..]))
+        );
+
+        assert!(CompactAddrV6::decode_prefix(&[1; 17]).is_err());
+    }
+
+    #[test]
+    fn tagged_round_trip() {
+        for value in [addr("192.0.2.1:6881"), addr("[2001:db8::1]:6881")] {
+            let bytes = value.to_vec();
+            assert_eq!(bytes.len(), value.encoded_len());
+            assert_eq!(CompactAddr::decode_prefix(&bytes), Ok((value, &[][..])));
+        }
+        assert!(CompactAddr::decode_prefix(&[]).is_err());
+    }
+
+    #[test]
+    fn decode_prefix_returns_rest() {
+        let mut bytes = addr("192.0.2.1:6881").to_vec();
+        bytes.extend_from_slice(&[0xff, 0xee]);
+        let (value, rest) = CompactAddr::decode_prefix(&bytes).unwrap();
+        assert_eq!(value, addr("192.0.2.1:6881"));
+        assert_eq!(rest, &[0xff, 0xee]);
+    }
+
+    #[test]
+    fn composed_message() {
+        let v4 = CompactAddrV4::from([10, 0, 0, 1, 0, 80]);
+        let endpoint = CompactEndpoint::new(addr("[2001:db8::2]:443"), Transport::Tcp);
+        let contact = VuzeContact::new(addr("192.0.2.3:6881"), 5);
+
+        let mut buf = Vec::new();
+        v4.encode(&mut buf);
+        Encode::encode(&endpoint, &mut buf);
+        Encode::encode(&contact, &mut buf);
+        assert_eq!(
+            buf.len(),
+            v4.encoded_len() + Encode::encoded_len(&endpoint) + Encode::encoded_len(&contact)
+        );
+
+        let mut reader = Reader::new(&buf);
+        assert_eq!(reader.decode::<CompactAddrV4>(), Ok(v4));
+        assert_eq!(reader.decode::<CompactEndpoint>(), Ok(endpoint));
+        assert_eq!(reader.decode::<VuzeContact>(), Ok(contact));
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn slice_encode() {
+        let values = [
+            CompactAddrV4::from([10, 0, 0, 1, 0, 1]),
+            CompactAddrV4::from([10, 0, 0, 2, 0, 2]),
+        ];
+        assert_eq!(Encode::encoded_len(&values[..]), 2 * V4_LEN);
+        assert_eq!(
+            Encode::to_vec(&values[..]),
+            [10, 0, 0, 1, 0, 1, 10, 0, 0, 2, 0, 2]
+        );
+        assert!(<[CompactAddrV4]>::to_vec(&[]).is_empty());
+    }
+
+    #[test]
+    fn holepunch_reads_remaining() {
+        let message = HolepunchMessage::Connect(addr("192.0.2.1:6881"));
+        let bytes = Encode::to_vec(&message);
+        let mut reader = Reader::new(&bytes);
+        assert_eq!(reader.decode::<HolepunchMessage>(), Ok(message));
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn node_info_matches_inherent_encoding() {
+        let node = NodeInfo::new([7; 20], addr("192.0.2.1:6881"));
+        let mut expected = Vec::new();
+        NodeInfo::encode(&node, &mut expected);
+        assert_eq!(Encode::to_vec(&node), expected);
+    }
+
+    #[test]
+    fn socks_decode() {
+        let value = SocksAddr::Domain("example.com".to_string(), 1080);
+        let mut bytes = Vec::new();
+        value.encode(&mut bytes).unwrap();
+        bytes.push(0xff);
+        let mut reader = Reader::new(&bytes);
+        assert_eq!(reader.decode::<SocksAddr>(), Ok(value));
+        assert_eq!(reader.remaining(), &[0xff]);
+    }
+}
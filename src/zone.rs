@@ -0,0 +1,190 @@
+// Copyright 2021 Bryant Luk
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Parsing of IPv6 socket addresses with a zone identifier.
+//!
+//! A link-local address may have a zone identifier which selects the network
+//! interface such as `[fe80::1%3]:6881`. A numeric zone is the scope ID. An
+//! interface name such as `[fe80::1%eth0]:6881` is resolved to its index if
+//! the `libc` feature is enabled on Unix platforms.
+//!
+//! Compact addresses cannot store the scope ID. Use the extended format's
+//! scoped IPv6 addresses to keep it.
+
+use crate::{ParseAddrError, ParseAddrErrorKind};
+use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6};
+
+/// Parses an IPv6 socket address with an optional zone identifier.
+///
+/// # Errors
+///
+/// Returns an error if the text is not an IPv6 socket address, the zone is
+/// invalid, or an interface name cannot be resolved.
+pub fn parse_v6(s: &str) -> Result<SocketAddrV6, ParseAddrError> {
+    let pct = match s.find('%') {
+        Some(pct) => pct,
+        None => {
+            return s
+                .parse::<SocketAddrV6>()
+                .map_err(|_| ParseAddrError::new(ParseAddrErrorKind::InvalidAddr))
+        }
+    };
+
+    let invalid = || ParseAddrError::new(ParseAddrErrorKind::InvalidAddr);
+    let ip = s[..pct].strip_prefix('[').ok_or_else(invalid)?;
+    let (zone, port) = s[pct + 1..].split_once(']').ok_or_else(invalid)?;
+    let ip = ip.parse::<Ipv6Addr>().map_err(|_| invalid())?;
+    let port = port
+        .strip_prefix(':')
+        .and_then(|port| port.parse::<u16>().ok())
+        .ok_or_else(invalid)?;
+    let scope_id = parse_zone(zone)?;
+
+    Ok(SocketAddrV6::new(ip, port, 0, scope_id))
+}
+
+/// Parses a socket address where an IPv6 address may have a zone identifier.
+///
+/// # Errors
+///
+/// Returns an error if the text is not a socket address, the zone is invalid,
+/// or an interface name cannot be resolved.
+pub fn parse(s: &str) -> Result<SocketAddr, ParseAddrError> {
+    if s.starts_with('[') {
+        parse_v6(s).map(SocketAddr::V6)
+    } else {
+        s.parse::<SocketAddr>()
+            .map_err(|_| ParseAddrError::new(ParseAddrErrorKind::InvalidAddr))
+    }
+}
+
+/// Returns the scope ID for the zone identifier.
+///
+/// # Errors
+///
+/// Returns an error if the zone is empty, the numeric zone overflows, or an
+/// interface name cannot be resolved.
+pub fn parse_zone(zone: &str) -> Result<u32, ParseAddrError> {
+    if zone.is_empty() {
+        return Err(ParseAddrError::new(ParseAddrErrorKind::InvalidZone));
+    }
+    if zone.bytes().all(|b| b.is_ascii_digit()) {
+        return zone
+            .parse::<u32>()
+            .map_err(|_| ParseAddrError::new(ParseAddrErrorKind::InvalidZone));
+    }
+    resolve_interface(zone)
+}
+
+#[cfg(all(feature = "libc", unix))]
+fn resolve_interface(name: &str) -> Result<u32, ParseAddrError> {
+    crate::sockaddr::interface_index(name)
+        .ok_or_else(|| ParseAddrError::new(ParseAddrErrorKind::UnknownInterface))
+}
+
+#[cfg(not(all(feature = "libc", unix)))]
+fn resolve_interface(_name: &str) -> Result<u32, ParseAddrError> {
+    Err(ParseAddrError::new(ParseAddrErrorKind::NamedZone))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(result: Result<SocketAddrV6, ParseAddrError>) -> ParseAddrErrorKind {
+        result.unwrap_err().kind()
+    }
+
+    #[test]
+    fn parse_numeric_zone() {
+        let addr = parse_v6("[fe80::1%3]:6881").unwrap();
+        assert_eq!(*addr.ip(), "fe80::1".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(addr.port(), 6881);
+        assert_eq!(addr.scope_id(), 3);
+        assert_eq!(addr.flowinfo(), 0);
+        assert_eq!(parse_v6("[fe80::1%0]:1").unwrap().scope_id(), 0);
+        assert_eq!(
+            parse_v6("[fe80::1%4294967295]:1").unwrap().scope_id(),
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn parse_without_zone() {
+        let addr = parse_v6("[2001:db8::1]:443").unwrap();
+        assert_eq!(addr.scope_id(), 0);
+        assert_eq!(addr.port(), 443);
+        assert_eq!(
+            kind(parse_v6("192.0.2.1:80")),
+            ParseAddrErrorKind::InvalidAddr
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for s in [
+            "fe80::1%3:6881",
+            "[fe80::1%3:6881",
+            "[fe80::1%3]",
+            "[fe80::1%3]6881",
+            "[fe80::1%3]:65536",
+            "[192.0.2.1%3]:6881",
+        ] {
+            assert_eq!(kind(parse_v6(s)), ParseAddrErrorKind::InvalidAddr, "{}", s);
+        }
+    }
+
+    #[test]
+    fn parse_invalid_zone() {
+        assert_eq!(
+            kind(parse_v6("[fe80::1%]:6881")),
+            ParseAddrErrorKind::InvalidZone
+        );
+        assert_eq!(
+            kind(parse_v6("[fe80::1%4294967296]:6881")),
+            ParseAddrErrorKind::InvalidZone
+        );
+    }
+
+    #[test]
+    fn parse_either_family() {
+        assert_eq!(
+            parse("192.0.2.1:6881").unwrap(),
+            "192.0.2.1:6881".parse::<SocketAddr>().unwrap()
+        );
+        match parse("[fe80::1%7]:6881").unwrap() {
+            SocketAddr::V6(addr) => assert_eq!(addr.scope_id(), 7),
+            SocketAddr::V4(_) => panic!("expected an IPv6 address"),
+        }
+        assert_eq!(
+            parse("fe80::1").unwrap_err().kind(),
+            ParseAddrErrorKind::InvalidAddr
+        );
+    }
+
+    #[cfg(all(feature = "libc", target_os = "linux"))]
+    #[test]
+    fn parse_interface_name() {
+        let addr = parse_v6("[fe80::1%lo]:6881").unwrap();
+        assert_ne!(addr.scope_id(), 0);
+        assert_eq!(parse_zone("lo"), Ok(addr.scope_id()));
+    }
+
+    #[test]
+    fn parse_unknown_interface_name() {
+        let expected = if cfg!(all(feature = "libc", unix)) {
+            ParseAddrErrorKind::UnknownInterface
+        } else {
+            ParseAddrErrorKind::NamedZone
+        };
+        assert_eq!(
+            parse_zone("no-such-interface0").unwrap_err().kind(),
+            expected
+        );
+    }
+}